@@ -5,15 +5,20 @@
 
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::any::Any;
 use std::collections::VecDeque;
 
 use crate::allocator::thread::ThreadBuilder;
 use crate::allocator::process::ProcessBuilder as TypedProcessBuilder;
 use crate::allocator::{Allocate, AllocateBuilder, Event, Thread, Process};
+use crate::allocator::simulated::{Simulated, SimulatedCluster};
+use crate::placement::ThreadPlacement;
 use crate::allocator::zero_copy::allocator_process::{ProcessBuilder, ProcessAllocator};
 use crate::allocator::zero_copy::allocator::{TcpBuilder, TcpAllocator};
+use crate::allocator::zero_copy::compression::Compression;
 
-use crate::{Push, Pull, Data, Message};
+use crate::{Push, Pull, Message};
+use crate::codec::Codec;
 
 /// Enumerates known implementors of `Allocate`.
 /// Passes trait method calls on to members.
@@ -26,6 +31,8 @@ pub enum Generic {
     ProcessBinary(ProcessAllocator),
     /// Inter-process allocator.
     ZeroCopy(TcpAllocator<Process>),
+    /// Simulated inter-process allocator.
+    Simulated(Simulated),
 }
 
 impl Generic {
@@ -36,6 +43,7 @@ impl Generic {
             &Generic::Process(ref p) => p.index(),
             &Generic::ProcessBinary(ref pb) => pb.index(),
             &Generic::ZeroCopy(ref z) => z.index(),
+            &Generic::Simulated(ref s) => s.index(),
         }
     }
     /// The number of workers.
@@ -45,15 +53,17 @@ impl Generic {
             &Generic::Process(ref p) => p.peers(),
             &Generic::ProcessBinary(ref pb) => pb.peers(),
             &Generic::ZeroCopy(ref z) => z.peers(),
+            &Generic::Simulated(ref s) => s.peers(),
         }
     }
-    /// Constructs several send endpoints and one receive endpoint.
-    fn allocate<T: Data>(&mut self, identifier: usize) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+    /// Constructs several send endpoints and one receive endpoint, serializing with `codec`.
+    fn allocate_with_codec<T: Send+Sync+Any+'static, C: Codec<T>>(&mut self, identifier: usize, codec: C) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
         match self {
-            &mut Generic::Thread(ref mut t) => t.allocate(identifier),
-            &mut Generic::Process(ref mut p) => p.allocate(identifier),
-            &mut Generic::ProcessBinary(ref mut pb) => pb.allocate(identifier),
-            &mut Generic::ZeroCopy(ref mut z) => z.allocate(identifier),
+            &mut Generic::Thread(ref mut t) => t.allocate_with_codec(identifier, codec),
+            &mut Generic::Process(ref mut p) => p.allocate_with_codec(identifier, codec),
+            &mut Generic::ProcessBinary(ref mut pb) => pb.allocate_with_codec(identifier, codec),
+            &mut Generic::ZeroCopy(ref mut z) => z.allocate_with_codec(identifier, codec),
+            &mut Generic::Simulated(ref mut s) => s.allocate_with_codec(identifier, codec),
         }
     }
     /// Perform work before scheduling operators.
@@ -63,6 +73,7 @@ impl Generic {
             &mut Generic::Process(ref mut p) => p.receive(),
             &mut Generic::ProcessBinary(ref mut pb) => pb.receive(),
             &mut Generic::ZeroCopy(ref mut z) => z.receive(),
+            &mut Generic::Simulated(ref mut s) => s.receive(),
         }
     }
     /// Perform work after scheduling operators.
@@ -72,6 +83,7 @@ impl Generic {
             &mut Generic::Process(ref mut p) => p.release(),
             &mut Generic::ProcessBinary(ref mut pb) => pb.release(),
             &mut Generic::ZeroCopy(ref mut z) => z.release(),
+            &mut Generic::Simulated(ref mut s) => s.release(),
         }
     }
     fn events(&self) -> &Rc<RefCell<VecDeque<(usize, Event)>>> {
@@ -80,6 +92,7 @@ impl Generic {
             &Generic::Process(ref p) => p.events(),
             &Generic::ProcessBinary(ref pb) => pb.events(),
             &Generic::ZeroCopy(ref z) => z.events(),
+            &Generic::Simulated(ref s) => s.events(),
         }
     }
 }
@@ -87,19 +100,31 @@ impl Generic {
 impl Allocate for Generic {
     fn index(&self) -> usize { self.index() }
     fn peers(&self) -> usize { self.peers() }
-    fn allocate<T: Data>(&mut self, identifier: usize) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
-        self.allocate(identifier)
+    fn allocate_with_codec<T: Send+Sync+Any+'static, C: Codec<T>>(&mut self, identifier: usize, codec: C) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate_with_codec(identifier, codec)
     }
 
     fn receive(&mut self) { self.receive(); }
     fn release(&mut self) { self.release(); }
     fn events(&self) -> &Rc<RefCell<VecDeque<(usize, Event)>>> { self.events() }
+    fn metrics(&self) -> Option<&crate::metrics::Metrics> {
+        match self {
+            &Generic::ZeroCopy(ref z) => z.metrics(),
+            _ => None,
+        }
+    }
+    fn set_channel_compression(&mut self, identifier: usize, compression: Compression) {
+        if let &mut Generic::ZeroCopy(ref mut z) = self {
+            z.set_channel_compression(identifier, compression);
+        }
+    }
     fn await_events(&self, _duration: Option<std::time::Duration>) {
         match self {
             &Generic::Thread(ref t) => t.await_events(_duration),
             &Generic::Process(ref p) => p.await_events(_duration),
             &Generic::ProcessBinary(ref pb) => pb.await_events(_duration),
             &Generic::ZeroCopy(ref z) => z.await_events(_duration),
+            &Generic::Simulated(ref s) => s.await_events(_duration),
         }
     }
 }
@@ -119,6 +144,10 @@ pub enum GenericBuilder {
     ProcessBinary(ProcessBuilder),
     /// Builder for `ZeroCopy` allocator.
     ZeroCopy(TcpBuilder<TypedProcessBuilder>),
+    /// Builder for `Simulated` allocator.
+    Simulated(SimulatedCluster),
+    /// Builder for another allocator, which first places its thread on a core.
+    Placed(Box<GenericBuilder>, ThreadPlacement),
 }
 
 impl AllocateBuilder for GenericBuilder {
@@ -129,6 +158,11 @@ impl AllocateBuilder for GenericBuilder {
             GenericBuilder::Process(p) => Generic::Process(p.build()),
             GenericBuilder::ProcessBinary(pb) => Generic::ProcessBinary(pb.build()),
             GenericBuilder::ZeroCopy(z) => Generic::ZeroCopy(z.build()),
+            GenericBuilder::Simulated(s) => Generic::Simulated(s.build()),
+            GenericBuilder::Placed(builder, placement) => {
+                placement.apply();
+                builder.build()
+            },
         }
     }
 }
@@ -2,16 +2,19 @@
 
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::any::Any;
 use std::time::Duration;
 use std::collections::VecDeque;
 
 pub use self::thread::Thread;
 pub use self::process::Process;
 pub use self::generic::{Generic, GenericBuilder};
+pub use self::simulated::Simulated;
 
 pub mod thread;
 pub mod process;
 pub mod generic;
+pub mod simulated;
 
 pub mod canary;
 pub mod counters;
@@ -19,6 +22,7 @@ pub mod counters;
 pub mod zero_copy;
 
 use crate::{Data, Push, Pull, Message};
+use crate::codec::{Codec, DefaultCodec};
 
 /// A proto-allocator, which implements `Send` and can be completed with `build`.
 ///
@@ -43,7 +47,16 @@ pub trait Allocate {
     /// The number of workers in the communication group.
     fn peers(&self) -> usize;
     /// Constructs several send endpoints and one receive endpoint.
-    fn allocate<T: Data>(&mut self, identifier: usize) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>);
+    ///
+    /// Channels that serialize their contents use `DefaultCodec`.
+    fn allocate<T: Data>(&mut self, identifier: usize) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate_with_codec(identifier, DefaultCodec::default())
+    }
+    /// Constructs several send endpoints and one receive endpoint, serializing with `codec`.
+    ///
+    /// The codec is only used by channels that serialize their contents, and all workers
+    /// must supply the same codec for the same channel identifier.
+    fn allocate_with_codec<T: Send+Sync+Any+'static, C: Codec<T>>(&mut self, identifier: usize, codec: C) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>);
     /// A shared queue of communication events with channel identifier.
     ///
     /// It is expected that users of the channel allocator will regularly
@@ -60,6 +73,17 @@ pub trait Allocate {
     /// good implementations should use this as a hint to park the thread.
     fn await_events(&self, _duration: Option<Duration>) { }
 
+    /// Communication metrics of the process, if the allocator maintains them.
+    ///
+    /// Only allocators that serialize data between processes maintain metrics.
+    fn metrics(&self) -> Option<&crate::metrics::Metrics> { None }
+
+    /// Sets the compression of payloads sent on channel `identifier` to other processes.
+    ///
+    /// This overrides the configured default, and must be called before the channel is allocated.
+    /// Allocators that do not send data between processes ignore this setting.
+    fn set_channel_compression(&mut self, _identifier: usize, _compression: zero_copy::compression::Compression) { }
+
     /// Ensure that received messages are surfaced in each channel.
     ///
     /// This method should be called to ensure that received messages are
@@ -0,0 +1,525 @@
+//! A deterministic, in-process simulation of a cluster of processes.
+//!
+//! The `Simulated` allocator runs the workers of several virtual processes as threads of one
+//! process. All messages between workers pass through a simulated network, which delays messages
+//! between virtual processes and holds them back during partitions.
+//!
+//! Workers advance in lockstep: each call to `Allocate::receive` is a *tick*, which waits until
+//! all workers still running have reached the same tick. A message sent during tick `t` with a
+//! delay of `d` ticks is delivered by the receiving worker's call to `receive` for tick
+//! `t + d + 1`, and messages delivered at the same tick are ordered by their due tick, sending
+//! worker, and sequence number. Messages within a virtual process have no delay, and the delays
+//! of others are drawn from a random number generator seeded by the configured seed, the channel
+//! identifier, and the sending and receiving worker. As a result, the same program and seed
+//! deliver the same messages in the same order at the same ticks, independent of thread
+//! scheduling. Each point-to-point channel remains FIFO, as the progress protocol requires, and
+//! no message is ever dropped.
+//!
+//! Workers must keep calling `receive` until they drop their allocator, and they never park, as
+//! other workers may be waiting for their next tick.
+//!
+//! # Examples
+//! ```
+//! use timely_communication::Allocate;
+//! use timely_communication::allocator::simulated::SimulationConfig;
+//!
+//! // two virtual processes of two workers, with messages between them delayed by up to
+//! // eight ticks, and process zero cut off from process one between ticks 10 and 20.
+//! let config = SimulationConfig::new(2, 2, 0x5eed)
+//!     .latency(1, 8)
+//!     .partition(vec![0], 10, 20);
+//!
+//! let guards = timely_communication::initialize(timely_communication::Configuration::Simulated(config), |mut allocator| {
+//!
+//!     let (mut senders, mut receiver) = allocator.allocate(0);
+//!
+//!     use timely_communication::Message;
+//!     for (index, sender) in senders.iter_mut().enumerate() {
+//!         sender.send(Message::from_typed(format!("hello, {}", index)));
+//!         sender.done();
+//!     }
+//!
+//!     let mut expecting = allocator.peers();
+//!     while expecting > 0 {
+//!         allocator.receive();
+//!         if receiver.recv().is_some() {
+//!             expecting -= 1;
+//!         }
+//!         allocator.release();
+//!     }
+//!
+//!     allocator.index()
+//! });
+//!
+//! assert!(guards.unwrap().join().into_iter().all(|result| result.is_ok()));
+//! ```
+
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::sync::{Arc, Condvar, Mutex};
+use std::any::Any;
+use std::time::Duration;
+use std::collections::{HashMap, VecDeque};
+
+use crate::allocator::process::ProcessBuilder;
+use crate::allocator::{Allocate, AllocateBuilder, Event, Process};
+use crate::{Push, Pull, Message};
+use crate::codec::Codec;
+
+/// A period during which some virtual processes are cut off from the others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Partition {
+    /// The virtual processes on one side of the partition.
+    pub processes: Vec<usize>,
+    /// The tick at which the partition starts.
+    pub start: u64,
+    /// The tick at which the partition heals.
+    pub end: u64,
+}
+
+impl Partition {
+    /// Indicates whether the partition separates processes `source` and `target`.
+    pub fn separates(&self, source: usize, target: usize) -> bool {
+        self.processes.contains(&source) != self.processes.contains(&target)
+    }
+}
+
+/// Describes a simulated cluster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulationConfig {
+    /// Number of virtual processes.
+    pub processes: usize,
+    /// Number of worker threads in each virtual process.
+    pub threads: usize,
+    /// Seed for the delays of messages.
+    pub seed: u64,
+    /// Inclusive bounds, in ticks, on the delay of messages between processes.
+    pub latency: (u64, u64),
+    /// Periods during which processes cannot communicate.
+    pub partitions: Vec<Partition>,
+}
+
+impl SimulationConfig {
+    /// A cluster of `processes` processes with `threads` workers each, without delays.
+    pub fn new(processes: usize, threads: usize, seed: u64) -> Self {
+        SimulationConfig {
+            processes,
+            threads,
+            seed,
+            latency: (0, 0),
+            partitions: Vec::new(),
+        }
+    }
+    /// Delays messages between processes by between `min` and `max` ticks, inclusive.
+    pub fn latency(mut self, min: u64, max: u64) -> Self {
+        assert!(min <= max, "minimum latency exceeds maximum latency");
+        self.latency = (min, max);
+        self
+    }
+    /// Separates `processes` from all other processes from tick `start` until tick `end`.
+    ///
+    /// Messages crossing the partition are held back until it heals.
+    pub fn partition(mut self, processes: Vec<usize>, start: u64, end: u64) -> Self {
+        self.partitions.push(Partition { processes, start, end });
+        self
+    }
+}
+
+/// State shared by all workers of a simulated cluster.
+struct Network {
+    config: SimulationConfig,
+    /// Workers still running, workers waiting for the next tick, and the number of ticks so far.
+    lockstep: Mutex<(usize, usize, u64)>,
+    condvar: Condvar,
+    /// Inboxes of each channel, by channel identifier, with the number of workers yet to allocate it.
+    channels: Mutex<Channels>,
+}
+
+/// Type-erased inboxes of channels, by channel identifier.
+type Channels = HashMap<usize, (usize, Box<dyn Any+Send>)>;
+
+impl Network {
+    /// Waits until all running workers have arrived at the next tick.
+    fn tick(&self) {
+        let mut lockstep = self.lockstep.lock().expect("lockstep mutex poisoned");
+        lockstep.1 += 1;
+        if lockstep.1 >= lockstep.0 {
+            lockstep.1 = 0;
+            lockstep.2 += 1;
+            self.condvar.notify_all();
+        }
+        else {
+            let tick = lockstep.2;
+            while lockstep.2 == tick {
+                lockstep = self.condvar.wait(lockstep).expect("lockstep mutex poisoned");
+            }
+        }
+    }
+    /// Stops waiting for a worker that has stopped.
+    fn leave(&self) {
+        // Tolerate poisoning, as we may be unwinding from a panic of another worker.
+        let mut lockstep = match self.lockstep.lock() {
+            Ok(lockstep) => lockstep,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        lockstep.0 -= 1;
+        if lockstep.1 > 0 && lockstep.1 >= lockstep.0 {
+            lockstep.1 = 0;
+            lockstep.2 += 1;
+            self.condvar.notify_all();
+        }
+    }
+}
+
+/// Messages in flight to one worker on one channel: due tick, sending worker, sequence number.
+type Inbox<T> = Arc<Mutex<Vec<(u64, usize, u64, Message<T>)>>>;
+
+/// Builds an instance of a `Simulated` allocator.
+pub struct SimulatedCluster {
+    inner: ProcessBuilder,
+    network: Arc<Network>,
+}
+
+impl AllocateBuilder for SimulatedCluster {
+    type Allocator = Simulated;
+    fn build(self) -> Simulated {
+        Simulated {
+            inner: self.inner.build(),
+            network: self.network,
+            clock: Rc::new(Cell::new(0)),
+            inbound: Vec::new(),
+        }
+    }
+}
+
+/// An allocator for workers of several simulated processes within one process.
+pub struct Simulated {
+    inner: Process,
+    network: Arc<Network>,
+    clock: Rc<Cell<u64>>,
+    inbound: Vec<(usize, Rc<RefCell<dyn Held>>)>,
+}
+
+impl Simulated {
+    /// Allocates builders for all workers of a simulated cluster.
+    pub fn new_vector(config: SimulationConfig) -> Vec<SimulatedCluster> {
+        let peers = config.processes * config.threads;
+        let network = Arc::new(Network {
+            config,
+            lockstep: Mutex::new((peers, 0, 0)),
+            condvar: Condvar::new(),
+            channels: Mutex::new(HashMap::new()),
+        });
+        Process::new_vector(peers)
+            .into_iter()
+            .map(|inner| SimulatedCluster { inner, network: network.clone() })
+            .collect()
+    }
+    /// The virtual process hosting worker `index`.
+    pub fn process_of(&self, index: usize) -> usize {
+        index / self.network.config.threads
+    }
+    /// The number of ticks so far.
+    pub fn tick(&self) -> u64 {
+        self.clock.get()
+    }
+}
+
+impl Allocate for Simulated {
+    fn index(&self) -> usize { self.inner.index() }
+    fn peers(&self) -> usize { self.inner.peers() }
+    fn allocate_with_codec<T: Send+Sync+Any+'static, C: Codec<T>>(&mut self, identifier: usize, _codec: C) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+
+        let peers = self.peers();
+        let inboxes = {
+            let mut channels = self.network.channels.lock().expect("channels mutex poisoned");
+            let inboxes = {
+                let entry = channels.entry(identifier).or_insert_with(|| {
+                    let inboxes: Vec<Inbox<T>> = (0 .. peers).map(|_| Arc::new(Mutex::new(Vec::new()))).collect();
+                    (peers, Box::new(inboxes))
+                });
+                entry.0 -= 1;
+                entry.1
+                    .downcast_ref::<Vec<Inbox<T>>>()
+                    .expect("failed to correctly cast channel")
+                    .clone()
+            };
+            if channels[&identifier].0 == 0 { channels.remove(&identifier); }
+            inboxes
+        };
+
+        let config = &self.network.config;
+        let source = self.index();
+        let mut pushers: Vec<Box<dyn Push<Message<T>>>> = Vec::with_capacity(peers);
+        for (target, inbox) in inboxes.iter().enumerate() {
+            let remote = self.process_of(source) != self.process_of(target);
+            let partitions =
+            config
+                .partitions
+                .iter()
+                .filter(|p| p.separates(self.process_of(source), self.process_of(target)))
+                .map(|p| (p.start, p.end))
+                .collect();
+
+            pushers.push(Box::new(LinkPusher {
+                inbox: inbox.clone(),
+                source,
+                clock: self.clock.clone(),
+                rng: SplitMix64::for_link(config.seed, identifier, source, target),
+                latency: if remote { config.latency } else { (0, 0) },
+                partitions,
+                last_due: 0,
+                sequence: 0,
+            }));
+        }
+
+        let link = Rc::new(RefCell::new(Link {
+            inbox: inboxes[source].clone(),
+            ready: VecDeque::new(),
+        }));
+        self.inbound.push((identifier, link.clone()));
+
+        (pushers, Box::new(LinkPuller { link, current: None }))
+    }
+
+    fn events(&self) -> &Rc<RefCell<VecDeque<(usize, Event)>>> {
+        self.inner.events()
+    }
+
+    fn await_events(&self, _duration: Option<Duration>) {
+        // Other workers may be waiting for our next tick, so we must not park.
+    }
+
+    fn receive(&mut self) {
+        self.network.tick();
+        let tick = self.clock.get() + 1;
+        self.clock.set(tick);
+        let mut events = self.inner.events().borrow_mut();
+        for (identifier, link) in self.inbound.iter() {
+            let released = link.borrow_mut().release(tick);
+            if released > 0 {
+                events.push_back((*identifier, Event::Pushed(released)));
+            }
+        }
+        // Retain links with a live puller.
+        self.inbound.retain(|(_, link)| Rc::strong_count(link) > 1);
+    }
+}
+
+impl Drop for Simulated {
+    fn drop(&mut self) {
+        self.network.leave();
+    }
+}
+
+/// Type-erased access to the messages in flight to a worker.
+trait Held {
+    /// Readies all messages due before `tick`, and returns their number.
+    fn release(&mut self, tick: u64) -> usize;
+}
+
+/// Simulated network state for messages to one worker on one channel.
+struct Link<T> {
+    inbox: Inbox<T>,
+    ready: VecDeque<Message<T>>,
+}
+
+impl<T> Held for Link<T> {
+    fn release(&mut self, tick: u64) -> usize {
+        let mut inbox = self.inbox.lock().expect("inbox mutex poisoned");
+        let mut due = Vec::new();
+        let mut index = 0;
+        while index < inbox.len() {
+            if inbox[index].0 < tick { due.push(inbox.swap_remove(index)); }
+            else { index += 1; }
+        }
+        due.sort_by_key(|&(due, source, sequence, _)| (due, source, sequence));
+        let released = due.len();
+        self.ready.extend(due.into_iter().map(|(_, _, _, message)| message));
+        released
+    }
+}
+
+/// The pull half of a simulated channel.
+struct LinkPuller<T> {
+    link: Rc<RefCell<Link<T>>>,
+    current: Option<Message<T>>,
+}
+
+impl<T> Pull<Message<T>> for LinkPuller<T> {
+    fn pull(&mut self) -> &mut Option<Message<T>> {
+        self.current = self.link.borrow_mut().ready.pop_front();
+        &mut self.current
+    }
+}
+
+/// The push half of a simulated channel, from one worker to another.
+struct LinkPusher<T> {
+    inbox: Inbox<T>,
+    source: usize,
+    clock: Rc<Cell<u64>>,
+    rng: SplitMix64,
+    latency: (u64, u64),
+    partitions: Vec<(u64, u64)>,
+    last_due: u64,
+    sequence: u64,
+}
+
+impl<T> LinkPusher<T> {
+    /// The tick after which a message sent now should be delivered.
+    fn due(&mut self) -> u64 {
+        let mut due = self.clock.get() + self.rng.range(self.latency.0, self.latency.1);
+        for &(start, end) in self.partitions.iter() {
+            if start <= due && due < end { due = end; }
+        }
+        // Maintain FIFO delivery for the channel.
+        due = ::std::cmp::max(due, self.last_due);
+        self.last_due = due;
+        due
+    }
+}
+
+impl<T> Push<Message<T>> for LinkPusher<T> {
+    fn push(&mut self, element: &mut Option<Message<T>>) {
+        if let Some(message) = element.take() {
+            let due = self.due();
+            self.sequence += 1;
+            self.inbox
+                .lock()
+                .expect("inbox mutex poisoned")
+                .push((due, self.source, self.sequence, message));
+        }
+    }
+}
+
+/// A small, seedable random number generator.
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    /// A generator for the link from `source` to `target` on channel `identifier`.
+    fn for_link(seed: u64, identifier: usize, source: usize, target: usize) -> Self {
+        let mut rng = SplitMix64 { state: seed };
+        for &value in [identifier, source, target].iter() {
+            rng.state = rng.next() ^ (value as u64);
+        }
+        rng
+    }
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /// A value between `min` and `max`, inclusive.
+    fn range(&mut self, min: u64, max: u64) -> u64 {
+        if min == max { min } else { min + self.next() % (max - min + 1) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::thread;
+
+    use crate::allocator::{Allocate, AllocateBuilder};
+    use crate::Message;
+    use super::{Simulated, SimulationConfig};
+
+    /// Each worker sends its index and a round number to every worker in each of `rounds` ticks,
+    /// and reports the tick, sender and round of every message it receives, in order.
+    fn run(config: SimulationConfig, rounds: usize) -> Vec<Vec<(u64, usize, usize)>> {
+        let threads =
+        Simulated::new_vector(config)
+            .into_iter()
+            .map(|builder| thread::spawn(move || {
+                let mut allocator = builder.build();
+                let (mut senders, mut receiver) = allocator.allocate::<(usize, usize)>(0);
+                let expected = allocator.peers() * rounds;
+                let mut received = Vec::new();
+                let mut round = 0;
+                while received.len() < expected {
+                    if round < rounds {
+                        for sender in senders.iter_mut() {
+                            sender.send(Message::from_typed((allocator.index(), round)));
+                            sender.done();
+                        }
+                        round += 1;
+                    }
+                    allocator.receive();
+                    while let Some(message) = receiver.recv() {
+                        let (source, round) = *message;
+                        received.push((allocator.tick(), source, round));
+                    }
+                    allocator.release();
+                }
+                received
+            }))
+            .collect::<Vec<_>>();
+
+        threads.into_iter().map(|thread| thread.join().expect("worker panicked")).collect()
+    }
+
+    #[test]
+    fn latency() {
+        let received = run(SimulationConfig::new(2, 2, 0).latency(5, 5), 1);
+        for (index, received) in received.iter().enumerate() {
+            for &(tick, source, _) in received.iter() {
+                let expected = if source / 2 == index / 2 { 1 } else { 6 };
+                assert_eq!(tick, expected, "message from {} to {}", source, index);
+            }
+        }
+    }
+
+    #[test]
+    fn partition() {
+        let received = run(SimulationConfig::new(2, 1, 0).partition(vec![0], 0, 10), 3);
+        for (index, received) in received.iter().enumerate() {
+            for &(tick, source, round) in received.iter() {
+                if source == index {
+                    assert_eq!(tick, round as u64 + 1);
+                }
+                else {
+                    assert_eq!(tick, 11);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reproducible() {
+        let config = SimulationConfig::new(2, 2, 0x5eed).latency(0, 7).partition(vec![1], 3, 6);
+        let first = run(config.clone(), 10);
+        assert_eq!(first, run(config.clone(), 10));
+        assert_eq!(first, run(config.clone(), 10));
+        assert_ne!(first, run(SimulationConfig { seed: 0x5eee, ..config }, 10));
+
+        // Each point-to-point channel is FIFO.
+        for received in first.iter() {
+            for source in 0 .. 4 {
+                let rounds = received.iter().filter(|m| m.1 == source).map(|m| m.2).collect::<Vec<_>>();
+                assert_eq!(rounds, (0 .. 10).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[test]
+    fn stopped_workers() {
+        // Workers that stop early do not hold back the ticks of the others.
+        let threads =
+        Simulated::new_vector(SimulationConfig::new(3, 1, 0))
+            .into_iter()
+            .map(|builder| thread::spawn(move || {
+                let mut allocator = builder.build();
+                for _ in 0 .. 10 * (allocator.index() + 1) {
+                    allocator.receive();
+                }
+                allocator.tick()
+            }))
+            .collect::<Vec<_>>();
+        let ticks = threads.into_iter().map(|thread| thread.join().unwrap()).collect::<Vec<_>>();
+        assert_eq!(ticks, vec![10, 20, 30]);
+    }
+}
@@ -1,20 +1,25 @@
 //! Zero-copy allocator based on TCP.
 use std::rc::Rc;
+use std::sync::Arc;
 use std::cell::RefCell;
+use std::any::Any;
 use std::collections::{VecDeque, HashMap, hash_map::Entry};
 use crossbeam_channel::{Sender, Receiver};
 
 use bytes::arc::Bytes;
 
-use crate::networking::MessageHeader;
+use crate::networking::{MessageHeader, WorkerLayout};
 
-use crate::{Allocate, Message, Data, Push, Pull};
+use crate::{Allocate, Message, Push, Pull};
+use crate::codec::Codec;
 use crate::allocator::AllocateBuilder;
 use crate::allocator::Event;
 use crate::allocator::canary::Canary;
+use crate::metrics::{Metrics, Traffic, Direction};
 
 use super::bytes_exchange::{BytesPull, SendEndpoint, MergeQueue};
 use super::push_pull::{Pusher, PullerInner};
+use super::compression::{Compression, decompress};
 
 /// Builds an instance of a TcpAllocator.
 ///
@@ -25,15 +30,20 @@ use super::push_pull::{Pusher, PullerInner};
 pub struct TcpBuilder<A: AllocateBuilder> {
     inner:  A,
     index:  usize,                      // number out of peers
-    peers:  usize,                      // number of peer allocators.
+    process: usize,                     // index of this process.
+    layout: WorkerLayout,               // assignment of workers to processes.
     futures:   Vec<Receiver<MergeQueue>>,  // to receive queues to each network thread.
     promises:   Vec<Sender<MergeQueue>>,    // to send queues from each network thread.
+    compression: Compression,               // default compression for outgoing payloads.
+    metrics: Metrics,                       // registry shared by the threads of this process.
 }
 
 /// Creates a vector of builders, sharing appropriate state.
 ///
-/// `threads` is the number of workers in a single process, `processes` is the
-/// total number of processes.
+/// `allocators` has one entry for each worker of this process, and `layout` describes
+/// the workers of all processes. Payloads sent to other processes are compressed
+/// according to `compression`, unless overridden for a specific channel, and
+/// traffic is recorded in `metrics`.
 /// The returned tuple contains
 /// ```ignore
 /// (
@@ -45,12 +55,16 @@ pub struct TcpBuilder<A: AllocateBuilder> {
 pub fn new_vector<A: AllocateBuilder>(
     allocators: Vec<A>,
     my_process: usize,
-    processes: usize)
+    layout: WorkerLayout,
+    compression: Compression,
+    metrics: Metrics)
 -> (Vec<TcpBuilder<A>>,
     Vec<Vec<Sender<MergeQueue>>>,
     Vec<Vec<Receiver<MergeQueue>>>)
 {
     let threads = allocators.len();
+    let processes = layout.processes();
+    assert_eq!(threads, layout.threads(my_process), "allocator count does not match layout");
 
     // For queues from worker threads to network threads, and vice versa.
     let (network_promises, worker_futures) = crate::promise_futures(processes-1, threads);
@@ -65,10 +79,13 @@ pub fn new_vector<A: AllocateBuilder>(
         .map(|(index, ((inner, promises), futures))| {
             TcpBuilder {
                 inner,
-                index: my_process * threads + index,
-                peers: threads * processes,
+                index: layout.offset(my_process) + index,
+                process: my_process,
+                layout: layout.clone(),
                 promises,
                 futures,
+                compression,
+                metrics: metrics.clone(),
             }})
         .collect();
 
@@ -80,20 +97,27 @@ impl<A: AllocateBuilder> TcpBuilder<A> {
     /// Builds a `TcpAllocator`, instantiating `Rc<RefCell<_>>` elements.
     pub fn build(self) -> TcpAllocator<A::Allocator> {
 
+        // Queues are held for each other process, in order.
+        let process = self.process;
+        let remote = |index: usize| if index < process { index } else { index + 1 };
+
         // Fulfill puller obligations.
-        let mut recvs = Vec::with_capacity(self.peers);
-        for promise in self.promises.into_iter() {
+        let mut recvs = Vec::with_capacity(self.layout.processes());
+        for (index, promise) in self.promises.into_iter().enumerate() {
             let buzzer = crate::buzzer::Buzzer::new();
             let queue = MergeQueue::new(buzzer);
+            self.metrics.register_queue(self.index, remote(index), Direction::Recv, queue.depth());
             promise.send(queue.clone()).expect("Failed to send MergeQueue");
             recvs.push(queue.clone());
         }
 
         // Extract pusher commitments.
-        let mut sends = Vec::with_capacity(self.peers);
-        for pusher in self.futures.into_iter() {
+        let mut sends = Vec::with_capacity(self.layout.processes());
+        for (index, pusher) in self.futures.into_iter().enumerate() {
             let queue = pusher.recv().expect("Failed to receive push queue");
+            self.metrics.register_queue(self.index, remote(index), Direction::Send, queue.depth());
             let sendpoint = SendEndpoint::new(queue);
+            self.metrics.register_slab(Some(self.index), remote(index), Direction::Send, sendpoint.reallocations());
             sends.push(Rc::new(RefCell::new(sendpoint)));
         }
 
@@ -103,13 +127,18 @@ impl<A: AllocateBuilder> TcpBuilder<A> {
         TcpAllocator {
             inner: self.inner.build(),
             index: self.index,
-            peers: self.peers,
+            process: self.process,
+            layout: self.layout,
             canaries: Rc::new(RefCell::new(Vec::new())),
             channel_id_bound: None,
             staged: Vec::new(),
             sends,
             recvs,
             to_local: HashMap::new(),
+            compression: self.compression,
+            channel_compression: HashMap::new(),
+            metrics: self.metrics,
+            received: HashMap::new(),
         }
     }
 }
@@ -120,7 +149,8 @@ pub struct TcpAllocator<A: Allocate> {
     inner:      A,                                  // A non-serialized inner allocator for process-local peers.
 
     index:      usize,                              // number out of peers
-    peers:      usize,                              // number of peer allocators (for typed channel allocation).
+    process:    usize,                              // index of this process.
+    layout:     WorkerLayout,                       // assignment of workers to processes.
 
     staged:     Vec<Bytes>,                         // staging area for incoming Bytes
     canaries:   Rc<RefCell<Vec<usize>>>,
@@ -131,12 +161,25 @@ pub struct TcpAllocator<A: Allocate> {
     sends:      Vec<Rc<RefCell<SendEndpoint<MergeQueue>>>>,     // sends[x] -> goes to process x.
     recvs:      Vec<MergeQueue>,                                // recvs[x] <- from process x.
     to_local:   HashMap<usize, Rc<RefCell<VecDeque<Bytes>>>>,   // to worker-local typed pullers.
+
+    compression: Compression,                                   // default payload compression.
+    channel_compression: HashMap<usize, Compression>,           // per-channel overrides.
+
+    metrics:    Metrics,                                        // registry shared by the threads of this process.
+    received:   HashMap<(usize, usize), Arc<Traffic>>,          // received traffic by channel and source.
+}
+
+impl<A: Allocate> TcpAllocator<A> {
+    /// The assignment of workers to processes.
+    pub fn layout(&self) -> &WorkerLayout {
+        &self.layout
+    }
 }
 
 impl<A: Allocate> Allocate for TcpAllocator<A> {
     fn index(&self) -> usize { self.index }
-    fn peers(&self) -> usize { self.peers }
-    fn allocate<T: Data>(&mut self, identifier: usize) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+    fn peers(&self) -> usize { self.layout.peers() }
+    fn allocate_with_codec<T: Send+Sync+Any+'static, C: Codec<T>>(&mut self, identifier: usize, codec: C) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
 
         // Assume and enforce in-order identifier allocation.
         if let Some(bound) = self.channel_id_bound {
@@ -147,16 +190,16 @@ impl<A: Allocate> Allocate for TcpAllocator<A> {
         // Result list of boxed pushers.
         let mut pushes = Vec::<Box<dyn Push<Message<T>>>>::new();
 
+        let compression = self.channel_compression.remove(&identifier).unwrap_or(self.compression);
+
         // Inner exchange allocations.
-        let inner_peers = self.inner.peers();
-        let (mut inner_sends, inner_recv) = self.inner.allocate(identifier);
+        let (mut inner_sends, inner_recv) = self.inner.allocate_with_codec(identifier, codec.clone());
 
         for target_index in 0 .. self.peers() {
 
-            // TODO: crappy place to hardcode this rule.
-            let mut process_id = target_index / inner_peers;
+            let mut process_id = self.layout.process_of(target_index);
 
-            if process_id == self.index / inner_peers {
+            if process_id == self.process {
                 pushes.push(inner_sends.remove(0));
             }
             else {
@@ -167,11 +210,13 @@ impl<A: Allocate> Allocate for TcpAllocator<A> {
                     target:     target_index,
                     length:     0,
                     seqno:      0,
+                    codec:      0,
                 };
 
                 // create, box, and stash new process_binary pusher.
-                if process_id > self.index / inner_peers { process_id -= 1; }
-                pushes.push(Box::new(Pusher::new(header, self.sends[process_id].clone())));
+                if process_id > self.process { process_id -= 1; }
+                let traffic = self.metrics.traffic(self.index, identifier, target_index);
+                pushes.push(Box::new(Pusher::with_compression(header, self.sends[process_id].clone(), codec.clone(), compression).with_traffic(traffic)));
             }
         }
 
@@ -183,7 +228,7 @@ impl<A: Allocate> Allocate for TcpAllocator<A> {
 
         use crate::allocator::counters::Puller as CountPuller;
         let canary = Canary::new(identifier, self.canaries.clone());
-        let puller = Box::new(CountPuller::new(PullerInner::new(inner_recv, channel, canary), identifier, self.events().clone()));
+        let puller = Box::new(CountPuller::new(PullerInner::new(inner_recv, channel, canary, codec), identifier, self.events().clone()));
 
         (pushes, puller, )
     }
@@ -223,9 +268,21 @@ impl<A: Allocate> Allocate for TcpAllocator<A> {
 
                 if let Some(header) = MessageHeader::try_read(&mut bytes[..]) {
 
+                    let (metrics, index) = (&self.metrics, self.index);
+                    self.received
+                        .entry((header.channel, header.source))
+                        .or_insert_with(|| metrics.traffic(index, header.channel, header.source))
+                        .received(header.length);
+
                     // Get the header and payload, ditch the header.
                     let mut peel = bytes.extract_to(header.required_bytes());
-                    let _ = peel.extract_to(40);
+                    let _ = peel.extract_to(::std::mem::size_of::<MessageHeader>());
+
+                    // Restore the serialized payload, if it was compressed. The sender has counted its records
+                    // towards progress tracking, so a payload that cannot be restored cannot be dropped either.
+                    let peel = decompress(header.codec, peel).unwrap_or_else(|error| {
+                        panic!("failed to decompress message on channel {} from worker {}: {}", header.channel, header.source, error)
+                    });
 
                     // Increment message count for channel.
                     // Safe to do this even if the channel has been dropped.
@@ -272,6 +329,12 @@ impl<A: Allocate> Allocate for TcpAllocator<A> {
     fn events(&self) -> &Rc<RefCell<VecDeque<(usize, Event)>>> {
         self.inner.events()
     }
+    fn metrics(&self) -> Option<&Metrics> {
+        Some(&self.metrics)
+    }
+    fn set_channel_compression(&mut self, identifier: usize, compression: Compression) {
+        self.channel_compression.insert(identifier, compression);
+    }
     fn await_events(&self, duration: Option<std::time::Duration>) {
         self.inner.await_events(duration);
     }
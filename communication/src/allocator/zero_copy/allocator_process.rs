@@ -2,6 +2,7 @@
 
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::any::Any;
 use std::collections::{VecDeque, HashMap, hash_map::Entry};
 use crossbeam_channel::{Sender, Receiver};
 
@@ -9,7 +10,8 @@ use bytes::arc::Bytes;
 
 use crate::networking::MessageHeader;
 
-use crate::{Allocate, Message, Data, Push, Pull};
+use crate::{Allocate, Message, Push, Pull};
+use crate::codec::Codec;
 use crate::allocator::{AllocateBuilder, Event};
 use crate::allocator::canary::Canary;
 
@@ -119,7 +121,7 @@ pub struct ProcessAllocator {
 impl Allocate for ProcessAllocator {
     fn index(&self) -> usize { self.index }
     fn peers(&self) -> usize { self.peers }
-    fn allocate<T: Data>(&mut self, identifier: usize) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+    fn allocate_with_codec<T: Send+Sync+Any+'static, C: Codec<T>>(&mut self, identifier: usize, codec: C) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
 
         // Assume and enforce in-order identifier allocation.
         if let Some(bound) = self.channel_id_bound {
@@ -138,10 +140,11 @@ impl Allocate for ProcessAllocator {
                 target:     target_index,
                 length:     0,
                 seqno:      0,
+                codec:      0,
             };
 
             // create, box, and stash new process_binary pusher.
-            pushes.push(Box::new(Pusher::new(header, self.sends[target_index].clone())));
+            pushes.push(Box::new(Pusher::new(header, self.sends[target_index].clone(), codec.clone())));
         }
 
         let channel =
@@ -152,7 +155,7 @@ impl Allocate for ProcessAllocator {
 
         use crate::allocator::counters::Puller as CountPuller;
         let canary = Canary::new(identifier, self.canaries.clone());
-        let puller = Box::new(CountPuller::new(Puller::new(channel, canary), identifier, self.events().clone()));
+        let puller = Box::new(CountPuller::new(Puller::new(channel, canary, codec), identifier, self.events().clone()));
 
         (pushes, puller)
     }
@@ -192,7 +195,7 @@ impl Allocate for ProcessAllocator {
 
                     // Get the header and payload, ditch the header.
                     let mut peel = bytes.extract_to(header.required_bytes());
-                    let _ = peel.extract_to(40);
+                    let _ = peel.extract_to(::std::mem::size_of::<MessageHeader>());
 
                     // Increment message count for channel.
                     // Safe to do this even if the channel has been dropped.
@@ -21,7 +21,7 @@ pub trait BytesPull {
     fn drain_into(&mut self, vec: &mut Vec<Bytes>);
 }
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 /// An unbounded queue of bytes intended for point-to-point communication
 /// between threads. Cloning returns another handle to the same queue.
 ///
@@ -31,6 +31,7 @@ pub struct MergeQueue {
     queue: Arc<Mutex<VecDeque<Bytes>>>, // queue of bytes.
     buzzer: crate::buzzer::Buzzer,  // awakens receiver thread.
     panic: Arc<AtomicBool>,
+    depth: Arc<AtomicUsize>,            // number of enqueued bytes.
 }
 
 impl MergeQueue {
@@ -40,8 +41,13 @@ impl MergeQueue {
             queue: Arc::new(Mutex::new(VecDeque::new())),
             buzzer,
             panic: Arc::new(AtomicBool::new(false)),
+            depth: Arc::new(AtomicUsize::new(0)),
         }
     }
+    /// A shared count of the bytes currently enqueued.
+    pub fn depth(&self) -> Arc<AtomicUsize> {
+        self.depth.clone()
+    }
     /// Indicates that all input handles to the queue have dropped.
     pub fn is_complete(&self) -> bool {
         if self.panic.load(Ordering::SeqCst) { panic!("MergeQueue poisoned."); }
@@ -61,7 +67,8 @@ impl BytesPush for MergeQueue {
         }
         let mut queue = lock_ok.expect("MergeQueue mutex poisoned.");
 
-        let mut iterator = iterator.into_iter();
+        let depth = &self.depth;
+        let mut iterator = iterator.into_iter().inspect(|bytes| { depth.fetch_add(bytes.len(), Ordering::Relaxed); });
         let mut should_ping = false;
         if let Some(bytes) = iterator.next() {
             let mut tail = if let Some(mut tail) = queue.pop_back() {
@@ -102,6 +109,8 @@ impl BytesPull for MergeQueue {
         }
         let mut queue = lock_ok.expect("MergeQueue mutex poisoned.");
 
+        let drained = queue.iter().map(|bytes| bytes.len()).sum();
+        self.depth.fetch_sub(drained, Ordering::Relaxed);
         vec.extend(queue.drain(..));
     }
 }
@@ -148,6 +157,10 @@ impl<P: BytesPush> SendEndpoint<P> {
             buffer: BytesSlab::new(20),
         }
     }
+    /// A shared count of the buffers allocated for staging writes after the first.
+    pub fn reallocations(&self) -> Arc<AtomicUsize> {
+        self.buffer.reallocations()
+    }
     /// Makes the next `bytes` bytes valid.
     ///
     /// The current implementation also sends the bytes, to ensure early visibility.
@@ -1,5 +1,8 @@
 //! A large binary allocation for writing and sharing.
 
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use bytes::arc::Bytes;
 
 /// A large binary allocation for writing and sharing.
@@ -13,6 +16,7 @@ pub struct BytesSlab {
     stash:          Vec<Bytes>,                 // reclaimed and resuable buffers.
     shift:          usize,                      // current buffer allocation size.
     valid:          usize,                      // buffer[..valid] are valid bytes.
+    reallocations:  Arc<AtomicUsize>,           // number of buffers allocated after the first.
 }
 
 impl BytesSlab {
@@ -24,8 +28,13 @@ impl BytesSlab {
             stash: Vec::new(),
             shift,
             valid: 0,
+            reallocations: Arc::new(AtomicUsize::new(0)),
         }
     }
+    /// A shared count of the buffers the slab has allocated after its initial buffer.
+    pub fn reallocations(&self) -> Arc<AtomicUsize> {
+        self.reallocations.clone()
+    }
     /// The empty region of the slab.
     pub fn empty(&mut self) -> &mut [u8] {
         &mut self.buffer[self.valid..]
@@ -82,7 +91,10 @@ impl BytesSlab {
                 self.in_progress.retain(|x| x.is_some());
             }
 
-            let new_buffer = self.stash.pop().unwrap_or_else(|| Bytes::from(vec![0; 1 << self.shift].into_boxed_slice()));
+            let new_buffer = self.stash.pop().unwrap_or_else(|| {
+                self.reallocations.fetch_add(1, Ordering::Relaxed);
+                Bytes::from(vec![0; 1 << self.shift].into_boxed_slice())
+            });
             let old_buffer = ::std::mem::replace(&mut self.buffer, new_buffer);
 
             self.buffer[.. self.valid].copy_from_slice(&old_buffer[.. self.valid]);
@@ -0,0 +1,207 @@
+//! Optional compression of serialized message payloads.
+//!
+//! Payloads at least `threshold` bytes long are compressed by the sending `Pusher`, and the codec
+//! used is recorded in the `MessageHeader`. The receiving allocator inspects the header and
+//! decompresses the payload before it is handed to a typed puller, so that compression is not
+//! visible to the dataflow.
+//!
+//! The codecs themselves are provided by optional crates, enabled by the `lz4` and `zstd` features.
+
+use bytes::arc::Bytes;
+
+/// The largest payload, in bytes, that is compressed or that a compressed payload may decode to.
+///
+/// Larger payloads are sent uncompressed, and compressed payloads claiming to decode to more are rejected
+/// before any allocation, so that a corrupt or malicious peer cannot exhaust memory.
+pub const MAX_PAYLOAD_BYTES: usize = 1 << 30;
+
+/// A codec for compressing message payloads.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Codec {
+    /// Payloads are sent as serialized.
+    Uncompressed,
+    /// LZ4 block compression (requires the `lz4` feature).
+    Lz4,
+    /// Zstandard compression at the indicated level (requires the `zstd` feature).
+    Zstd(i32),
+}
+
+impl Codec {
+    /// The identifier recorded in `MessageHeader::codec`.
+    pub fn identifier(&self) -> usize {
+        match self {
+            Codec::Uncompressed => 0,
+            Codec::Lz4 => 1,
+            Codec::Zstd(_) => 2,
+        }
+    }
+
+    /// Indicates whether the codec was compiled in to this build.
+    pub fn available(&self) -> bool {
+        match self {
+            Codec::Uncompressed => true,
+            Codec::Lz4 => cfg!(feature = "lz4"),
+            Codec::Zstd(_) => cfg!(feature = "zstd"),
+        }
+    }
+
+    /// Parses a codec from text, e.g. `"lz4"`, `"zstd"`, or `"zstd:9"`.
+    pub fn parse(text: &str) -> Result<Codec, String> {
+        let mut parts = text.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some("none"), None) => Ok(Codec::Uncompressed),
+            (Some("lz4"), None) => Ok(Codec::Lz4),
+            (Some("zstd"), None) => Ok(Codec::Zstd(0)),
+            (Some("zstd"), Some(level)) => {
+                level.parse()
+                     .map(Codec::Zstd)
+                     .map_err(|e| format!("invalid zstd level {:?}: {}", level, e))
+            },
+            _ => Err(format!("unrecognized compression codec: {:?}", text)),
+        }
+    }
+
+    /// Appends the compressed form of `source` to `target`.
+    pub fn compress(&self, source: &[u8], target: &mut Vec<u8>) {
+        match self {
+            Codec::Uncompressed => target.extend_from_slice(source),
+            #[cfg(feature = "lz4")]
+            Codec::Lz4 => {
+                target.extend_from_slice(&::lz4_flex::compress_prepend_size(source));
+            },
+            #[cfg(feature = "zstd")]
+            Codec::Zstd(level) => {
+                ::zstd::stream::copy_encode(source, target, *level).expect("zstd compression failed");
+            },
+            #[allow(unreachable_patterns)]
+            _ => panic!("compression codec {:?} not available in this build", self),
+        }
+    }
+}
+
+/// Compression settings for a channel.
+///
+/// The default settings do not compress anything.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Compression {
+    /// The codec to apply to large payloads.
+    pub codec: Codec,
+    /// The serialized length in bytes at which payloads start to be compressed.
+    pub threshold: usize,
+}
+
+impl Compression {
+    /// Settings that compress payloads of at least `threshold` bytes with `codec`.
+    pub fn new(codec: Codec, threshold: usize) -> Self {
+        Compression { codec, threshold }
+    }
+    /// Settings that never compress.
+    pub fn none() -> Self {
+        Compression { codec: Codec::Uncompressed, threshold: usize::max_value() }
+    }
+    /// Indicates whether a payload of `length` bytes should be compressed.
+    #[inline]
+    pub fn applies_to(&self, length: usize) -> bool {
+        self.codec != Codec::Uncompressed && length >= self.threshold && length <= MAX_PAYLOAD_BYTES
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self { Compression::none() }
+}
+
+/// Decompresses a payload written with the codec identified by `codec`.
+///
+/// Uncompressed payloads are returned unchanged; compressed payloads are decoded into a new allocation.
+/// An error is returned for codecs not available in this build, for corrupt payloads, and for payloads
+/// that would decode to more than `MAX_PAYLOAD_BYTES`.
+pub fn decompress(codec: usize, payload: Bytes) -> Result<Bytes, String> {
+    match codec {
+        0 => Ok(payload),
+        #[cfg(feature = "lz4")]
+        1 => {
+            // The decoded length is prepended as a little-endian `u32`, and checked before allocating.
+            if payload.len() < 4 {
+                return Err("lz4 payload too short for its length prefix".to_owned());
+            }
+            let length = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]) as usize;
+            if length > MAX_PAYLOAD_BYTES {
+                return Err(format!("lz4 payload decodes to {} bytes, more than the limit of {}", length, MAX_PAYLOAD_BYTES));
+            }
+            ::lz4_flex::decompress_size_prepended(&payload[..])
+                .map(|decoded| Bytes::from(decoded.into_boxed_slice()))
+                .map_err(|e| format!("lz4 decompression failed: {:?}", e))
+        },
+        #[cfg(feature = "zstd")]
+        2 => {
+            use std::io::Read;
+            // Decoding stops one byte past the limit, so that larger payloads are detected without being held.
+            let mut decoded = Vec::new();
+            ::zstd::stream::read::Decoder::new(&payload[..])
+                .and_then(|decoder| decoder.take(MAX_PAYLOAD_BYTES as u64 + 1).read_to_end(&mut decoded))
+                .map_err(|e| format!("zstd decompression failed: {}", e))?;
+            if decoded.len() > MAX_PAYLOAD_BYTES {
+                return Err(format!("zstd payload decodes to more than the limit of {} bytes", MAX_PAYLOAD_BYTES));
+            }
+            Ok(Bytes::from(decoded.into_boxed_slice()))
+        },
+        _ => Err(format!("unsupported compression codec: {}", codec)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use bytes::arc::Bytes;
+    use super::{Codec, Compression, decompress, MAX_PAYLOAD_BYTES};
+
+    fn round_trip(codec: Codec) {
+        let payload = (0 .. 10_000u32).flat_map(|x| (x % 97).to_le_bytes().to_vec()).collect::<Vec<u8>>();
+        let mut compressed = Vec::new();
+        codec.compress(&payload[..], &mut compressed);
+        let decoded = decompress(codec.identifier(), Bytes::from(compressed.into_boxed_slice())).unwrap();
+        assert_eq!(&decoded[..], &payload[..]);
+    }
+
+    #[test]
+    fn uncompressed() {
+        round_trip(Codec::Uncompressed);
+        assert!(!Compression::none().applies_to(usize::max_value() - 1));
+        assert!(Compression::new(Codec::Lz4, 10).applies_to(10));
+        assert!(!Compression::new(Codec::Lz4, 10).applies_to(MAX_PAYLOAD_BYTES + 1));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4() {
+        round_trip(Codec::Lz4);
+        let corrupt = Bytes::from(vec![255u8; 16].into_boxed_slice());
+        assert!(decompress(Codec::Lz4.identifier(), corrupt).is_err());
+        // A prefix claiming a huge decoded length is rejected before allocating.
+        let bomb = Bytes::from(vec![0xff, 0xff, 0xff, 0x7f, 0, 0, 0, 0].into_boxed_slice());
+        assert!(decompress(Codec::Lz4.identifier(), bomb).is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd() {
+        round_trip(Codec::Zstd(3));
+        let corrupt = Bytes::from(vec![255u8; 16].into_boxed_slice());
+        assert!(decompress(Codec::Zstd(0).identifier(), corrupt).is_err());
+    }
+
+    #[test]
+    fn unknown_codec() {
+        let payload = Bytes::from(vec![0u8; 16].into_boxed_slice());
+        assert!(decompress(7, payload).is_err());
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Codec::parse("none"), Ok(Codec::Uncompressed));
+        assert_eq!(Codec::parse("lz4"), Ok(Codec::Lz4));
+        assert_eq!(Codec::parse("zstd:9"), Ok(Codec::Zstd(9)));
+        assert!(Codec::parse("zstd:high").is_err());
+        assert!(Codec::parse("gzip").is_err());
+    }
+}
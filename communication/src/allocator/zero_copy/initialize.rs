@@ -3,9 +3,13 @@
 use std::sync::Arc;
 // use crate::allocator::Process;
 use crate::allocator::process::ProcessBuilder;
-use crate::networking::create_sockets;
+use crate::networking::{create_secured_sockets, WorkerLayout};
 use super::tcp::{send_loop, recv_loop};
 use super::allocator::{TcpBuilder, new_vector};
+use super::compression::Compression;
+use super::security::{Security, Session};
+use crate::placement::{Placement, place_current_thread};
+use crate::metrics::Metrics;
 
 /// Join handles for send and receive threads.
 ///
@@ -29,20 +33,27 @@ impl Drop for CommsGuard {
     }
 }
 
-use crate::logging::{CommunicationSetup, CommunicationEvent};
+use crate::logging::{CommunicationSetup, CommunicationEvent, ThreadRole};
 use logging_core::Logger;
 
 /// Initializes network connections
+///
+/// The `layout` describes the number of workers in each process, and must be the same in all processes.
+/// The send and receive threads are pinned to the cores `placement` assigns them, and links
+/// to other processes are authenticated and encrypted according to `security`.
 pub fn initialize_networking(
     addresses: Vec<String>,
     my_index: usize,
-    threads: usize,
+    layout: WorkerLayout,
     noisy: bool,
+    compression: Compression,
+    placement: Placement,
+    security: Security,
     log_sender: Box<dyn Fn(CommunicationSetup)->Option<Logger<CommunicationEvent, CommunicationSetup>>+Send+Sync>)
 -> ::std::io::Result<(Vec<TcpBuilder<ProcessBuilder>>, CommsGuard)>
 {
-    let sockets = create_sockets(addresses, my_index, noisy)?;
-    initialize_networking_from_sockets(sockets, my_index, threads, log_sender)
+    let links = create_secured_sockets(addresses, my_index, noisy, security)?;
+    initialize_networking_from_links(links, my_index, layout, compression, placement, log_sender)
 }
 
 /// Initialize send and recv threads from sockets.
@@ -51,26 +62,50 @@ pub fn initialize_networking(
 /// a vector of process-local allocators connected to instantiated send and recv threads.
 ///
 /// It is important that the `sockets` argument contain sockets for each remote process, in order, and
-/// with position `my_index` set to `None`.
+/// with position `my_index` set to `None`, and that `layout` describe the same number of processes.
 pub fn initialize_networking_from_sockets(
-    mut sockets: Vec<Option<std::net::TcpStream>>,
+    sockets: Vec<Option<std::net::TcpStream>>,
+    my_index: usize,
+    layout: WorkerLayout,
+    compression: Compression,
+    placement: Placement,
+    log_sender: Box<dyn Fn(CommunicationSetup)->Option<Logger<CommunicationEvent, CommunicationSetup>>+Send+Sync>)
+-> ::std::io::Result<(Vec<TcpBuilder<ProcessBuilder>>, CommsGuard)>
+{
+    let links = sockets.into_iter().map(|socket| socket.map(|socket| (socket, None))).collect();
+    initialize_networking_from_links(links, my_index, layout, compression, placement, log_sender)
+}
+
+/// Initialize send and recv threads from sockets, each with the keys of its session if it is secured.
+///
+/// The same requirements as for `initialize_networking_from_sockets` apply to `links`.
+pub fn initialize_networking_from_links(
+    mut sockets: Vec<Option<(std::net::TcpStream, Option<Session>)>>,
     my_index: usize,
-    threads: usize,
+    layout: WorkerLayout,
+    compression: Compression,
+    placement: Placement,
     log_sender: Box<dyn Fn(CommunicationSetup)->Option<Logger<CommunicationEvent, CommunicationSetup>>+Send+Sync>)
 -> ::std::io::Result<(Vec<TcpBuilder<ProcessBuilder>>, CommsGuard)>
 {
     // Sockets are expected to be blocking,
     for socket in sockets.iter_mut() {
-        if let Some(socket) = socket {
+        if let Some((socket, _)) = socket {
             socket.set_nonblocking(false).expect("failed to set socket to blocking");
         }
     }
 
     let log_sender = Arc::new(log_sender);
-    let processes = sockets.len();
+    assert_eq!(sockets.len(), layout.processes(), "socket count does not match layout");
+    let worker_offset = layout.offset(my_index);
 
-    let process_allocators = crate::allocator::process::Process::new_vector(threads);
-    let (builders, promises, futures) = new_vector(process_allocators, my_index, processes);
+    // Cores for send and receive threads follow those of the workers.
+    let cores = placement.cluster_cores(layout.threads(my_index), layout.processes() - 1);
+    let mut comm_cores = cores[layout.threads(my_index) ..].to_vec().into_iter();
+
+    let process_allocators = crate::allocator::process::Process::new_vector(layout.threads(my_index));
+    let metrics = Metrics::new();
+    let (builders, promises, futures) = new_vector(process_allocators, my_index, layout, compression, metrics.clone());
 
     let mut promises_iter = promises.into_iter();
     let mut futures_iter = futures.into_iter();
@@ -81,10 +116,17 @@ pub fn initialize_networking_from_sockets(
     // for each process, if a stream exists (i.e. not local) ...
     for index in 0..sockets.len() {
 
-        if let Some(stream) = sockets[index].take() {
+        if let Some((stream, session)) = sockets[index].take() {
             // remote process
 
+            let (send_cipher, recv_cipher) = match session.map(|session| session.split()) {
+                Some((send, recv)) => (Some(send), Some(recv)),
+                None => (None, None),
+            };
+
             let remote_recv = promises_iter.next().unwrap();
+            let send_core = comm_cores.next().unwrap();
+            let recv_core = comm_cores.next().unwrap();
 
             {
                 let log_sender = log_sender.clone();
@@ -94,13 +136,15 @@ pub fn initialize_networking_from_sockets(
                     .name(format!("timely:send-{}", index))
                     .spawn(move || {
 
-                        let logger = log_sender(CommunicationSetup {
+                        let mut logger = log_sender(CommunicationSetup {
                             process: my_index,
                             sender: true,
                             remote: Some(index),
+                            worker: None,
                         });
+                        place_current_thread(send_core, my_index, ThreadRole::Send(index), &mut logger);
 
-                        send_loop(stream, remote_recv, my_index, index, logger);
+                        send_loop(stream, remote_recv, my_index, index, send_cipher, logger);
                     })?;
 
                 send_guards.push(join_guard);
@@ -111,17 +155,20 @@ pub fn initialize_networking_from_sockets(
             {
                 // let remote_sends = remote_sends.clone();
                 let log_sender = log_sender.clone();
+                let metrics = metrics.clone();
                 let stream = stream.try_clone()?;
                 let join_guard =
                 ::std::thread::Builder::new()
                     .name(format!("timely:recv-{}", index))
                     .spawn(move || {
-                        let logger = log_sender(CommunicationSetup {
+                        let mut logger = log_sender(CommunicationSetup {
                             process: my_index,
                             sender: false,
                             remote: Some(index),
+                            worker: None,
                         });
-                        recv_loop(stream, remote_send, threads * my_index, my_index, index, logger);
+                        place_current_thread(recv_core, my_index, ThreadRole::Recv(index), &mut logger);
+                        recv_loop(stream, remote_send, worker_offset, my_index, index, recv_cipher, metrics, logger);
                     })?;
 
                 recv_guards.push(join_guard);
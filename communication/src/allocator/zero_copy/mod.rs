@@ -10,4 +10,6 @@ pub mod tcp;
 pub mod allocator;
 pub mod allocator_process;
 pub mod initialize;
-pub mod push_pull;
\ No newline at end of file
+pub mod push_pull;
+pub mod compression;
+pub mod security;
\ No newline at end of file
@@ -1,6 +1,7 @@
 //! Push and Pull implementations wrapping serialized data.
 
 use std::rc::Rc;
+use std::sync::Arc;
 use std::cell::RefCell;
 use std::collections::VecDeque;
 
@@ -9,33 +10,56 @@ use bytes::arc::Bytes;
 use crate::allocator::canary::Canary;
 use crate::networking::MessageHeader;
 
-use crate::{Data, Push, Pull};
+use crate::{Push, Pull};
 use crate::allocator::Message;
+use crate::codec::Codec;
+use crate::metrics::Traffic;
 
 use super::bytes_exchange::{BytesPush, SendEndpoint};
+use super::compression::Compression;
 
 /// An adapter into which one may push elements of type `T`.
 ///
 /// This pusher has a fixed MessageHeader, and access to a SharedByteBuffer which it uses to
-/// acquire buffers for serialization.
-pub struct Pusher<T, P: BytesPush> {
+/// acquire buffers for serialization with its codec. Payloads selected by its `Compression`
+/// settings are serialized into a local buffer and compressed before they are written.
+pub struct Pusher<T, P: BytesPush, C> {
     header:     MessageHeader,
     sender:     Rc<RefCell<SendEndpoint<P>>>,
+    codec:      C,
+    compression: Compression,
+    serialized: Vec<u8>,                // staging for payloads to compress.
+    compressed: Vec<u8>,                // staging for compressed payloads.
+    traffic:    Option<Arc<Traffic>>,   // counts of sent messages and bytes.
     phantom:    ::std::marker::PhantomData<T>,
 }
 
-impl<T, P: BytesPush> Pusher<T, P> {
-    /// Creates a new `Pusher` from a header and shared byte buffer.
-    pub fn new(header: MessageHeader, sender: Rc<RefCell<SendEndpoint<P>>>) -> Pusher<T, P> {
+impl<T, P: BytesPush, C> Pusher<T, P, C> {
+    /// Creates a new `Pusher` from a header, shared byte buffer, and codec.
+    pub fn new(header: MessageHeader, sender: Rc<RefCell<SendEndpoint<P>>>, codec: C) -> Pusher<T, P, C> {
+        Self::with_compression(header, sender, codec, Compression::none())
+    }
+    /// Creates a new `Pusher` which compresses payloads according to `compression`.
+    pub fn with_compression(header: MessageHeader, sender: Rc<RefCell<SendEndpoint<P>>>, codec: C, compression: Compression) -> Pusher<T, P, C> {
         Pusher {
             header:     header,
             sender:     sender,
+            codec,
+            compression,
+            serialized: Vec::new(),
+            compressed: Vec::new(),
+            traffic:    None,
             phantom:    ::std::marker::PhantomData,
         }
     }
+    /// Records the messages and payload bytes sent in `traffic`.
+    pub fn with_traffic(mut self, traffic: Arc<Traffic>) -> Self {
+        self.traffic = Some(traffic);
+        self
+    }
 }
 
-impl<T:Data, P: BytesPush> Push<Message<T>> for Pusher<T, P> {
+impl<T, P: BytesPush, C: Codec<T>> Push<Message<T>> for Pusher<T, P, C> {
     #[inline]
     fn push(&mut self, element: &mut Option<Message<T>>) {
         if let Some(ref mut element) = *element {
@@ -43,9 +67,28 @@ impl<T:Data, P: BytesPush> Push<Message<T>> for Pusher<T, P> {
             // determine byte lengths and build header.
             let mut header = self.header;
             self.header.seqno += 1;
-            header.length = element.length_in_bytes();
+            header.length = element.length_in_bytes(&self.codec);
             assert!(header.length > 0);
 
+            // large payloads are serialized and compressed before writing, unless compression does not shrink them.
+            let compress = self.compression.applies_to(header.length);
+            let mut shrunk = false;
+            if compress {
+                self.serialized.clear();
+                self.compressed.clear();
+                element.into_bytes(&self.codec, &mut self.serialized);
+                self.compression.codec.compress(&self.serialized[..], &mut self.compressed);
+                shrunk = self.compressed.len() < self.serialized.len();
+                if shrunk {
+                    header.length = self.compressed.len();
+                    header.codec = self.compression.codec.identifier();
+                }
+                assert!(header.length > 0);
+            }
+            let payload = if !compress { None }
+                else if shrunk { Some(&self.compressed[..]) }
+                else { Some(&self.serialized[..]) };
+
             // acquire byte buffer and write header, element.
             let mut borrow = self.sender.borrow_mut();
             {
@@ -53,9 +96,19 @@ impl<T:Data, P: BytesPush> Push<Message<T>> for Pusher<T, P> {
                 assert!(bytes.len() >= header.required_bytes());
                 let writer = &mut bytes;
                 header.write_to(writer).expect("failed to write header!");
-                element.into_bytes(writer);
+                if let Some(payload) = payload {
+                    use std::io::Write;
+                    writer.write_all(payload).expect("failed to write serialized payload!");
+                }
+                else {
+                    element.into_bytes(&self.codec, writer);
+                }
             }
             borrow.make_valid(header.required_bytes());
+
+            if let Some(traffic) = &self.traffic {
+                traffic.sent(header.length);
+            }
         }
     }
 }
@@ -66,31 +119,35 @@ impl<T:Data, P: BytesPush> Push<Message<T>> for Pusher<T, P> {
 /// not the most efficient thing possible, which would probably instead be something
 /// like the `bytes` crate (../bytes/) which provides an exclusive view of a shared
 /// allocation.
-pub struct Puller<T> {
+pub struct Puller<T, C> {
     _canary: Canary,
     current: Option<Message<T>>,
     receiver: Rc<RefCell<VecDeque<Bytes>>>,    // source of serialized buffers
+    codec: C,
 }
 
-impl<T:Data> Puller<T> {
-    /// Creates a new `Puller` instance from a shared queue.
-    pub fn new(receiver: Rc<RefCell<VecDeque<Bytes>>>, _canary: Canary) -> Puller<T> {
+impl<T, C: Codec<T>> Puller<T, C> {
+    /// Creates a new `Puller` instance from a shared queue and codec.
+    pub fn new(receiver: Rc<RefCell<VecDeque<Bytes>>>, _canary: Canary, codec: C) -> Puller<T, C> {
         Puller {
             _canary,
             current: None,
             receiver,
+            codec,
         }
     }
 }
 
-impl<T:Data> Pull<Message<T>> for Puller<T> {
+impl<T, C: Codec<T>> Pull<Message<T>> for Puller<T, C> {
     #[inline]
     fn pull(&mut self) -> &mut Option<Message<T>> {
+        let codec = &self.codec;
         self.current =
         self.receiver
             .borrow_mut()
             .pop_front()
-            .map(|bytes| unsafe { Message::from_bytes(bytes) });
+            // Peers encode the channel with the same codec and type.
+            .map(|bytes| unsafe { Message::from_bytes(bytes, codec) });
 
         &mut self.current
     }
@@ -102,26 +159,28 @@ impl<T:Data> Pull<Message<T>> for Puller<T> {
 /// not the most efficient thing possible, which would probably instead be something
 /// like the `bytes` crate (../bytes/) which provides an exclusive view of a shared
 /// allocation.
-pub struct PullerInner<T> {
+pub struct PullerInner<T, C> {
     inner: Box<dyn Pull<Message<T>>>,               // inner pullable (e.g. intra-process typed queue)
     _canary: Canary,
     current: Option<Message<T>>,
     receiver: Rc<RefCell<VecDeque<Bytes>>>,     // source of serialized buffers
+    codec: C,
 }
 
-impl<T:Data> PullerInner<T> {
-    /// Creates a new `PullerInner` instance from a shared queue.
-    pub fn new(inner: Box<dyn Pull<Message<T>>>, receiver: Rc<RefCell<VecDeque<Bytes>>>, _canary: Canary) -> Self {
+impl<T, C: Codec<T>> PullerInner<T, C> {
+    /// Creates a new `PullerInner` instance from a shared queue and codec.
+    pub fn new(inner: Box<dyn Pull<Message<T>>>, receiver: Rc<RefCell<VecDeque<Bytes>>>, _canary: Canary, codec: C) -> Self {
         PullerInner {
             inner,
             _canary,
             current: None,
             receiver,
+            codec,
         }
     }
 }
 
-impl<T:Data> Pull<Message<T>> for PullerInner<T> {
+impl<T, C: Codec<T>> Pull<Message<T>> for PullerInner<T, C> {
     #[inline]
     fn pull(&mut self) -> &mut Option<Message<T>> {
 
@@ -130,11 +189,13 @@ impl<T:Data> Pull<Message<T>> for PullerInner<T> {
             inner
         }
         else {
+            let codec = &self.codec;
             self.current =
             self.receiver
                 .borrow_mut()
                 .pop_front()
-                .map(|bytes| unsafe { Message::from_bytes(bytes) });
+                // Peers encode the channel with the same codec and type.
+                .map(|bytes| unsafe { Message::from_bytes(bytes, codec) });
 
             &mut self.current
         }
@@ -0,0 +1,377 @@
+//! Authentication and encryption of links between processes.
+//!
+//! By default, processes connect with a fixed handshake and exchange data in cleartext, so that
+//! any process that knows the handshake can join a computation. With a pre-shared key, each
+//! connection is instead authenticated by an HMAC-SHA256 challenge and response in both
+//! directions, from which a key for each direction of the link is derived. All subsequent data
+//! are sealed in frames with ChaCha20-Poly1305, and a frame that fails authentication takes down
+//! the receiving process.
+//!
+//! Pre-shared keys require the `encryption` feature.
+//!
+//! # Examples
+//! ```
+//! use timely_communication::{Allocate, Configuration, Message};
+//! use timely_communication::allocator::zero_copy::compression::Compression;
+//! use timely_communication::allocator::zero_copy::security::Security;
+//!
+//! let security = Security::PreSharedKey(b"a secret shared by both processes".to_vec());
+//! if !security.available() { return; }
+//!
+//! // two processes over loopback, each run by a thread of this process.
+//! let addresses = vec!["127.0.0.1:2201".to_owned(), "127.0.0.1:2202".to_owned()];
+//! let processes: Vec<_> = (0 .. 2).map(|process| {
+//!     let config = Configuration::Cluster {
+//!         threads: vec![1, 1],
+//!         process,
+//!         addresses: addresses.clone(),
+//!         report: false,
+//!         compression: Compression::none(),
+//!         security: security.clone(),
+//!         log_fn: Box::new(|_| None),
+//!     };
+//!     std::thread::spawn(move || {
+//!         let guards = timely_communication::initialize(config, |mut allocator| {
+//!             let (mut senders, mut receiver) = allocator.allocate(0);
+//!             for sender in senders.iter_mut() {
+//!                 sender.send(Message::from_typed(format!("hello from {}", allocator.index())));
+//!                 sender.done();
+//!             }
+//!             let mut received = Vec::new();
+//!             while received.len() < allocator.peers() {
+//!                 allocator.receive();
+//!                 if let Some(message) = receiver.recv() {
+//!                     received.push(String::clone(&message));
+//!                 }
+//!                 allocator.release();
+//!             }
+//!             received.sort();
+//!             received
+//!         });
+//!         guards.unwrap().join().into_iter().map(|result| result.unwrap()).collect::<Vec<_>>()
+//!     })
+//! }).collect();
+//!
+//! for process in processes {
+//!     assert_eq!(process.join().unwrap(), vec![vec!["hello from 0".to_owned(), "hello from 1".to_owned()]]);
+//! }
+//! ```
+
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// The minimum length in bytes of a pre-shared key.
+pub const MIN_KEY_LENGTH: usize = 16;
+
+/// The largest plaintext sealed in one frame.
+const FRAME_CAPACITY: usize = 1 << 16;
+/// The bytes added to each sealed frame by its authentication tag.
+const TAG_LENGTH: usize = 16;
+
+/// How links between processes are secured.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Security {
+    /// Connections are not authenticated, and data are sent in cleartext.
+    Plaintext,
+    /// Connections are authenticated with a key known to all processes, and data are encrypted.
+    PreSharedKey(Vec<u8>),
+}
+
+impl Default for Security {
+    fn default() -> Self { Security::Plaintext }
+}
+
+// Keys are not printed, so that configurations can be logged.
+impl ::std::fmt::Debug for Security {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match self {
+            Security::Plaintext => write!(f, "Plaintext"),
+            Security::PreSharedKey(_) => write!(f, "PreSharedKey(..)"),
+        }
+    }
+}
+
+impl Security {
+    /// Reads a pre-shared key from the file at `path`, ignoring surrounding whitespace.
+    pub fn from_key_file<P: AsRef<Path>>(path: P) -> Result<Security, String> {
+        let path = path.as_ref();
+        let contents = ::std::fs::read(path).map_err(|e| format!("failed to read key file {}: {}", path.display(), e))?;
+        let start = contents.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(contents.len());
+        let end = contents.iter().rposition(|b| !b.is_ascii_whitespace()).map(|x| x + 1).unwrap_or(start);
+        let key = contents[start .. end].to_vec();
+        if key.len() < MIN_KEY_LENGTH {
+            return Err(format!("key in {} is shorter than {} bytes", path.display(), MIN_KEY_LENGTH));
+        }
+        Ok(Security::PreSharedKey(key))
+    }
+
+    /// Indicates whether links can be secured in this way in this build.
+    pub fn available(&self) -> bool {
+        match self {
+            Security::Plaintext => true,
+            Security::PreSharedKey(_) => cfg!(feature = "encryption"),
+        }
+    }
+}
+
+/// The encryption state of both directions of an authenticated link.
+pub struct Session {
+    send: Cipher,
+    recv: Cipher,
+}
+
+impl Session {
+    /// Separates the states for sending and receiving.
+    pub fn split(self) -> (Cipher, Cipher) {
+        (self.send, self.recv)
+    }
+}
+
+/// Authenticates the peer at the other end of `stream`, once the handshake magic and indices are exchanged.
+///
+/// The process `connector` initiated the connection to the process `acceptor`, and `connecting`
+/// indicates which of the two is calling. Returns the session keys for the link, or `None` if the
+/// link is not secured.
+pub fn authenticate<S: Read+Write>(stream: &mut S, security: &Security, connector: usize, acceptor: usize, connecting: bool) -> io::Result<Option<Session>> {
+    match security {
+        Security::Plaintext => Ok(None),
+        Security::PreSharedKey(key) => psk::authenticate(stream, key, connector, acceptor, connecting).map(Some),
+    }
+}
+
+/// A writer that seals data written through it into authenticated, encrypted frames.
+///
+/// Without a cipher, data are written through unchanged. Data are only sealed once a frame fills
+/// or the writer is flushed.
+pub struct SealedWriter<W: Write> {
+    inner: W,
+    cipher: Option<Cipher>,
+    frame: Vec<u8>,
+}
+
+impl<W: Write> SealedWriter<W> {
+    /// Seals data written to `inner` with `cipher`, if supplied.
+    pub fn new(inner: W, cipher: Option<Cipher>) -> Self {
+        SealedWriter { inner, cipher, frame: Vec::new() }
+    }
+    /// The underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+    /// Seals and writes the current frame, if it is not empty.
+    fn seal_frame(&mut self) -> io::Result<()> {
+        if let Some(cipher) = self.cipher.as_mut() {
+            if !self.frame.is_empty() {
+                cipher.seal(&mut self.frame)?;
+                self.inner.write_all(&(self.frame.len() as u32).to_le_bytes())?;
+                self.inner.write_all(&self.frame[..])?;
+                self.frame.clear();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for SealedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.cipher.is_none() {
+            return self.inner.write(buf);
+        }
+        let accepted = ::std::cmp::min(buf.len(), FRAME_CAPACITY - self.frame.len());
+        self.frame.extend_from_slice(&buf[.. accepted]);
+        if self.frame.len() == FRAME_CAPACITY {
+            self.seal_frame()?;
+        }
+        Ok(accepted)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.seal_frame()?;
+        self.inner.flush()
+    }
+}
+
+/// A reader that opens frames sealed by a `SealedWriter`.
+///
+/// Without a cipher, data are read through unchanged. A frame that fails authentication is
+/// reported as an error of kind `InvalidData`.
+pub struct OpenedReader<R: Read> {
+    inner: R,
+    cipher: Option<Cipher>,
+    frame: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> OpenedReader<R> {
+    /// Opens data read from `inner` with `cipher`, if supplied.
+    pub fn new(inner: R, cipher: Option<Cipher>) -> Self {
+        OpenedReader { inner, cipher, frame: Vec::new(), position: 0 }
+    }
+    /// Reads the length of the next frame, or `None` if the stream ends cleanly before it.
+    fn read_length(&mut self) -> io::Result<Option<usize>> {
+        let mut length = [0u8; 4];
+        let mut filled = 0;
+        while filled < length.len() {
+            match self.inner.read(&mut length[filled ..])? {
+                0 if filled == 0 => return Ok(None),
+                0 => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "stream ended within frame length")),
+                read => filled += read,
+            }
+        }
+        Ok(Some(u32::from_le_bytes(length) as usize))
+    }
+}
+
+impl<R: Read> Read for OpenedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.cipher.is_none() {
+            return self.inner.read(buf);
+        }
+        if self.position == self.frame.len() {
+            let length = match self.read_length()? {
+                Some(length) => length,
+                None => return Ok(0),
+            };
+            if length <= TAG_LENGTH || length > FRAME_CAPACITY + TAG_LENGTH {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid frame length {}", length)));
+            }
+            self.frame.resize(length, 0);
+            self.inner.read_exact(&mut self.frame[..])?;
+            self.cipher.as_mut().expect("cipher unexpectedly absent").open(&mut self.frame)?;
+            self.position = 0;
+        }
+        let available = &self.frame[self.position ..];
+        let count = ::std::cmp::min(available.len(), buf.len());
+        buf[.. count].copy_from_slice(&available[.. count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+pub use self::psk::Cipher;
+
+#[cfg(feature = "encryption")]
+mod psk {
+
+    use std::io::{self, Read, Write};
+
+    use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+    use chacha20poly1305::aead::{AeadInPlace, KeyInit};
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    use super::Session;
+
+    type HmacSha256 = Hmac<Sha256>;
+
+    const NONCE_LENGTH: usize = 32;
+
+    /// The encryption state of one direction of a link.
+    pub struct Cipher {
+        aead: ChaCha20Poly1305,
+        counter: u64,
+    }
+
+    impl Cipher {
+        fn new(key: &[u8]) -> Self {
+            Cipher { aead: ChaCha20Poly1305::new(Key::from_slice(key)), counter: 0 }
+        }
+        /// Frames are numbered, so that replayed, dropped, or reordered frames fail to open.
+        fn next_nonce(&mut self) -> Nonce {
+            let mut nonce = [0u8; 12];
+            nonce[4..].copy_from_slice(&self.counter.to_le_bytes());
+            self.counter = self.counter.checked_add(1).expect("frame counter exhausted");
+            *Nonce::from_slice(&nonce)
+        }
+        /// Encrypts `frame` in place, appending its authentication tag.
+        pub(super) fn seal(&mut self, frame: &mut Vec<u8>) -> io::Result<()> {
+            let nonce = self.next_nonce();
+            self.aead
+                .encrypt_in_place(&nonce, b"", frame)
+                .map_err(|_| io::Error::new(io::ErrorKind::Other, "failed to seal frame"))
+        }
+        /// Authenticates and decrypts `frame` in place, removing its authentication tag.
+        pub(super) fn open(&mut self, frame: &mut Vec<u8>) -> io::Result<()> {
+            let nonce = self.next_nonce();
+            self.aead
+                .decrypt_in_place(&nonce, b"", frame)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "failed to authenticate frame"))
+        }
+    }
+
+    /// A message authentication code of `parts` under `key`, for the purpose `label`.
+    fn mac(key: &[u8], label: &[u8], parts: &[&[u8]]) -> HmacSha256 {
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(label);
+        for part in parts.iter() {
+            mac.update(part);
+        }
+        mac
+    }
+
+    pub(super) fn authenticate<S: Read+Write>(stream: &mut S, key: &[u8], connector: usize, acceptor: usize, connecting: bool) -> io::Result<Session> {
+
+        let mut connector_nonce = [0u8; NONCE_LENGTH];
+        let mut acceptor_nonce = [0u8; NONCE_LENGTH];
+        let own_nonce = if connecting { &mut connector_nonce } else { &mut acceptor_nonce };
+        getrandom::getrandom(own_nonce).map_err(|e| io::Error::new(io::ErrorKind::Other, format!("failed to generate nonce: {}", e)))?;
+
+        let connector_bytes = (connector as u64).to_le_bytes();
+        let acceptor_bytes = (acceptor as u64).to_le_bytes();
+        let rejected = || io::Error::new(io::ErrorKind::PermissionDenied, "peer failed to authenticate with the pre-shared key");
+
+        // The connector sends a challenge, which the acceptor answers with its own challenge and proof of the key.
+        let mut proof = [0u8; 32];
+        if connecting {
+            stream.write_all(&connector_nonce)?;
+            stream.flush()?;
+            stream.read_exact(&mut acceptor_nonce)?;
+            stream.read_exact(&mut proof)?;
+            let transcript: [&[u8]; 4] = [&connector_bytes, &acceptor_bytes, &connector_nonce, &acceptor_nonce];
+            mac(key, b"timely acceptor proof", &transcript).verify_slice(&proof).map_err(|_| rejected())?;
+            stream.write_all(&mac(key, b"timely connector proof", &transcript).finalize().into_bytes())?;
+            stream.flush()?;
+        }
+        else {
+            stream.read_exact(&mut connector_nonce)?;
+            let transcript: [&[u8]; 4] = [&connector_bytes, &acceptor_bytes, &connector_nonce, &acceptor_nonce];
+            stream.write_all(&acceptor_nonce)?;
+            stream.write_all(&mac(key, b"timely acceptor proof", &transcript).finalize().into_bytes())?;
+            stream.flush()?;
+            stream.read_exact(&mut proof)?;
+            mac(key, b"timely connector proof", &transcript).verify_slice(&proof).map_err(|_| rejected())?;
+        }
+
+        // Each direction of the link has its own key, fresh to this connection.
+        let transcript: [&[u8]; 4] = [&connector_bytes, &acceptor_bytes, &connector_nonce, &acceptor_nonce];
+        let outbound = Cipher::new(&mac(key, b"timely connector key", &transcript).finalize().into_bytes());
+        let inbound = Cipher::new(&mac(key, b"timely acceptor key", &transcript).finalize().into_bytes());
+        let (send, recv) = if connecting { (outbound, inbound) } else { (inbound, outbound) };
+        Ok(Session { send, recv })
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+mod psk {
+
+    use std::io::{self, Read, Write};
+
+    use super::Session;
+
+    /// The encryption state of one direction of a link.
+    ///
+    /// This build does not support encryption, and so no states exist.
+    pub enum Cipher { }
+
+    impl Cipher {
+        pub(super) fn seal(&mut self, _frame: &mut Vec<u8>) -> io::Result<()> {
+            match *self { }
+        }
+        pub(super) fn open(&mut self, _frame: &mut Vec<u8>) -> io::Result<()> {
+            match *self { }
+        }
+    }
+
+    pub(super) fn authenticate<S: Read+Write>(_stream: &mut S, _key: &[u8], _connector: usize, _acceptor: usize, _connecting: bool) -> io::Result<Session> {
+        Err(io::Error::new(io::ErrorKind::Other, "pre-shared keys require the `encryption` feature"))
+    }
+}
@@ -5,9 +5,11 @@ use std::net::TcpStream;
 use crossbeam_channel::{Sender, Receiver};
 
 use crate::networking::MessageHeader;
+use crate::metrics::{Metrics, Direction};
 
 use super::bytes_slab::BytesSlab;
 use super::bytes_exchange::MergeQueue;
+use super::security::{Cipher, SealedWriter, OpenedReader};
 
 use logging_core::Logger;
 
@@ -19,20 +21,28 @@ use crate::logging::{CommunicationEvent, CommunicationSetup, MessageEvent, State
 /// messages, followed by a header for a zero length message indicating the end of stream.
 /// If the stream ends without being shut down, the receive thread panics in an attempt to
 /// take down the computation and cause the failures to cascade.
+/// Allocations of the receive buffer are recorded in `metrics`. If a `cipher` is supplied, the
+/// stream is read as frames sealed by the remote `send_loop`, and a frame that fails to open
+/// takes down the computation in the same way.
 pub fn recv_loop(
-    mut reader: TcpStream,
+    reader: TcpStream,
     targets: Vec<Receiver<MergeQueue>>,
     worker_offset: usize,
     process: usize,
     remote: usize,
+    cipher: Option<Cipher>,
+    metrics: Metrics,
     mut logger: Option<Logger<CommunicationEvent, CommunicationSetup>>)
 {
+    let mut reader = OpenedReader::new(reader, cipher);
+
     // Log the receive thread's start.
     logger.as_mut().map(|l| l.log(StateEvent { send: false, process, remote, start: true }));
 
     let mut targets: Vec<MergeQueue> = targets.into_iter().map(|x| x.recv().expect("Failed to receive MergeQueue")).collect();
 
     let mut buffer = BytesSlab::new(20);
+    metrics.register_slab(None, remote, Direction::Recv, buffer.reallocations());
 
     // Where we stash Bytes before handing them off.
     let mut stageds = Vec::with_capacity(targets.len());
@@ -111,12 +121,14 @@ pub fn recv_loop(
 ///
 /// The intended communication pattern is a sequence of (header, message)^* for valid
 /// messages, followed by a header for a zero length message indicating the end of stream.
+/// If a `cipher` is supplied, the stream is sealed in frames.
 pub fn send_loop(
     // TODO: Maybe we don't need BufWriter with consolidation in writes.
     writer: TcpStream,
     sources: Vec<Sender<MergeQueue>>,
     process: usize,
     remote: usize,
+    cipher: Option<Cipher>,
     mut logger: Option<Logger<CommunicationEvent, CommunicationSetup>>)
 {
 
@@ -130,7 +142,7 @@ pub fn send_loop(
         queue
     }).collect();
 
-    let mut writer = ::std::io::BufWriter::with_capacity(1 << 16, writer);
+    let mut writer = SealedWriter::new(::std::io::BufWriter::with_capacity(1 << 16, writer), cipher);
     let mut stash = Vec::new();
 
     while !sources.is_empty() {
@@ -181,10 +193,11 @@ pub fn send_loop(
         target:     0,
         length:     0,
         seqno:      0,
+        codec:      0,
     };
     header.write_to(&mut writer).expect("Failed to write header!");
     writer.flush().expect("Failed to flush writer.");
-    writer.get_mut().shutdown(::std::net::Shutdown::Write).expect("Write shutdown failed");
+    writer.get_mut().get_mut().shutdown(::std::net::Shutdown::Write).expect("Write shutdown failed");
     logger.as_mut().map(|logger| logger.log(MessageEvent { is_send: true, header }));
 
     // Log the receive thread's start.
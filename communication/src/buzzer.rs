@@ -1,22 +1,137 @@
 //! A type that can unpark specific threads.
 
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::Waker;
 use std::thread::Thread;
 
-/// Can unpark a specific thread.
+/// Can unpark a specific thread, and wake the task driving its worker, if any.
 #[derive(Clone)]
 pub struct Buzzer {
     thread: Thread,
+    waker: WakerSlot,
 }
 
 impl Buzzer {
     /// Creates a new buzzer for the current thread.
     pub fn new() -> Self {
         Self {
-            thread: std::thread::current()
+            thread: std::thread::current(),
+            waker: WakerSlot::current(),
         }
     }
-    /// Unparks the target thread.
+    /// Unparks the target thread, and wakes its registered task.
     pub fn buzz(&self) {
+        self.waker.wake();
         self.thread.unpark()
     }
-}
\ No newline at end of file
+}
+
+/// Shared slots for the wakers of a thread's worker.
+///
+/// Each thread has one set of slots, captured by the buzzers created on that thread. A worker that
+/// is driven as a future registers its task's waker, and a worker blocked in `poll(2)` registers
+/// the waker of its self-pipe, so that events which would unpark the thread also wake either. The
+/// two are held separately, so that neither replaces the other.
+#[derive(Clone, Default)]
+pub struct WakerSlot {
+    wakers: Arc<Mutex<Wakers>>,
+    // Set while either waker is registered, so that waking an empty slot takes no lock.
+    registered: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct Wakers {
+    task: Option<Waker>,
+    poll: Option<Waker>,
+}
+
+thread_local! {
+    static CURRENT_SLOT: WakerSlot = WakerSlot::default();
+}
+
+impl WakerSlot {
+    /// The slot of the current thread.
+    pub fn current() -> Self {
+        CURRENT_SLOT.with(|slot| slot.clone())
+    }
+    /// Registers the waker of the task driving the worker, to be woken by subsequent events.
+    pub fn register(&self, waker: &Waker) {
+        let mut wakers = self.wakers.lock().expect("waker slot poisoned");
+        Self::replace(&mut wakers.task, waker);
+        self.update(&wakers);
+    }
+    /// Removes any registered task waker.
+    pub fn clear(&self) {
+        let mut wakers = self.wakers.lock().expect("waker slot poisoned");
+        wakers.task = None;
+        self.update(&wakers);
+    }
+    /// Registers a waker that interrupts the worker's `poll(2)`, to be woken by subsequent events.
+    pub fn register_poll(&self, waker: &Waker) {
+        let mut wakers = self.wakers.lock().expect("waker slot poisoned");
+        Self::replace(&mut wakers.poll, waker);
+        self.update(&wakers);
+    }
+    /// Removes any registered poll waker.
+    pub fn clear_poll(&self) {
+        let mut wakers = self.wakers.lock().expect("waker slot poisoned");
+        wakers.poll = None;
+        self.update(&wakers);
+    }
+    /// Wakes the registered wakers, if any.
+    pub fn wake(&self) {
+        // Registration precedes the worker's inspection of its queues, so an event enqueued before
+        // this load either sees the flag set or is seen by the worker.
+        if !self.registered.load(Ordering::SeqCst) {
+            return;
+        }
+        let wakers = self.wakers.lock().expect("waker slot poisoned");
+        for waker in wakers.task.iter().chain(wakers.poll.iter()) {
+            waker.wake_by_ref();
+        }
+    }
+    fn update(&self, wakers: &Wakers) {
+        self.registered.store(wakers.task.is_some() || wakers.poll.is_some(), Ordering::SeqCst);
+    }
+    fn replace(slot: &mut Option<Waker>, waker: &Waker) {
+        if !slot.as_ref().map(|current| current.will_wake(waker)).unwrap_or(false) {
+            *slot = Some(waker.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Wake, Waker};
+
+    use super::WakerSlot;
+
+    struct Count(AtomicUsize);
+    impl Wake for Count {
+        fn wake(self: Arc<Self>) { self.0.fetch_add(1, Ordering::SeqCst); }
+    }
+
+    #[test]
+    fn separate_wakers() {
+        let slot = WakerSlot::default();
+        let task = Arc::new(Count(AtomicUsize::new(0)));
+        let poll = Arc::new(Count(AtomicUsize::new(0)));
+        slot.register(&Waker::from(task.clone()));
+        slot.register_poll(&Waker::from(poll.clone()));
+        slot.wake();
+        assert_eq!((task.0.load(Ordering::SeqCst), poll.0.load(Ordering::SeqCst)), (1, 1));
+
+        slot.clear_poll();
+        slot.wake();
+        assert_eq!((task.0.load(Ordering::SeqCst), poll.0.load(Ordering::SeqCst)), (2, 1));
+
+        slot.clear();
+        assert!(!slot.registered.load(Ordering::SeqCst));
+        slot.wake();
+        assert_eq!((task.0.load(Ordering::SeqCst), poll.0.load(Ordering::SeqCst)), (2, 1));
+    }
+}
@@ -0,0 +1,325 @@
+//! Serialization strategies for typed channels.
+//!
+//! A `Codec<T>` determines how instances of `T` are written to binary data and read back, for
+//! those channels that must serialize their contents (for example, to send them to another
+//! process). Codecs are chosen per channel, through `Allocate::allocate_with_codec`. Channels
+//! allocated with `Allocate::allocate` use `DefaultCodec`, which is abomonation unless the crate
+//! is built with the `bincode` feature. `BincodeCodec` is available for individual channels in
+//! either case.
+//!
+//! All workers must use the same codec for the same channel.
+
+use std::io::Write;
+use std::mem::size_of;
+
+use abomonation::Abomonation;
+use bytes::arc::Bytes;
+
+use crate::Message;
+
+/// A serialization strategy for instances of `T`.
+pub trait Codec<T>: Clone+'static {
+    /// The number of bytes required to encode `typed`.
+    fn length_in_bytes(&self, typed: &T) -> usize;
+    /// Writes the binary representation of `typed` into `writer`.
+    fn encode<W: Write>(&self, typed: &T, writer: &mut W);
+    /// Decodes binary data written by `encode`.
+    ///
+    /// # Safety
+    ///
+    /// Codecs may trust their input, and `bytes` must have been written by `encode` or
+    /// `encode_message` of the same codec for the same type `T`.
+    unsafe fn decode(&self, bytes: Bytes) -> Message<T>;
+
+    /// The number of bytes required to encode `message`.
+    ///
+    /// Codecs may override this method to use a binary representation held by the message.
+    fn message_length(&self, message: &Message<T>) -> usize {
+        self.length_in_bytes(&**message)
+    }
+    /// Writes the binary representation of `message` into `writer`.
+    ///
+    /// Codecs may override this method to use a binary representation held by the message.
+    fn encode_message<W: Write>(&self, message: &Message<T>, writer: &mut W) {
+        self.encode(&**message, writer)
+    }
+}
+
+/// The codec used by `Allocate::allocate`.
+#[cfg(not(feature = "bincode"))]
+pub type DefaultCodec = AbomonationCodec;
+/// The codec used by `Allocate::allocate`.
+#[cfg(feature = "bincode")]
+pub type DefaultCodec = BincodeCodec;
+
+/// A codec based on `Abomonation`, which decodes data in place.
+///
+/// Decoded messages reference the received binary data, and are only copied if ownership
+/// is required. Decoding does not validate its input, which is only appropriate when all
+/// binary data come from other workers of the same computation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AbomonationCodec;
+
+impl<T: Abomonation+'static> Codec<T> for AbomonationCodec {
+    fn length_in_bytes(&self, typed: &T) -> usize {
+        abomonation::measure(typed)
+    }
+    fn encode<W: Write>(&self, typed: &T, writer: &mut W) {
+        unsafe { abomonation::encode(typed, writer).expect("AbomonationCodec::encode(): Abomonation::encode failed"); }
+    }
+    unsafe fn decode(&self, bytes: Bytes) -> Message<T> {
+        // Abomonation trusts the bytes, which the caller guarantees were written by `encode`.
+        let abomonated = abomonation::abomonated::Abomonated::new(bytes).expect("Abomonated::new() failed.");
+        Message::from_abomonated(abomonated)
+    }
+    fn message_length(&self, message: &Message<T>) -> usize {
+        message.abomonated_bytes()
+               .map(|bytes| bytes.len())
+               .unwrap_or_else(|| self.length_in_bytes(&**message))
+    }
+    fn encode_message<W: Write>(&self, message: &Message<T>, writer: &mut W) {
+        if let Some(bytes) = message.abomonated_bytes() {
+            writer.write_all(bytes).expect("AbomonationCodec::encode_message(): write_all failed.");
+        }
+        else {
+            self.encode(&**message, writer);
+        }
+    }
+}
+
+/// A codec based on `bincode`, which validates its input and decodes into owned data.
+///
+/// It is available whatever the default codec, so that individual channels may use it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BincodeCodec;
+
+impl<T: ::serde::Serialize+for<'a>::serde::Deserialize<'a>+'static> Codec<T> for BincodeCodec {
+    fn length_in_bytes(&self, typed: &T) -> usize {
+        ::bincode::serialized_size(typed).expect("bincode::serialized_size() failed") as usize
+    }
+    fn encode<W: Write>(&self, typed: &T, writer: &mut W) {
+        ::bincode::serialize_into(writer, typed).expect("bincode::serialize_into() failed");
+    }
+    unsafe fn decode(&self, bytes: Bytes) -> Message<T> {
+        let typed = ::bincode::deserialize(&bytes[..]).expect("bincode::deserialize() failed");
+        Message::from_typed(typed)
+    }
+}
+
+/// Types whose values may be transferred between processes as raw memory images.
+///
+/// # Safety
+///
+/// Implementors must have no padding bytes, pointers, or references, and every bit pattern of
+/// their size must be a valid value. Memory images are only meaningful between processes with
+/// the same architecture.
+pub unsafe trait Plain: Copy+'static { }
+
+macro_rules! implement_plain {
+    ($($t:ty),*) => { $( unsafe impl Plain for $t { } )* };
+}
+implement_plain!((), u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+unsafe impl<P: Plain, const N: usize> Plain for [P; N] { }
+
+/// Fixed-size values written field by field, each a `Plain` value, so that padding between
+/// fields is never read.
+pub trait Fields: Copy+'static {
+    /// The number of bytes written by `write`.
+    fn length() -> usize;
+    /// Writes the fields into `writer`.
+    fn write<W: Write>(&self, writer: &mut W);
+    /// Reads fields written by `write` from the start of `bytes`, which holds at least `length()` bytes.
+    fn read(bytes: &[u8]) -> Self;
+}
+
+impl<P: Plain> Fields for P {
+    fn length() -> usize { size_of::<P>() }
+    fn write<W: Write>(&self, writer: &mut W) {
+        writer.write_all(as_bytes(::std::slice::from_ref(self))).expect("Fields::write(): write_all failed.");
+    }
+    fn read(bytes: &[u8]) -> Self {
+        assert!(bytes.len() >= size_of::<P>(), "Fields::read(): insufficient data");
+        // Any bit pattern is a valid `P`.
+        unsafe { ::std::ptr::read_unaligned(bytes.as_ptr() as *const P) }
+    }
+}
+
+macro_rules! implement_fields {
+    ($($name:ident),*) => {
+        impl<$($name: Fields),*> Fields for ($($name,)*) {
+            fn length() -> usize { 0 $(+ $name::length())* }
+            #[allow(non_snake_case)]
+            fn write<W: Write>(&self, writer: &mut W) {
+                let ($(ref $name,)*) = *self;
+                $( $name.write(writer); )*
+            }
+            #[allow(unused_assignments)]
+            fn read(mut bytes: &[u8]) -> Self {
+                ($({
+                    let field = $name::read(bytes);
+                    bytes = &bytes[$name::length() ..];
+                    field
+                },)*)
+            }
+        }
+    };
+}
+implement_fields!(A, B);
+implement_fields!(A, B, C);
+implement_fields!(A, B, C, D);
+
+/// Views a slice of plain values as its underlying bytes.
+fn as_bytes<P: Plain>(slice: &[P]) -> &[u8] {
+    // `P` has no padding, so all bytes are initialized.
+    unsafe { ::std::slice::from_raw_parts(slice.as_ptr() as *const u8, ::std::mem::size_of_val(slice)) }
+}
+
+/// Types that decompose into a fixed-size prefix and a column of plain records.
+///
+/// The prefix and the records are transferred as raw memory images, and so are only meaningful
+/// between processes with the same architecture.
+///
+/// # Safety
+///
+/// `ColumnarCodec` decodes columns in place, as vectors borrowing the received data. Implementors
+/// must move the column unchanged between `from_parts` and `into_parts`, without reallocating,
+/// resizing, or dropping it, and must not otherwise free it.
+pub unsafe trait Columnar: Sized {
+    /// Fixed-size information accompanying the column.
+    type Prefix: Fields;
+    /// The type of records in the column.
+    type Record: Plain;
+    /// The fixed-size information.
+    fn prefix(&self) -> Self::Prefix;
+    /// The column of records.
+    fn column(&self) -> &[Self::Record];
+    /// Reassembles an instance from its prefix and column.
+    fn from_parts(prefix: Self::Prefix, column: Vec<Self::Record>) -> Self;
+    /// Takes an instance apart into its prefix and column.
+    fn into_parts(self) -> (Self::Prefix, Vec<Self::Record>);
+}
+
+unsafe impl<R: Plain> Columnar for Vec<R> {
+    type Prefix = ();
+    type Record = R;
+    fn prefix(&self) { }
+    fn column(&self) -> &[R] { &self[..] }
+    fn from_parts(_prefix: (), column: Vec<R>) -> Self { column }
+    fn into_parts(self) -> ((), Vec<R>) { ((), self) }
+}
+
+/// A codec for `Columnar` types, which writes the column as one contiguous memory image.
+///
+/// Neither encoding nor decoding visits individual records. Decoding does not copy the column
+/// either, if the received data are suitably aligned for its records: the decoded message views
+/// the column in place, and keeps the received data alive for as long as it does. Otherwise the
+/// column is copied in bulk into a vector owned by the message.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ColumnarCodec;
+
+impl<T: Columnar+'static> Codec<T> for ColumnarCodec {
+    fn length_in_bytes(&self, typed: &T) -> usize {
+        size_of::<u64>() + T::Prefix::length() + ::std::mem::size_of_val(typed.column())
+    }
+    fn encode<W: Write>(&self, typed: &T, writer: &mut W) {
+        let column = typed.column();
+        (column.len() as u64).write(writer);
+        typed.prefix().write(writer);
+        writer.write_all(as_bytes(column)).expect("ColumnarCodec::encode(): write_all failed.");
+    }
+    unsafe fn decode(&self, bytes: Bytes) -> Message<T> {
+        // Decoding is safe for any input, as prefixes and records are plain data.
+        let length = u64::read(&bytes[..]) as usize;
+        let prefix = T::Prefix::read(&bytes[size_of::<u64>() ..]);
+        let column_bytes = &bytes[size_of::<u64>() + T::Prefix::length() ..];
+        let column_length = length.checked_mul(size_of::<T::Record>());
+        assert!(column_length.map(|column_length| column_bytes.len() >= column_length).unwrap_or(false), "ColumnarCodec::decode(): insufficient data");
+
+        let start = column_bytes.as_ptr();
+        if length > 0 && size_of::<T::Record>() > 0 && start.align_offset(::std::mem::align_of::<T::Record>()) == 0 {
+            // The vector views the received data, which the message holds; `release` forgets it unfreed.
+            let column = Vec::from_raw_parts(start as *mut T::Record, length, length);
+            Message::from_borrowed(T::from_parts(prefix, column), release::<T>, bytes)
+        }
+        else {
+            let mut column = Vec::<T::Record>::with_capacity(length);
+            ::std::ptr::copy_nonoverlapping(start, column.as_mut_ptr() as *mut u8, length * size_of::<T::Record>());
+            column.set_len(length);
+            Message::from_typed(T::from_parts(prefix, column))
+        }
+    }
+}
+
+/// Takes apart an instance decoded in place, forgetting its borrowed column.
+fn release<T: Columnar>(typed: T) {
+    let (_prefix, column) = typed.into_parts();
+    ::std::mem::forget(column);
+}
+
+#[cfg(test)]
+mod tests {
+
+    use bytes::arc::Bytes;
+    use super::{Codec, ColumnarCodec, AbomonationCodec, BincodeCodec};
+
+    fn round_trip<T, C: Codec<T>>(codec: C, typed: T) -> T where T: Clone {
+        let mut bytes = Vec::new();
+        codec.encode(&typed, &mut bytes);
+        assert_eq!(bytes.len(), codec.length_in_bytes(&typed));
+        let message = unsafe { codec.decode(Bytes::from(bytes.into_boxed_slice())) };
+        (*message).clone()
+    }
+
+    #[test]
+    fn columnar() {
+        let records = vec![[1u8, 2, 3], [4, 5, 6]];
+        assert_eq!(round_trip(ColumnarCodec, records.clone()), records);
+        let records: Vec<f64> = Vec::new();
+        assert_eq!(round_trip(ColumnarCodec, records.clone()), records);
+    }
+
+    #[test]
+    fn columnar_in_place() {
+        let records = vec![1u64, 2, 3];
+        let mut bytes = Vec::new();
+        ColumnarCodec.encode(&records, &mut bytes);
+
+        // A leading byte misaligns the column, which is then copied; otherwise it is viewed in place.
+        for skip in 0 .. 2 {
+            let mut padded = vec![0u8; skip];
+            padded.extend_from_slice(&bytes[..]);
+            let mut padded = Bytes::from(padded.into_boxed_slice());
+            let _ = padded.extract_to(skip);
+            let column_start = padded[8 ..].as_ptr();
+            let aligned = column_start.align_offset(::std::mem::align_of::<u64>()) == 0;
+
+            let message = unsafe { <ColumnarCodec as Codec<Vec<u64>>>::decode(&ColumnarCodec, padded) };
+            assert_eq!(*message, records);
+            assert_eq!(message.as_ptr() as *const u8 == column_start, aligned);
+            let mut message = message;
+            message.as_mut().push(4);
+            assert_eq!(*message, vec![1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "insufficient data")]
+    fn columnar_truncated() {
+        let mut bytes = Vec::new();
+        ColumnarCodec.encode(&vec![1u64, 2, 3], &mut bytes);
+        bytes.truncate(bytes.len() - 1);
+        let _ = unsafe { <ColumnarCodec as Codec<Vec<u64>>>::decode(&ColumnarCodec, Bytes::from(bytes.into_boxed_slice())) };
+    }
+
+    #[test]
+    fn bincode() {
+        let records = vec!["hello".to_owned(), "world".to_owned()];
+        assert_eq!(round_trip(BincodeCodec, records.clone()), records);
+    }
+
+    #[test]
+    fn abomonation() {
+        let records = vec!["hello".to_owned(), "world".to_owned()];
+        assert_eq!(round_trip(AbomonationCodec, records.clone()), records);
+    }
+}
@@ -10,10 +10,17 @@ use std::sync::Arc;
 use std::any::Any;
 
 use crate::allocator::thread::ThreadBuilder;
-use crate::allocator::{AllocateBuilder, Process, Generic, GenericBuilder};
+use crate::allocator::{AllocateBuilder, Process, Simulated, Generic, GenericBuilder};
+use crate::allocator::simulated::SimulationConfig;
 use crate::allocator::zero_copy::initialize::initialize_networking;
+use crate::networking::WorkerLayout;
+use crate::allocator::zero_copy::compression::Compression;
+use crate::allocator::zero_copy::security::Security;
+#[cfg(feature = "getopts")]
+use crate::allocator::zero_copy::compression::Codec;
 
-use crate::logging::{CommunicationSetup, CommunicationEvent};
+use crate::logging::{CommunicationSetup, CommunicationEvent, ThreadRole};
+use crate::placement::{Placement, ThreadPlacement, LogFn};
 use logging_core::Logger;
 
 
@@ -25,17 +32,34 @@ pub enum Configuration {
     Process(usize),
     /// Expect multiple processes.
     Cluster {
-        /// Number of per-process worker threads
-        threads: usize,
+        /// Number of worker threads of each process
+        threads: Vec<usize>,
         /// Identity of this process
         process: usize,
         /// Addresses of all processes
         addresses: Vec<String>,
         /// Verbosely report connection process
         report: bool,
+        /// Compression of large payloads sent between processes
+        compression: Compression,
+        /// Authentication and encryption of links between processes
+        security: Security,
         /// Closure to create a new logger for a communication thread
         log_fn: Box<dyn Fn(CommunicationSetup) -> Option<Logger<CommunicationEvent, CommunicationSetup>> + Send + Sync>,
-    }
+    },
+    /// Simulate multiple processes within this process.
+    Simulated(SimulationConfig),
+    /// Another configuration, whose threads are pinned to cores according to a placement.
+    Placed {
+        /// The configuration whose threads are placed
+        config: Box<Configuration>,
+        /// Cores to pin the threads to
+        placement: Placement,
+        /// Closure to create a new logger reporting the placement of a thread
+        ///
+        /// A cluster reports the placement of its threads to its own loggers instead.
+        log_fn: Box<dyn Fn(CommunicationSetup) -> Option<Logger<CommunicationEvent, CommunicationSetup>> + Send + Sync>,
+    },
 }
 
 #[cfg(feature = "getopts")]
@@ -48,8 +72,12 @@ impl Configuration {
         opts.optopt("w", "threads", "number of per-process worker threads", "NUM");
         opts.optopt("p", "process", "identity of this process", "IDX");
         opts.optopt("n", "processes", "number of processes", "NUM");
-        opts.optopt("h", "hostfile", "text file whose lines are process addresses", "FILE");
+        opts.optopt("h", "hostfile", "text file whose lines are process addresses, each optionally followed by a thread count", "FILE");
         opts.optflag("r", "report", "reports connection progress");
+        opts.optopt("", "compression", "codec for large inter-process payloads (none, lz4, zstd[:LEVEL])", "CODEC");
+        opts.optopt("", "compression-threshold", "payload size in bytes at which to compress (default: 4096)", "BYTES");
+        opts.optopt("", "psk-file", "file holding a key shared by all processes, to authenticate and encrypt links", "FILE");
+        opts.optopt("", "placement", "cores for worker and communication threads (e.g. 0-7,16-23), numa, or none", "CORES");
 
         opts
     }
@@ -60,70 +88,195 @@ impl Configuration {
     pub fn from_args<I: Iterator<Item=String>>(args: I) -> Result<Configuration,String> {
         let opts = Configuration::options();
 
-        opts.parse(args)
-            .map_err(|e| format!("{:?}", e))
-            .map(|matches| {
-
-            // let mut config = Configuration::new(1, 0, Vec::new());
-            let threads = matches.opt_str("w").map(|x| x.parse().unwrap_or(1)).unwrap_or(1);
-            let process = matches.opt_str("p").map(|x| x.parse().unwrap_or(0)).unwrap_or(0);
-            let processes = matches.opt_str("n").map(|x| x.parse().unwrap_or(1)).unwrap_or(1);
-            let report = matches.opt_present("report");
-
-            assert!(process < processes);
-
-            if processes > 1 {
-                let mut addresses = Vec::new();
-                if let Some(hosts) = matches.opt_str("h") {
-                    let reader = ::std::io::BufReader::new(::std::fs::File::open(hosts.clone()).unwrap());
-                    for x in reader.lines().take(processes) {
-                        addresses.push(x.unwrap());
-                    }
-                    if addresses.len() < processes {
-                        panic!("could only read {} addresses from {}, but -n: {}", addresses.len(), hosts, processes);
-                    }
-                }
-                else {
-                    for index in 0..processes {
-                        addresses.push(format!("localhost:{}", 2101 + index));
-                    }
+        let matches = opts.parse(args).map_err(|e| format!("{:?}", e))?;
+        let compression = match matches.opt_str("compression") {
+            Some(codec) => {
+                let codec = Codec::parse(&codec)?;
+                if !codec.available() {
+                    return Err(format!("compression codec {:?} not available in this build", codec));
                 }
+                let threshold = match matches.opt_str("compression-threshold") {
+                    Some(text) => text.parse().map_err(|e| format!("invalid compression threshold {:?}: {}", text, e))?,
+                    None => 4096,
+                };
+                Compression::new(codec, threshold)
+            },
+            None => Compression::none(),
+        };
+        let security = match matches.opt_str("psk-file") {
+            Some(path) => Security::from_key_file(path)?,
+            None => Security::Plaintext,
+        };
+
+        // let mut config = Configuration::new(1, 0, Vec::new());
+        let threads = matches.opt_str("w").map(|x| x.parse().unwrap_or(1)).unwrap_or(1);
+        let process = matches.opt_str("p").map(|x| x.parse().unwrap_or(0)).unwrap_or(0);
+        let processes = matches.opt_str("n").map(|x| x.parse().unwrap_or(1)).unwrap_or(1);
+        let report = matches.opt_present("report");
+
+        assert!(process < processes);
 
-                assert!(processes == addresses.len());
-                Configuration::Cluster {
-                    threads,
-                    process,
-                    addresses,
-                    report,
-                    log_fn: Box::new( | _ | None),
+        let config = if processes > 1 {
+            let mut addresses = Vec::new();
+            let mut process_threads = Vec::new();
+            if let Some(hosts) = matches.opt_str("h") {
+                let reader = ::std::io::BufReader::new(::std::fs::File::open(hosts.clone()).unwrap());
+                for x in reader.lines().take(processes) {
+                    let (address, count) = parse_host(&x.unwrap(), threads)?;
+                    addresses.push(address);
+                    process_threads.push(count);
+                }
+                if addresses.len() < processes {
+                    panic!("could only read {} addresses from {}, but -n: {}", addresses.len(), hosts, processes);
                 }
             }
-            else if threads > 1 { Configuration::Process(threads) }
-            else { Configuration::Thread }
-        })
+            else {
+                for index in 0..processes {
+                    addresses.push(format!("localhost:{}", 2101 + index));
+                    process_threads.push(threads);
+                }
+            }
+
+            assert!(processes == addresses.len());
+            Configuration::Cluster {
+                threads: process_threads,
+                process,
+                addresses,
+                report,
+                compression,
+                security,
+                log_fn: Box::new( | _ | None),
+            }
+        }
+        else if threads > 1 { Configuration::Process(threads) }
+        else { Configuration::Thread };
+
+        let config = match matches.opt_str("placement") {
+            Some(placement) => Configuration::Placed {
+                config: Box::new(config),
+                placement: Placement::parse(&placement)?,
+                log_fn: Box::new( | _ | None),
+            },
+            None => config,
+        };
+
+        Ok(config)
     }
 
     /// Attempts to assemble the described communication infrastructure.
     pub fn try_build(self) -> Result<(Vec<GenericBuilder>, Box<dyn Any+Send>), String> {
+        self.try_build_placed(Placement::Unpinned, None)
+    }
+
+    /// Assembles the communication infrastructure, with threads placed according to `placement`.
+    ///
+    /// The placement of worker threads is reported to loggers constructed by `log_fn`, if supplied.
+    fn try_build_placed(self, placement: Placement, log_fn: Option<LogFn>) -> Result<(Vec<GenericBuilder>, Box<dyn Any+Send>), String> {
         match self {
             Configuration::Thread => {
-                Ok((vec![GenericBuilder::Thread(ThreadBuilder)], Box::new(())))
+                let placement = placement.for_process(0, 1);
+                Ok((place_workers(vec![GenericBuilder::Thread(ThreadBuilder)], &placement, 0, 0, log_fn), Box::new(())))
             },
             Configuration::Process(threads) => {
-                Ok((Process::new_vector(threads).into_iter().map(|x| GenericBuilder::Process(x)).collect(), Box::new(())))
+                let builders = Process::new_vector(threads).into_iter().map(|x| GenericBuilder::Process(x)).collect();
+                let placement = placement.for_process(0, threads);
+                Ok((place_workers(builders, &placement, 0, 0, log_fn), Box::new(())))
             },
-            Configuration::Cluster { threads, process, addresses, report, log_fn } => {
-                match initialize_networking(addresses, process, threads, report, log_fn) {
+            Configuration::Cluster { threads, process, addresses, report, compression, security, log_fn } => {
+                if !compression.codec.available() {
+                    return Err(format!("compression codec {:?} not available in this build", compression.codec));
+                }
+                if !security.available() {
+                    return Err(format!("link security {:?} not available in this build", security));
+                }
+                if threads.len() != addresses.len() {
+                    return Err(format!("thread counts given for {} processes, but {} addresses", threads.len(), addresses.len()));
+                }
+                // Each process runs its workers and a send and a receive thread for each other process.
+                let remotes = addresses.len() - 1;
+                let start = local_threads(&addresses, &threads, process, remotes);
+                let placement = placement.for_process(start, threads[process] + 2 * remotes);
+                let layout = WorkerLayout::new(&threads);
+                let offset = layout.offset(process);
+                let log_fn: LogFn = Arc::new(log_fn);
+                let comm_log_fn = log_fn.clone();
+                match initialize_networking(addresses, process, layout, report, compression, placement.clone(), security, Box::new(move |setup: CommunicationSetup| comm_log_fn(setup))) {
                     Ok((stuff, guard)) => {
-                        Ok((stuff.into_iter().map(|x| GenericBuilder::ZeroCopy(x)).collect(), Box::new(guard)))
+                        let builders = stuff.into_iter().map(|x| GenericBuilder::ZeroCopy(x)).collect();
+                        Ok((place_workers(builders, &placement, process, offset, Some(log_fn)), Box::new(guard)))
                     },
                     Err(err) => Err(format!("failed to initialize networking: {}", err))
                 }
             },
+            Configuration::Simulated(config) => {
+                let builders: Vec<_> = Simulated::new_vector(config).into_iter().map(|x| GenericBuilder::Simulated(x)).collect();
+                let placement = placement.for_process(0, builders.len());
+                Ok((place_workers(builders, &placement, 0, 0, log_fn), Box::new(())))
+            },
+            Configuration::Placed { config, placement, log_fn } => {
+                config.try_build_placed(placement, Some(Arc::new(log_fn)))
+            },
         }
     }
 }
 
+/// The number of threads run by the processes that precede `process` on its host.
+///
+/// Processes share a host if the host parts of their addresses agree, and each runs its workers
+/// and `remotes` pairs of send and receive threads.
+#[cfg(feature = "getopts")]
+fn local_threads(addresses: &[String], threads: &[usize], process: usize, remotes: usize) -> usize {
+    let host = |address: &str| address.rsplitn(2, ':').last().unwrap_or("").to_owned();
+    let my_host = host(&addresses[process]);
+    (0 .. process)
+        .filter(|&index| host(&addresses[index]) == my_host)
+        .map(|index| threads[index] + 2 * remotes)
+        .sum()
+}
+
+/// Wraps the builders of a process's workers so that each first places its thread.
+///
+/// The workers have global indices starting from `offset`, and report their placement to loggers
+/// constructed by `log_fn`, if supplied.
+#[cfg(feature = "getopts")]
+fn place_workers(builders: Vec<GenericBuilder>, placement: &Placement, process: usize, offset: usize, log_fn: Option<LogFn>) -> Vec<GenericBuilder> {
+    if *placement == Placement::Unpinned {
+        return builders;
+    }
+    let cores = placement.cores(builders.len());
+    builders
+        .into_iter()
+        .zip(cores)
+        .enumerate()
+        .map(|(index, (builder, core))| {
+            let placement = ThreadPlacement {
+                core,
+                process,
+                role: ThreadRole::Worker(offset + index),
+                log_fn: log_fn.clone(),
+            };
+            GenericBuilder::Placed(Box::new(builder), placement)
+        })
+        .collect()
+}
+
+/// Parses a hostfile line of the form `address [threads]`.
+///
+/// Lines without a thread count use `default_threads`.
+#[cfg(feature = "getopts")]
+fn parse_host(line: &str, default_threads: usize) -> Result<(String, usize), String> {
+    let mut tokens = line.split_whitespace();
+    let address = tokens.next().ok_or_else(|| "empty line in hostfile".to_owned())?;
+    let threads = match tokens.next() {
+        Some(count) => count.parse().map_err(|e| format!("invalid thread count {:?} for {}: {}", count, address, e))?,
+        None => default_threads,
+    };
+    if tokens.next().is_some() {
+        return Err(format!("unexpected text after thread count for {}", address));
+    }
+    Ok((address.to_owned(), threads))
+}
+
 /// Initializes communication and executes a distributed computation.
 ///
 /// This method allocates an `allocator::Generic` for each thread, spawns local worker threads,
@@ -309,3 +462,36 @@ impl<T:Send+'static> Drop for WorkerGuards<T> {
         // println!("WORKER THREADS JOINED");
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Configuration, local_threads};
+    use crate::allocator::zero_copy::compression::{Codec, Compression};
+
+    fn parse(args: &[&str]) -> Result<Configuration, String> {
+        Configuration::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn compression_args() {
+        match parse(&["-n", "2", "--compression", "none", "--compression-threshold", "100"]) {
+            Ok(Configuration::Cluster { compression, .. }) => assert_eq!(compression, Compression::new(Codec::Uncompressed, 100)),
+            _ => panic!("expected a cluster configuration"),
+        }
+        assert!(parse(&["-n", "2", "--compression", "none", "--compression-threshold", "lots"]).is_err());
+        assert!(parse(&["-n", "2", "--compression", "gzip"]).is_err());
+        assert_eq!(parse(&["-n", "2", "--compression", "lz4"]).is_ok(), cfg!(feature = "lz4"));
+        assert_eq!(parse(&["-n", "2", "--compression", "zstd:3"]).is_ok(), cfg!(feature = "zstd"));
+    }
+
+    #[test]
+    fn local_threads_by_host() {
+        let addresses: Vec<String> = vec!["a:2101", "b:2101", "a:2102", "a:2103"].into_iter().map(String::from).collect();
+        let threads = vec![2, 4, 3, 1];
+        assert_eq!(local_threads(&addresses, &threads, 0, 3), 0);
+        assert_eq!(local_threads(&addresses, &threads, 1, 3), 0);
+        assert_eq!(local_threads(&addresses, &threads, 2, 3), 8);
+        assert_eq!(local_threads(&addresses, &threads, 3, 3), 17);
+    }
+}
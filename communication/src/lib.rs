@@ -7,9 +7,9 @@
 //! receive endpoint. Messages sent into a send endpoint will eventually be received by the corresponding worker,
 //! if it receives often enough. The point-to-point channels are each FIFO, but with no fairness guarantees.
 //!
-//! To be communicated, a type must implement the [`Serialize`](./trait.Serialize.html) trait. A default implementation of `Serialize` is
-//! provided for any type implementing [`Abomonation`](../abomonation/trait.Abomonation.html). To implement other serialization strategies, wrap your type
-//! and implement `Serialize` for your wrapper.
+//! To be communicated, a type must be supported by the [`Codec`](./codec/trait.Codec.html) chosen for its channel. Channels allocated
+//! with `allocate` use the [`DefaultCodec`](./codec/type.DefaultCodec.html), which supports any type implementing [`Data`](./trait.Data.html);
+//! other serialization strategies can be chosen per channel with `allocate_with_codec`.
 //!
 //! Channel endpoints also implement a lower-level `push` and `pull` interface (through the [`Push`](./trait.Push.html) and [`Pull`](./trait.Pull.html)
 //! traits), which is used for more precise control of resources.
@@ -77,10 +77,14 @@
 
 #[cfg(feature = "getopts")]
 extern crate getopts;
-#[cfg(feature = "bincode")]
 extern crate bincode;
-#[cfg(feature = "bincode")]
 extern crate serde;
+#[cfg(feature = "lz4")]
+extern crate lz4_flex;
+#[cfg(feature = "zstd")]
+extern crate zstd;
+#[cfg(target_os = "linux")]
+extern crate libc;
 
 extern crate abomonation;
 #[macro_use] extern crate abomonation_derive;
@@ -90,9 +94,12 @@ extern crate timely_logging as logging_core;
 
 pub mod allocator;
 pub mod networking;
+pub mod placement;
 pub mod initialize;
 pub mod logging;
 pub mod message;
+pub mod codec;
+pub mod metrics;
 pub mod buzzer;
 
 use std::any::Any;
@@ -106,14 +113,15 @@ pub use allocator::Generic as Allocator;
 pub use allocator::Allocate;
 pub use initialize::{initialize, initialize_from, Configuration, WorkerGuards};
 pub use message::Message;
+pub use codec::Codec;
 
-/// A composite trait for types that may be used with channels.
+/// A composite trait for types that may be used with channels using the default codec.
 #[cfg(not(feature = "bincode"))]
 pub trait Data : Send+Sync+Any+Abomonation+'static { }
 #[cfg(not(feature = "bincode"))]
 impl<T: Send+Sync+Any+Abomonation+'static> Data for T { }
 
-/// A composite trait for types that may be used with channels.
+/// A composite trait for types that may be used with channels using the default codec.
 #[cfg(feature = "bincode")]
 pub trait Data : Send+Sync+Any+Serialize+for<'a>Deserialize<'a>+'static { }
 #[cfg(feature = "bincode")]
@@ -9,6 +9,8 @@ pub struct CommunicationSetup {
     pub process: usize,
     /// The remote process id.
     pub remote: Option<usize>,
+    /// The global index of the worker, for a worker thread.
+    pub worker: Option<usize>,
 }
 
 /// Various communication events.
@@ -18,6 +20,8 @@ pub enum CommunicationEvent {
     Message(MessageEvent),
     /// A state transition.
     State(StateEvent),
+    /// The placement of a thread on a core.
+    Placement(PlacementEvent),
 }
 
 /// An observed message.
@@ -42,9 +46,36 @@ pub struct StateEvent {
     pub start: bool,
 }
 
+/// The role of a thread started by the communication layer.
+#[derive(Abomonation, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ThreadRole {
+    /// A worker thread, with its global index.
+    Worker(usize),
+    /// A send thread, with its remote process id.
+    Send(usize),
+    /// A receive thread, with its remote process id.
+    Recv(usize),
+}
+
+/// Pinning a thread to a core.
+#[derive(Abomonation, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct PlacementEvent {
+    /// The host process id.
+    pub process: usize,
+    /// The role of the thread.
+    pub role: ThreadRole,
+    /// The core the placement assigned to the thread.
+    pub requested: Option<usize>,
+    /// The core the thread is pinned to, or `None` if pinning failed.
+    pub core: Option<usize>,
+}
+
 impl From<MessageEvent> for CommunicationEvent {
     fn from(v: MessageEvent) -> CommunicationEvent { CommunicationEvent::Message(v) }
 }
 impl From<StateEvent> for CommunicationEvent {
     fn from(v: StateEvent) -> CommunicationEvent { CommunicationEvent::State(v) }
 }
+impl From<PlacementEvent> for CommunicationEvent {
+    fn from(v: PlacementEvent) -> CommunicationEvent { CommunicationEvent::Placement(v) }
+}
@@ -1,9 +1,10 @@
 //! Types wrapping typed data.
 
 use std::sync::Arc;
+use std::mem::ManuallyDrop;
 use bytes::arc::Bytes;
 use abomonation;
-use crate::Data;
+use crate::codec::Codec;
 
 /// Either an immutable or mutable reference.
 pub enum RefOrMut<'a, T> where T: 'a {
@@ -55,6 +56,26 @@ enum MessageContents<T> {
     Owned(T),
     /// Atomic reference counted. Only available as a reference.
     Arc(Arc<T>),
+    /// Rust typed instance borrowing binary data. Only available as a reference.
+    Borrowed(Borrowed<T>),
+}
+
+/// A typed instance whose memory is partly borrowed from binary data.
+///
+/// The instance is never dropped as is: `release` takes it apart and forgets the borrowed memory,
+/// before the binary data are dropped.
+struct Borrowed<T> {
+    typed: ManuallyDrop<T>,
+    release: fn(T),
+    _bytes: Bytes,
+}
+
+impl<T> Drop for Borrowed<T> {
+    fn drop(&mut self) {
+        // The instance is not used again, and `release` does not free the borrowed memory.
+        let typed = unsafe { ManuallyDrop::take(&mut self.typed) };
+        (self.release)(typed);
+    }
 }
 
 impl<T> Message<T> {
@@ -72,6 +93,7 @@ impl<T> Message<T> {
             MessageContents::Binary(_) => None,
             MessageContents::Owned(typed) => Some(typed),
             MessageContents::Arc(_) => None,
+            MessageContents::Borrowed(_) => None,
         }
     }
     /// Returns a mutable reference, if typed.
@@ -80,6 +102,7 @@ impl<T> Message<T> {
             MessageContents::Binary(_) => None,
             MessageContents::Owned(typed) => Some(typed),
             MessageContents::Arc(_) => None,
+            MessageContents::Borrowed(_) => None,
         }
     }
     /// Returns an immutable or mutable typed reference.
@@ -92,83 +115,55 @@ impl<T> Message<T> {
             MessageContents::Binary(bytes) => { RefOrMut::Ref(bytes) },
             MessageContents::Owned(typed) => { RefOrMut::Mut(typed) },
             MessageContents::Arc(typed) => { RefOrMut::Ref(typed) },
+            MessageContents::Borrowed(borrowed) => { RefOrMut::Ref(&borrowed.typed) },
         }
     }
 }
 
-// These methods require `T` to implement `Abomonation`, for serialization functionality.
-#[cfg(not(feature = "bincode"))]
-impl<T: Data> Message<T> {
-    /// Wrap bytes as a message.
+// These methods serialize and deserialize data using a supplied codec.
+impl<T> Message<T> {
+    /// Wrap bytes as a message, decoded by `codec`.
     ///
     /// # Safety
     ///
-    /// This method is unsafe, in that `Abomonated::new()` is unsafe: it presumes that
-    /// the binary data can be safely decoded, which is unsafe for e.g. UTF8 data and
-    /// enumerations (perhaps among many other types).
-    pub unsafe fn from_bytes(bytes: Bytes) -> Self {
-        let abomonated = abomonation::abomonated::Abomonated::new(bytes).expect("Abomonated::new() failed.");
-        Message { payload: MessageContents::Binary(abomonated) }
+    /// This method is unsafe, in that codecs may trust their input: `bytes` must have been
+    /// written by `into_bytes` with the same codec, for the same type `T`. For example,
+    /// `AbomonationCodec` presumes the binary data can be safely decoded, which is unsafe for
+    /// e.g. UTF8 data and enumerations (perhaps among many other types).
+    pub unsafe fn from_bytes<C: Codec<T>>(bytes: Bytes, codec: &C) -> Self {
+        codec.decode(bytes)
     }
 
-    /// The number of bytes required to serialize the data.
-    pub fn length_in_bytes(&self) -> usize {
-        match &self.payload {
-            MessageContents::Binary(bytes) => { bytes.as_bytes().len() },
-            MessageContents::Owned(typed) => { abomonation::measure(typed) },
-            MessageContents::Arc(typed) =>{ abomonation::measure::<T>(&**typed) } ,
-        }
+    /// The number of bytes required to serialize the data with `codec`.
+    pub fn length_in_bytes<C: Codec<T>>(&self, codec: &C) -> usize {
+        codec.message_length(self)
     }
 
-    /// Writes the binary representation into `writer`.
-    pub fn into_bytes<W: ::std::io::Write>(&self, writer: &mut W) {
-        match &self.payload {
-            MessageContents::Binary(bytes) => {
-                writer.write_all(bytes.as_bytes()).expect("Message::into_bytes(): write_all failed.");
-            },
-            MessageContents::Owned(typed) => {
-                unsafe { abomonation::encode(typed, writer).expect("Message::into_bytes(): Abomonation::encode failed"); }
-            },
-            MessageContents::Arc(typed) => {
-                unsafe { abomonation::encode(&**typed, writer).expect("Message::into_bytes(): Abomonation::encode failed"); }
-            },
-        }
+    /// Writes the binary representation, as produced by `codec`, into `writer`.
+    pub fn into_bytes<C: Codec<T>, W: ::std::io::Write>(&self, codec: &C, writer: &mut W) {
+        codec.encode_message(self, writer)
     }
-}
 
-#[cfg(feature = "bincode")]
-impl<T: Data> Message<T> {
-    /// Wrap bytes as a message.
-    pub fn from_bytes(bytes: Bytes) -> Self {
-        let typed = ::bincode::deserialize(&bytes[..]).expect("bincode::deserialize() failed");
-        Message { payload: MessageContents::Owned(typed) }
+    /// Wraps abomonated binary data as a message.
+    pub(crate) fn from_abomonated(abomonated: abomonation::abomonated::Abomonated<T, Bytes>) -> Self {
+        Message { payload: MessageContents::Binary(abomonated) }
     }
 
-    /// The number of bytes required to serialize the data.
-    pub fn length_in_bytes(&self) -> usize {
-        match &self.payload {
-            MessageContents::Binary(bytes) => { bytes.as_bytes().len() },
-            MessageContents::Owned(typed) => {
-                ::bincode::serialized_size(&typed).expect("bincode::serialized_size() failed") as usize
-            },
-            MessageContents::Arc(typed) => {
-                ::bincode::serialized_size(&**typed).expect("bincode::serialized_size() failed") as usize
-            },
-        }
+    /// Wraps a typed instance whose memory is partly borrowed from `bytes`.
+    ///
+    /// # Safety
+    ///
+    /// The borrowed memory must lie within `bytes`, and `release` must take `typed` apart without
+    /// freeing it, as the instance is never dropped.
+    pub(crate) unsafe fn from_borrowed(typed: T, release: fn(T), bytes: Bytes) -> Self {
+        Message { payload: MessageContents::Borrowed(Borrowed { typed: ManuallyDrop::new(typed), release, _bytes: bytes }) }
     }
 
-    /// Writes the binary representation into `writer`.
-    pub fn into_bytes<W: ::std::io::Write>(&self, writer: &mut W) {
+    /// The abomonated binary data backing the message, if any.
+    pub(crate) fn abomonated_bytes(&self) -> Option<&[u8]> {
         match &self.payload {
-            MessageContents::Binary(bytes) => {
-                writer.write_all(bytes.as_bytes()).expect("Message::into_bytes(): write_all failed.");
-            },
-            MessageContents::Owned(typed) => {
-                ::bincode::serialize_into(writer, &typed).expect("bincode::serialize_into() failed");
-            },
-            MessageContents::Arc(typed) => {
-                ::bincode::serialize_into(writer, &**typed).expect("bincode::serialize_into() failed");
-            },
+            MessageContents::Binary(bytes) => Some(bytes.as_bytes()),
+            _ => None,
         }
     }
 }
@@ -181,6 +176,7 @@ impl<T> ::std::ops::Deref for Message<T> {
             MessageContents::Binary(bytes) => { bytes },
             MessageContents::Owned(typed) => { typed },
             MessageContents::Arc(typed) => { typed },
+            MessageContents::Borrowed(borrowed) => { &borrowed.typed },
         }
     }
 }
@@ -193,6 +189,7 @@ impl<T: Clone> Message<T> {
             MessageContents::Owned(instance) => instance,
             // TODO: Could attempt `Arc::try_unwrap()` here.
             MessageContents::Arc(instance) => (*instance).clone(),
+            MessageContents::Borrowed(borrowed) => (*borrowed.typed).clone(),
         }
     }
     /// Ensures the message is typed data and returns a mutable reference to it.
@@ -203,6 +200,7 @@ impl<T: Clone> Message<T> {
             MessageContents::Owned(_) => None,
             // TODO: Could attempt `Arc::try_unwrap()` here.
             MessageContents::Arc(typed) => Some((**typed).clone()),
+            MessageContents::Borrowed(borrowed) => Some((*borrowed.typed).clone()),
         };
 
         if let Some(cloned) = cloned {
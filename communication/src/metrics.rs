@@ -0,0 +1,414 @@
+//! Cumulative metrics about communication between processes.
+//!
+//! A `Metrics` registry is shared by the workers and network threads of a process. It counts the
+//! messages and payload bytes each worker sends to and receives from each peer on each channel,
+//! and tracks the depth of the queues between workers and network threads and the number of
+//! buffer reallocations made by their byte slabs. Only the cluster allocator between processes
+//! maintains a registry.
+//!
+//! The registry can be queried at runtime, and rendered in the Prometheus text format to any
+//! writer, to a file, or to clients of a local socket. An `Exporter` does the latter two for any
+//! number of sources, so that a process can export all of its metrics from one place.
+
+use std::io::{self, BufRead, Write};
+use std::collections::HashMap;
+use std::net::{TcpListener, ToSocketAddrs};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Counts of messages and payload bytes on one channel between two workers.
+#[derive(Debug, Default)]
+pub struct Traffic {
+    messages_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    messages_received: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+impl Traffic {
+    /// Records a sent message with a payload of `bytes` bytes.
+    #[inline]
+    pub fn sent(&self, bytes: usize) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+    /// Records a received message with a payload of `bytes` bytes.
+    #[inline]
+    pub fn received(&self, bytes: usize) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+    /// The current counts.
+    pub fn snapshot(&self) -> TrafficSnapshot {
+        TrafficSnapshot {
+            messages_sent: self.messages_sent.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            messages_received: self.messages_received.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counts of messages and payload bytes at one moment.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct TrafficSnapshot {
+    /// Messages sent.
+    pub messages_sent: u64,
+    /// Payload bytes sent.
+    pub bytes_sent: u64,
+    /// Messages received.
+    pub messages_received: u64,
+    /// Payload bytes received.
+    pub bytes_received: u64,
+}
+
+impl ::std::ops::AddAssign for TrafficSnapshot {
+    fn add_assign(&mut self, other: Self) {
+        self.messages_sent += other.messages_sent;
+        self.bytes_sent += other.bytes_sent;
+        self.messages_received += other.messages_received;
+        self.bytes_received += other.bytes_received;
+    }
+}
+
+/// Identifies the traffic of one worker with one peer on one channel.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct TrafficKey {
+    /// The local worker.
+    pub worker: usize,
+    /// The channel identifier.
+    pub channel: usize,
+    /// The remote worker.
+    pub peer: usize,
+}
+
+/// The direction of a queue between a worker and a network thread.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Direction {
+    /// From a worker to a send thread.
+    Send,
+    /// From a receive thread to a worker.
+    Recv,
+}
+
+impl Direction {
+    fn label(&self) -> &'static str {
+        match self {
+            Direction::Send => "send",
+            Direction::Recv => "recv",
+        }
+    }
+}
+
+/// Where a shared count of a queue or slab is kept, and what it describes.
+struct Shared {
+    worker: Option<usize>,
+    remote: usize,
+    direction: Direction,
+    value: Arc<AtomicUsize>,
+}
+
+impl Shared {
+    fn labels(&self) -> String {
+        let worker = self.worker.map(|worker| format!("worker=\"{}\",", worker)).unwrap_or_default();
+        format!("{}remote=\"{}\",direction=\"{}\"", worker, self.remote, self.direction.label())
+    }
+}
+
+#[derive(Default)]
+struct MetricsInner {
+    traffic: HashMap<TrafficKey, Arc<Traffic>>,
+    queues: Vec<Shared>,
+    slabs: Vec<Shared>,
+}
+
+/// A registry of communication metrics, shared by the threads of a process.
+///
+/// Clones refer to the same registry.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Mutex<MetricsInner>>,
+}
+
+impl Metrics {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The counters for traffic between `worker` and `peer` on `channel`, created if absent.
+    pub fn traffic(&self, worker: usize, channel: usize, peer: usize) -> Arc<Traffic> {
+        let key = TrafficKey { worker, channel, peer };
+        self.lock().traffic.entry(key).or_default().clone()
+    }
+
+    /// Registers the depth in bytes of a queue between `worker` and the network thread for `remote`.
+    pub fn register_queue(&self, worker: usize, remote: usize, direction: Direction, depth: Arc<AtomicUsize>) {
+        self.lock().queues.push(Shared { worker: Some(worker), remote, direction, value: depth });
+    }
+
+    /// Registers the allocation count of a byte slab used for traffic with process `remote`.
+    ///
+    /// The `worker` is `None` for slabs owned by network threads.
+    pub fn register_slab(&self, worker: Option<usize>, remote: usize, direction: Direction, reallocations: Arc<AtomicUsize>) {
+        self.lock().slabs.push(Shared { worker, remote, direction, value: reallocations });
+    }
+
+    /// The current counts for each worker, channel, and peer, in order.
+    pub fn traffic_snapshot(&self) -> Vec<(TrafficKey, TrafficSnapshot)> {
+        let mut snapshot: Vec<_> = self.lock().traffic.iter().map(|(key, traffic)| (*key, traffic.snapshot())).collect();
+        snapshot.sort_by_key(|(key, _)| *key);
+        snapshot
+    }
+
+    /// The total counts for `channel`, across all workers and peers.
+    pub fn channel_traffic(&self, channel: usize) -> TrafficSnapshot {
+        self.total(|key| key.channel == channel)
+    }
+
+    /// The total counts for traffic with `peer`, across all workers and channels.
+    pub fn peer_traffic(&self, peer: usize) -> TrafficSnapshot {
+        self.total(|key| key.peer == peer)
+    }
+
+    /// The current depths in bytes of queues, for each worker, remote process, and direction.
+    pub fn queue_depths(&self) -> Vec<(usize, usize, Direction, usize)> {
+        self.lock()
+            .queues
+            .iter()
+            .map(|queue| (queue.worker.unwrap_or(0), queue.remote, queue.direction, queue.value.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// The total number of buffers allocated by byte slabs after their first.
+    pub fn slab_reallocations(&self) -> usize {
+        self.lock()
+            .slabs
+            .iter()
+            .map(|slab| slab.value.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Writes all metrics in the Prometheus text exposition format.
+    pub fn write_prometheus<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+
+        let traffic = self.traffic_snapshot();
+        let counters: [(&str, &str, fn(&TrafficSnapshot) -> u64); 4] = [
+            ("timely_comm_messages_sent_total", "Messages sent to a peer on a channel.", |t| t.messages_sent),
+            ("timely_comm_bytes_sent_total", "Payload bytes sent to a peer on a channel.", |t| t.bytes_sent),
+            ("timely_comm_messages_received_total", "Messages received from a peer on a channel.", |t| t.messages_received),
+            ("timely_comm_bytes_received_total", "Payload bytes received from a peer on a channel.", |t| t.bytes_received),
+        ];
+        for (name, help, value) in counters.iter() {
+            writeln!(writer, "# HELP {} {}", name, help)?;
+            writeln!(writer, "# TYPE {} counter", name)?;
+            for (key, snapshot) in traffic.iter() {
+                writeln!(writer, "{}{{worker=\"{}\",channel=\"{}\",peer=\"{}\"}} {}", name, key.worker, key.channel, key.peer, value(snapshot))?;
+            }
+        }
+
+        let inner = self.lock();
+        let shared = [
+            ("timely_comm_queue_depth_bytes", "gauge", "Bytes enqueued between a worker and a network thread.", &inner.queues),
+            ("timely_comm_slab_reallocations_total", "counter", "Buffers allocated by byte slabs after their first.", &inner.slabs),
+        ];
+        for (name, kind, help, values) in shared.iter() {
+            writeln!(writer, "# HELP {} {}", name, help)?;
+            writeln!(writer, "# TYPE {} {}", name, kind)?;
+            for value in values.iter() {
+                writeln!(writer, "{}{{{}}} {}", name, value.labels(), value.value.load(Ordering::Relaxed))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes all metrics in the Prometheus text format to the file at `path`.
+    ///
+    /// The file is written to a temporary path and then renamed, so readers never observe a partial file.
+    pub fn export_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.exporter().export_to_file(path)
+    }
+
+    /// Serves metrics in the Prometheus text format to each client connecting to `address`.
+    ///
+    /// This is `Exporter::serve` for an exporter of this registry alone.
+    pub fn serve<A: ToSocketAddrs>(&self, address: A) -> io::Result<::std::thread::JoinHandle<()>> {
+        self.exporter().serve(address)
+    }
+
+    /// Registers this registry as a source of `exporter`.
+    pub fn register(&self, exporter: &Exporter) {
+        let metrics = self.clone();
+        exporter.register(move |writer| metrics.write_prometheus(writer));
+    }
+
+    fn exporter(&self) -> Exporter {
+        let exporter = Exporter::new();
+        self.register(&exporter);
+        exporter
+    }
+
+    fn total<F: Fn(&TrafficKey)->bool>(&self, filter: F) -> TrafficSnapshot {
+        let mut total = TrafficSnapshot::default();
+        for (key, traffic) in self.lock().traffic.iter() {
+            if filter(key) {
+                total += traffic.snapshot();
+            }
+        }
+        total
+    }
+
+    fn lock(&self) -> ::std::sync::MutexGuard<'_, MetricsInner> {
+        self.inner.lock().expect("metrics mutex poisoned")
+    }
+}
+
+/// A source of metrics in the Prometheus text format.
+type Source = Arc<dyn Fn(&mut dyn Write) -> io::Result<()> + Send + Sync>;
+
+/// Exports the metrics of several sources in the Prometheus text format.
+///
+/// Sources, such as a communication `Metrics` registry or the metrics timely aggregates from its
+/// logs, register with `register`. The exporter serves all of them from one local HTTP endpoint, or
+/// writes all of them to one file. Clones refer to the same exporter.
+///
+/// # Examples
+/// ```
+/// use timely_communication::metrics::{Exporter, Metrics};
+///
+/// let exporter = Exporter::new();
+/// Metrics::new().register(&exporter);
+/// exporter.register(|writer| writeln!(writer, "application_up 1"));
+///
+/// let mut text = Vec::new();
+/// exporter.write_prometheus(&mut text).unwrap();
+/// assert!(String::from_utf8(text).unwrap().ends_with("application_up 1\n"));
+/// ```
+#[derive(Clone, Default)]
+pub struct Exporter {
+    sources: Arc<Mutex<Vec<Source>>>,
+}
+
+impl Exporter {
+    /// Creates an exporter without sources.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a source, which writes its metrics to the supplied writer.
+    pub fn register<F: Fn(&mut dyn Write) -> io::Result<()> + Send + Sync + 'static>(&self, source: F) {
+        self.sources.lock().expect("exporter mutex poisoned").push(Arc::new(source));
+    }
+
+    /// Writes the metrics of all sources, in the order they registered.
+    pub fn write_prometheus(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let sources = self.sources.lock().expect("exporter mutex poisoned").clone();
+        for source in sources.iter() {
+            source(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the metrics of all sources to the file at `path`.
+    ///
+    /// The file is written to a temporary path and then renamed, so readers never observe a partial file.
+    pub fn export_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        {
+            let mut file = io::BufWriter::new(::std::fs::File::create(&temporary)?);
+            self.write_prometheus(&mut file)?;
+            file.flush()?;
+        }
+        ::std::fs::rename(temporary, path)
+    }
+
+    /// Writes the metrics of all sources to the file at `path` now and then every `period`, from a
+    /// background thread that stops with a warning if writing fails.
+    pub fn export_every<P: AsRef<Path>>(&self, path: P, period: ::std::time::Duration) -> io::Result<::std::thread::JoinHandle<()>> {
+        let path = path.as_ref().to_path_buf();
+        self.export_to_file(&path)?;
+        let exporter = self.clone();
+        ::std::thread::Builder::new()
+            .name("timely:metrics-file".to_owned())
+            .spawn(move || {
+                loop {
+                    ::std::thread::sleep(period);
+                    if let Err(error) = exporter.export_to_file(&path) {
+                        eprintln!("timely: failed to write metrics to {:?}: {}; metrics file disabled", path, error);
+                        return;
+                    }
+                }
+            })
+    }
+
+    /// Serves the metrics of all sources to each client connecting to `address`.
+    ///
+    /// Each connection receives a minimal HTTP response, once its request line and headers have been
+    /// read, so that the address can be scraped directly. The serving thread runs until the process exits.
+    pub fn serve<A: ToSocketAddrs>(&self, address: A) -> io::Result<::std::thread::JoinHandle<()>> {
+        let listener = TcpListener::bind(address)?;
+        let exporter = self.clone();
+        ::std::thread::Builder::new()
+            .name("timely:metrics".to_owned())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if let Ok(mut stream) = stream {
+                        // Replying before the request is read can reset the connection on some clients.
+                        if read_request(&mut stream).is_err() {
+                            continue;
+                        }
+                        let mut body = Vec::new();
+                        if exporter.write_prometheus(&mut body).is_ok() {
+                            let _ = write!(stream, "HTTP/1.0 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n", body.len());
+                            let _ = stream.write_all(&body);
+                        }
+                    }
+                }
+            })
+    }
+}
+
+/// Reads an HTTP request line and headers, up to the empty line that ends them.
+fn read_request(stream: &mut ::std::net::TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(::std::time::Duration::from_secs(5)))?;
+    let mut reader = io::BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    use super::Metrics;
+
+    #[test]
+    fn serve_replies_after_request() {
+        let metrics = Metrics::new();
+        metrics.traffic(0, 3, 1).sent(10);
+
+        let listener = ::std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        metrics.serve(address).unwrap();
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nAccept: */*\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.0 200 OK\r\n"));
+        assert!(response.contains("timely_comm_bytes_sent_total{worker=\"0\",channel=\"3\",peer=\"1\"} 10"));
+    }
+}
@@ -10,10 +10,25 @@ use std::time::Duration;
 
 use abomonation::{encode, decode};
 
+use crate::allocator::zero_copy::security::{Security, Session, authenticate};
+
 // This constant is sent along immediately after establishing a TCP stream, so
 // that it is easy to sniff out Timely traffic when it is multiplexed with
 // other traffic on the same port.
 const HANDSHAKE_MAGIC: u64 = 0xc2f1fb770118add9;
+// Sent instead of `HANDSHAKE_MAGIC` by processes that authenticate their links,
+// so that processes with differing security settings refuse to connect.
+const AUTHENTICATED_HANDSHAKE_MAGIC: u64 = 0x7e5a0c3d51b9e26f;
+// How long an accepted connection may take to complete its handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The handshake magic sent by processes with the given security settings.
+fn handshake_magic(security: &Security) -> u64 {
+    match security {
+        Security::Plaintext => HANDSHAKE_MAGIC,
+        Security::PreSharedKey(_) => AUTHENTICATED_HANDSHAKE_MAGIC,
+    }
+}
 
 /// Framing data for each `Vec<u8>` transmission, indicating a typed channel, the source and
 /// destination workers, and the length in bytes.
@@ -29,6 +44,8 @@ pub struct MessageHeader {
     pub length:     usize,
     /// sequence number.
     pub seqno:      usize,
+    /// payload compression codec (zero if uncompressed).
+    pub codec:      usize,
 }
 
 impl MessageHeader {
@@ -59,17 +76,95 @@ impl MessageHeader {
     }
 }
 
+/// The assignment of global worker indices to processes.
+///
+/// Processes may host different numbers of workers. The workers of process `p` have the
+/// contiguous global indices `offset(p) .. offset(p+1)`, computed as prefix sums of the
+/// per-process worker counts.
+///
+/// # Examples
+/// ```
+/// use timely_communication::networking::WorkerLayout;
+///
+/// let layout = WorkerLayout::new(&[8, 64, 16]);
+/// assert_eq!(layout.peers(), 88);
+/// assert_eq!(layout.workers(1), 8 .. 72);
+/// assert_eq!(layout.process_of(72), 2);
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct WorkerLayout {
+    // offsets[p] is the index of the first worker of process p; the last entry is the number of workers.
+    offsets: Vec<usize>,
+}
+
+impl WorkerLayout {
+    /// Creates a layout from the number of workers in each process.
+    pub fn new(threads: &[usize]) -> Self {
+        let mut offsets = Vec::with_capacity(threads.len() + 1);
+        offsets.push(0);
+        for count in threads.iter() {
+            let last = offsets[offsets.len() - 1];
+            offsets.push(last + count);
+        }
+        WorkerLayout { offsets }
+    }
+    /// Creates a layout of `processes` processes each with `threads` workers.
+    pub fn uniform(processes: usize, threads: usize) -> Self {
+        Self::new(&vec![threads; processes])
+    }
+    /// The number of processes.
+    pub fn processes(&self) -> usize {
+        self.offsets.len() - 1
+    }
+    /// The total number of workers.
+    pub fn peers(&self) -> usize {
+        self.offsets[self.offsets.len() - 1]
+    }
+    /// The number of workers in `process`.
+    pub fn threads(&self, process: usize) -> usize {
+        self.offsets[process + 1] - self.offsets[process]
+    }
+    /// The global index of the first worker of `process`.
+    pub fn offset(&self, process: usize) -> usize {
+        self.offsets[process]
+    }
+    /// The global indices of the workers of `process`.
+    pub fn workers(&self, process: usize) -> ::std::ops::Range<usize> {
+        self.offsets[process] .. self.offsets[process + 1]
+    }
+    /// The process hosting the worker with global index `worker`.
+    pub fn process_of(&self, worker: usize) -> usize {
+        assert!(worker < self.peers(), "worker index {} out of range", worker);
+        // The last process whose first worker is at most `worker`.
+        match self.offsets.binary_search_by(|offset| if *offset <= worker { ::std::cmp::Ordering::Less } else { ::std::cmp::Ordering::Greater }) {
+            Ok(_) => unreachable!(),
+            Err(position) => position - 1,
+        }
+    }
+}
+
 /// Creates socket connections from a list of host addresses.
 ///
 /// The item at index i in the resulting vec, is a Some(TcpSocket) to process i, except
 /// for item `my_index` which is None (no socket to self).
 pub fn create_sockets(addresses: Vec<String>, my_index: usize, noisy: bool) -> Result<Vec<Option<TcpStream>>> {
+    let links = create_secured_sockets(addresses, my_index, noisy, Security::Plaintext)?;
+    Ok(links.into_iter().map(|link| link.map(|(stream, _session)| stream)).collect())
+}
+
+/// Creates socket connections from a list of host addresses, secured according to `security`.
+///
+/// As with `create_sockets`, the item at index i is a connection to process i, except for item
+/// `my_index`. Each connection is accompanied by the keys for its session, if it is secured.
+pub fn create_secured_sockets(addresses: Vec<String>, my_index: usize, noisy: bool, security: Security) -> Result<Vec<Option<(TcpStream, Option<Session>)>>> {
 
     let hosts1 = Arc::new(addresses);
     let hosts2 = hosts1.clone();
+    let security1 = Arc::new(security);
+    let security2 = security1.clone();
 
-    let start_task = thread::spawn(move || start_connections(hosts1, my_index, noisy));
-    let await_task = thread::spawn(move || await_connections(hosts2, my_index, noisy));
+    let start_task = thread::spawn(move || start_connections(hosts1, my_index, noisy, &security1));
+    let await_task = thread::spawn(move || await_connections(hosts2, my_index, noisy, &security2));
 
     let mut results = start_task.join().unwrap()?;
     results.push(None);
@@ -83,16 +178,19 @@ pub fn create_sockets(addresses: Vec<String>, my_index: usize, noisy: bool) -> R
 
 
 /// Result contains connections [0, my_index - 1].
-pub fn start_connections(addresses: Arc<Vec<String>>, my_index: usize, noisy: bool) -> Result<Vec<Option<TcpStream>>> {
-    let results = addresses.iter().take(my_index).enumerate().map(|(index, address)| {
+///
+/// Connections are authenticated according to `security`, which must match that of the other processes.
+pub fn start_connections(addresses: Arc<Vec<String>>, my_index: usize, noisy: bool, security: &Security) -> Result<Vec<Option<(TcpStream, Option<Session>)>>> {
+    addresses.iter().take(my_index).enumerate().map(|(index, address)| {
         loop {
             match TcpStream::connect(address) {
                 Ok(mut stream) => {
                     stream.set_nodelay(true).expect("set_nodelay call failed");
-                    unsafe { encode(&HANDSHAKE_MAGIC, &mut stream) }.expect("failed to encode/send handshake magic");
+                    unsafe { encode(&handshake_magic(security), &mut stream) }.expect("failed to encode/send handshake magic");
                     unsafe { encode(&(my_index as u64), &mut stream) }.expect("failed to encode/send worker index");
+                    let session = authenticate(&mut stream, security, my_index, index, true)?;
                     if noisy { println!("worker {}:\tconnection to worker {}", my_index, index); }
-                    break Some(stream);
+                    break Ok(Some((stream, session)));
                 },
                 Err(error) => {
                     println!("worker {}:\terror connecting to worker {}: {}; retrying", my_index, index, error);
@@ -100,30 +198,142 @@ pub fn start_connections(addresses: Arc<Vec<String>>, my_index: usize, noisy: bo
                 },
             }
         }
-    }).collect();
-
-    Ok(results)
+    }).collect()
 }
 
 /// Result contains connections [my_index + 1, addresses.len() - 1].
-pub fn await_connections(addresses: Arc<Vec<String>>, my_index: usize, noisy: bool) -> Result<Vec<Option<TcpStream>>> {
+///
+/// Connections are authenticated according to `security`, which must match that of the other processes.
+/// Connections that fail the handshake or authentication are reported and dropped, and the process
+/// keeps waiting for connections from the expected processes.
+pub fn await_connections(addresses: Arc<Vec<String>>, my_index: usize, noisy: bool, security: &Security) -> Result<Vec<Option<(TcpStream, Option<Session>)>>> {
     let mut results: Vec<_> = (0..(addresses.len() - my_index - 1)).map(|_| None).collect();
     let listener = TcpListener::bind(&addresses[my_index][..])?;
 
-    for _ in (my_index + 1) .. addresses.len() {
-        let mut stream = listener.accept()?.0;
-        stream.set_nodelay(true).expect("set_nodelay call failed");
-        let mut buffer = [0u8;16];
-        stream.read_exact(&mut buffer)?;
-        let (magic, mut buffer) = unsafe { decode::<u64>(&mut buffer) }.expect("failed to decode magic");
-        if magic != &HANDSHAKE_MAGIC {
-            return Err(io::Error::new(io::ErrorKind::InvalidData,
-                "received incorrect timely handshake"));
+    while results.iter().any(|result| result.is_none()) {
+        let (mut stream, peer) = listener.accept()?;
+        match accept_connection(&mut stream, &results, my_index, security) {
+            Ok((identifier, session)) => {
+                results[identifier - my_index - 1] = Some((stream, session));
+                if noisy { println!("worker {}:\tconnection from worker {}", my_index, identifier); }
+            },
+            Err(error) => {
+                println!("worker {}:\trejected connection from {}: {}", my_index, peer, error);
+            },
         }
-        let identifier = unsafe { decode::<u64>(&mut buffer) }.expect("failed to decode worker index").0.clone() as usize;
-        results[identifier - my_index - 1] = Some(stream);
-        if noisy { println!("worker {}:\tconnection from worker {}", my_index, identifier); }
     }
 
     Ok(results)
 }
+
+/// Reads the handshake of a connection and authenticates it, returning the identifier of the connecting process.
+///
+/// `results` holds the connections accepted so far, starting with that of process `my_index + 1`.
+fn accept_connection(stream: &mut TcpStream, results: &[Option<(TcpStream, Option<Session>)>], my_index: usize, security: &Security) -> Result<(usize, Option<Session>)> {
+    stream.set_nodelay(true)?;
+    // A peer that stalls during the handshake must not hold up the others.
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let mut buffer = [0u8;16];
+    stream.read_exact(&mut buffer)?;
+    let (magic, mut buffer) = unsafe { decode::<u64>(&mut buffer) }.expect("failed to decode magic");
+    if magic != &handshake_magic(security) {
+        let message = if magic == &HANDSHAKE_MAGIC || magic == &AUTHENTICATED_HANDSHAKE_MAGIC {
+            "received timely handshake with different link security"
+        }
+        else {
+            "received incorrect timely handshake"
+        };
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+    let identifier = unsafe { decode::<u64>(&mut buffer) }.expect("failed to decode worker index").0.clone() as usize;
+    if identifier <= my_index || identifier > my_index + results.len() || results[identifier - my_index - 1].is_some() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("received handshake from unexpected process {}", identifier)));
+    }
+    let session = authenticate(stream, security, identifier, my_index, false)?;
+    stream.set_read_timeout(None)?;
+    Ok((identifier, session))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::allocator::zero_copy::security::Security;
+    use super::{await_connections, start_connections};
+
+    /// Connects to `address`, retrying until it accepts connections.
+    fn connect(address: &str) -> TcpStream {
+        loop {
+            match TcpStream::connect(address) {
+                Ok(stream) => break stream,
+                Err(_) => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+    }
+
+    /// Accepts connections for process 0 of `addresses`, rejecting those that `reject` makes
+    /// first, and returns whether process 1 then connected with a session.
+    fn accept_after<F: FnOnce(&Arc<Vec<String>>)>(addresses: Vec<String>, security: Security, reject: F) -> bool {
+        let addresses = Arc::new(addresses);
+        let acceptor = {
+            let addresses = addresses.clone();
+            let security = security.clone();
+            thread::spawn(move || await_connections(addresses, 0, false, &security))
+        };
+
+        reject(&addresses);
+
+        let connected = start_connections(addresses.clone(), 1, false, &security).unwrap();
+        let accepted = acceptor.join().unwrap().unwrap();
+        assert!(connected[0].is_some());
+        assert!(accepted[0].is_some());
+        accepted[0].as_ref().unwrap().1.is_some()
+    }
+
+    #[test]
+    fn rejects_bad_handshakes() {
+        let addresses = vec!["127.0.0.1:23101".to_owned(), "127.0.0.1:23102".to_owned()];
+        let session = accept_after(addresses, Security::Plaintext, |addresses| {
+            // A connection that is not timely, and one from a process that does not exist.
+            let mut stream = connect(&addresses[0]);
+            stream.write_all(&[0u8; 16]).unwrap();
+            assert_eq!(stream.read(&mut [0u8; 1]).unwrap(), 0);
+            let mut stream = connect(&addresses[0]);
+            stream.write_all(&super::HANDSHAKE_MAGIC.to_le_bytes()).unwrap();
+            stream.write_all(&7u64.to_le_bytes()).unwrap();
+            assert_eq!(stream.read(&mut [0u8; 1]).unwrap(), 0);
+        });
+        assert!(!session);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn authenticates() {
+        let addresses = vec!["127.0.0.1:23111".to_owned(), "127.0.0.1:23112".to_owned()];
+        let security = Security::PreSharedKey(b"a key shared by both processes".to_vec());
+        assert!(accept_after(addresses, security, |_| { }));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn rejects_wrong_key() {
+        let addresses = vec!["127.0.0.1:23121".to_owned(), "127.0.0.1:23122".to_owned()];
+        let security = Security::PreSharedKey(b"a key shared by both processes".to_vec());
+        assert!(accept_after(addresses, security, |addresses| {
+            // A process with the wrong key fails to authenticate, and is dropped by the acceptor.
+            let wrong = Security::PreSharedKey(b"a key known to an impostor only".to_vec());
+            let error = start_connections(addresses.clone(), 1, false, &wrong).err().unwrap();
+            assert_eq!(error.kind(), ::std::io::ErrorKind::PermissionDenied);
+            // A process with different link security is dropped too.
+            let mut stream = connect(&addresses[0]);
+            stream.write_all(&super::HANDSHAKE_MAGIC.to_le_bytes()).unwrap();
+            stream.write_all(&1u64.to_le_bytes()).unwrap();
+            assert_eq!(stream.read(&mut [0u8; 1]).unwrap(), 0);
+        }));
+    }
+}
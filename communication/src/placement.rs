@@ -0,0 +1,235 @@
+//! Placement of worker and communication threads on processor cores.
+//!
+//! A `Placement` assigns a core to each thread started by `initialize`, in order: first the
+//! worker threads of the process, then the send and receive threads for each remote process.
+//! Processes sharing a host take consecutive cores, in the order of their indices.
+//! Threads are pinned to their core once started, and the applied placement is reported as
+//! a `PlacementEvent` to the communication logger, where one is configured.
+
+use std::io;
+use std::sync::Arc;
+
+use crate::logging::{CommunicationSetup, CommunicationEvent, PlacementEvent, ThreadRole};
+use logging_core::Logger;
+
+/// Shared constructor of communication loggers.
+pub(crate) type LogFn = Arc<dyn Fn(CommunicationSetup)->Option<Logger<CommunicationEvent, CommunicationSetup>>+Send+Sync>;
+
+/// A policy for placing threads on cores.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum Placement {
+    /// Threads are placed by the operating system.
+    Unpinned,
+    /// Threads are pinned to the listed cores in turn, wrapping around if there are more threads.
+    Cores(Vec<usize>),
+    /// Threads are spread across NUMA nodes in turn, as described by `/sys/devices/system/node`.
+    Numa,
+}
+
+impl Default for Placement {
+    fn default() -> Self { Placement::Unpinned }
+}
+
+impl Placement {
+    /// Parses a placement from text: `"none"`, `"numa"`, or a core list such as `"0-7,16-23"`.
+    pub fn parse(text: &str) -> Result<Placement, String> {
+        match text {
+            "none" => Ok(Placement::Unpinned),
+            "numa" => Ok(Placement::Numa),
+            list => parse_cpu_list(list).map(Placement::Cores),
+        }
+    }
+
+    /// The cores assigned to `count` threads, if any.
+    ///
+    /// Automatic NUMA placement leaves threads unpinned if the topology cannot be read.
+    pub fn cores(&self, count: usize) -> Vec<Option<usize>> {
+        let cores = self.core_list();
+        (0 .. count)
+            .map(|index| if cores.is_empty() { None } else { Some(cores[index % cores.len()]) })
+            .collect()
+    }
+
+    /// The placement for the `count` threads of a process, which follow `start` threads of other processes on the same host.
+    ///
+    /// Warns if the threads do not fit on the cores left by the other processes, in which case some cores are shared.
+    pub fn for_process(&self, start: usize, count: usize) -> Placement {
+        let cores = self.core_list();
+        if cores.is_empty() {
+            return Placement::Unpinned;
+        }
+        if start + count > cores.len() {
+            eprintln!("timely: placing {} threads after {} others on {} cores; some cores will be shared", count, start, cores.len());
+        }
+        let shift = start % cores.len();
+        Placement::Cores(cores[shift ..].iter().chain(cores[.. shift].iter()).cloned().collect())
+    }
+
+    /// The cores assigned to `workers` worker threads followed by send and receive threads for `remotes` processes.
+    pub(crate) fn cluster_cores(&self, workers: usize, remotes: usize) -> Vec<Option<usize>> {
+        self.cores(workers + 2 * remotes)
+    }
+
+    /// The cores threads are pinned to in turn, or none if threads are unpinned.
+    fn core_list(&self) -> Vec<usize> {
+        match self {
+            Placement::Unpinned => Vec::new(),
+            Placement::Cores(cores) => cores.clone(),
+            Placement::Numa => numa_spread().unwrap_or_default(),
+        }
+    }
+}
+
+/// Describes the placement of one thread, and where to report it.
+pub struct ThreadPlacement {
+    /// The core to pin the thread to, if any.
+    pub core: Option<usize>,
+    /// The process hosting the thread.
+    pub process: usize,
+    /// The role of the thread.
+    pub role: ThreadRole,
+    pub(crate) log_fn: Option<LogFn>,
+}
+
+impl ThreadPlacement {
+    /// Pins the current thread and reports the applied placement.
+    ///
+    /// Each worker thread reports to its own logger, identified by its worker index, and only
+    /// constructs it if the thread is to be pinned.
+    pub fn apply(&self) {
+        let worker = match self.role {
+            ThreadRole::Worker(index) => Some(index),
+            _ => None,
+        };
+        let setup = CommunicationSetup { sender: false, process: self.process, remote: None, worker };
+        let mut logger = if self.core.is_some() { self.log_fn.as_ref().and_then(|log_fn| log_fn(setup)) } else { None };
+        place_current_thread(self.core, self.process, self.role, &mut logger);
+    }
+}
+
+/// Pins the current thread to `core`, if supplied, and reports the outcome to `logger`.
+///
+/// Returns the core the thread was pinned to, or `None` if it was not pinned.
+pub fn place_current_thread(
+    core: Option<usize>,
+    process: usize,
+    role: ThreadRole,
+    logger: &mut Option<Logger<CommunicationEvent, CommunicationSetup>>) -> Option<usize>
+{
+    let applied = core.and_then(|core| {
+        match pin_current_thread(core) {
+            Ok(()) => Some(core),
+            Err(error) => {
+                eprintln!("timely: failed to pin {:?} to core {}: {}", role, core, error);
+                None
+            }
+        }
+    });
+    if core.is_some() {
+        logger.as_mut().map(|l| l.log(PlacementEvent { process, role, requested: core, core: applied }));
+    }
+    applied
+}
+
+/// Pins the current thread to `core`.
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(core: usize) -> io::Result<()> {
+    let limit = 8 * ::std::mem::size_of::<libc::cpu_set_t>();
+    if core >= limit {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("core {} is beyond the {} cores a thread can be pinned to", core, limit)));
+    }
+    unsafe {
+        let mut set: libc::cpu_set_t = ::std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, ::std::mem::size_of::<libc::cpu_set_t>(), &set) == 0 {
+            Ok(())
+        }
+        else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+/// Pins the current thread to `core`.
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_core: usize) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "thread pinning is not supported on this platform"))
+}
+
+/// Parses a list of cores such as `"0-3,8,10-11"`.
+fn parse_cpu_list(text: &str) -> Result<Vec<usize>, String> {
+    let mut cores = Vec::new();
+    for range in text.trim().split(',').filter(|range| !range.is_empty()) {
+        let mut bounds = range.splitn(2, '-');
+        let lower = bounds.next().unwrap_or("");
+        let lower: usize = lower.trim().parse().map_err(|e| format!("invalid core {:?}: {}", lower, e))?;
+        let upper = match bounds.next() {
+            Some(upper) => upper.trim().parse().map_err(|e| format!("invalid core {:?}: {}", upper, e))?,
+            None => lower,
+        };
+        if upper < lower {
+            return Err(format!("invalid core range {:?}", range));
+        }
+        cores.extend(lower ..= upper);
+    }
+    if cores.is_empty() {
+        return Err(format!("empty core list {:?}", text));
+    }
+    Ok(cores)
+}
+
+/// Orders the cores of all NUMA nodes so that consecutive cores belong to different nodes.
+fn numa_spread() -> Option<Vec<usize>> {
+    let mut nodes = Vec::new();
+    for entry in ::std::fs::read_dir("/sys/devices/system/node").ok()? {
+        let entry = entry.ok()?;
+        let name = entry.file_name().into_string().ok()?;
+        if let Some(index) = name.strip_prefix("node").and_then(|index| index.parse::<usize>().ok()) {
+            let list = ::std::fs::read_to_string(entry.path().join("cpulist")).ok()?;
+            if let Ok(cores) = parse_cpu_list(&list) {
+                nodes.push((index, cores));
+            }
+        }
+    }
+    nodes.sort();
+
+    let mut spread = Vec::new();
+    let longest = nodes.iter().map(|(_, cores)| cores.len()).max()?;
+    for position in 0 .. longest {
+        for (_, cores) in nodes.iter() {
+            if let Some(core) = cores.get(position) {
+                spread.push(*core);
+            }
+        }
+    }
+    Some(spread)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Placement;
+
+    #[test]
+    fn for_process() {
+        let placement = Placement::Cores(vec![0, 1, 2, 3]);
+        assert_eq!(placement.for_process(0, 2).cores(2), vec![Some(0), Some(1)]);
+        assert_eq!(placement.for_process(2, 2).cores(2), vec![Some(2), Some(3)]);
+        assert_eq!(placement.for_process(3, 3).cores(3), vec![Some(3), Some(0), Some(1)]);
+        assert_eq!(Placement::Unpinned.for_process(2, 2).cores(2), vec![None, None]);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Placement::parse("none"), Ok(Placement::Unpinned));
+        assert_eq!(Placement::parse("0-2,8"), Ok(Placement::Cores(vec![0, 1, 2, 8])));
+        assert!(Placement::parse("3-1").is_err());
+        assert!(Placement::parse("").is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn pin_out_of_range() {
+        assert!(super::pin_current_thread(1 << 20).is_err());
+    }
+}
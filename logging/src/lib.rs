@@ -44,6 +44,18 @@ impl<Id: Clone+'static> Registry<Id> {
         self.map.insert(name.to_owned(), (Box::new(logger.clone()), Box::new(logger))).map(|x| x.0)
     }
 
+    /// Filters the events of the logger bound to `name` before they are buffered.
+    ///
+    /// The filter applies to all handles on the logger, as with `Logger::set_filter`, but not
+    /// to loggers bound to `name` later. Returns false if no logger for events of type `T` is
+    /// bound to `name`.
+    pub fn filter<T: 'static, F: FnMut(&T)->bool+'static>(&mut self, name: &str, filter: F) -> bool {
+        match self.get::<T>(name) {
+            Some(logger) => { logger.set_filter(filter); true },
+            None => false,
+        }
+    }
+
     /// Removes a bound logger.
     ///
     /// This is intended primarily to close a logging stream and let the associated writer
@@ -92,6 +104,7 @@ pub struct Logger<T, E> {
     offset: Duration,                                                   // offset to allow re-calibration.
     action: Rc<RefCell<dyn FnMut(&Duration, &mut Vec<(Duration, E, T)>)>>,  // action to take on full log buffers.
     buffer: Rc<RefCell<Vec<(Duration, E, T)>>>,                         // shared buffer; not obviously best design.
+    filter: Rc<RefCell<Option<Box<dyn FnMut(&T)->bool>>>>,             // events to keep, if not all.
 }
 
 impl<T, E: Clone> Clone for Logger<T, E> {
@@ -102,6 +115,7 @@ impl<T, E: Clone> Clone for Logger<T, E> {
             offset: self.offset.clone(),
             action: self.action.clone(),
             buffer: self.buffer.clone(),
+            filter: self.filter.clone(),
         }
     }
 }
@@ -118,9 +132,22 @@ impl<T, E: Clone> Logger<T, E> {
             offset,
             action: Rc::new(RefCell::new(action)),
             buffer: Rc::new(RefCell::new(Vec::with_capacity(1024))),
+            filter: Rc::new(RefCell::new(None)),
         }
     }
 
+    /// Drops logged events for which `filter` returns false, before they are buffered.
+    ///
+    /// The filter is shared by all clones of the logger, and replaces any previous filter.
+    pub fn set_filter<F: FnMut(&T)->bool+'static>(&self, filter: F) {
+        *self.filter.borrow_mut() = Some(Box::new(filter));
+    }
+
+    /// Removes the filter, so that all logged events are kept.
+    pub fn clear_filter(&self) {
+        *self.filter.borrow_mut() = None;
+    }
+
     /// Logs an event.
     ///
     /// The event has its timestamp recorded at the moment of logging, but it may be delayed
@@ -151,9 +178,14 @@ impl<T, E: Clone> Logger<T, E> {
     where I: IntoIterator, I::Item: Into<T>
     {
         let mut buffer = self.buffer.borrow_mut();
+        let mut filter = self.filter.borrow_mut();
         let elapsed = self.time.elapsed() + self.offset;
         for event in events {
-            buffer.push((elapsed.clone(), self.id.clone(), event.into()));
+            let event = event.into();
+            if let Some(filter) = filter.as_mut() {
+                if !filter(&event) { continue; }
+            }
+            buffer.push((elapsed.clone(), self.id.clone(), event));
             if buffer.len() == buffer.capacity() {
                 // Would call `self.flush()`, but for `RefCell` panic.
                 let mut action = self.action.borrow_mut();
@@ -170,6 +202,25 @@ impl<T, E: Clone> Logger<T, E> {
         }
     }
 
+    /// Passes a batch of events, already timestamped, to the logger's action, as of `time`.
+    ///
+    /// Buffered events are passed on first, so that the action sees one stream of events, and
+    /// the logger's filter applies to the batch. This allows one logger to forward its events
+    /// to another.
+    pub fn push_batch(&self, time: &Duration, batch: &mut Vec<(Duration, E, T)>) {
+        let mut buffer = self.buffer.borrow_mut();
+        let mut action = self.action.borrow_mut();
+        let action = &mut *action;
+        if !buffer.is_empty() {
+            (*action)(time, &mut *buffer);
+            buffer.clear();
+        }
+        if let Some(filter) = self.filter.borrow_mut().as_mut() {
+            batch.retain(|(_, _, event)| filter(event));
+        }
+        (*action)(time, batch);
+    }
+
     /// Flushes logged messages and communicates the new minimal timestamp.
     pub fn flush(&mut self) {
         <Self as Flush>::flush(self);
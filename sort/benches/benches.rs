@@ -121,7 +121,7 @@ fn radix_sort<T: Copy, U: Unsigned, F: Fn(&T)->U>(bencher: &mut Bencher, size: u
     where Standard: Distribution<T>
 {
 
-    let seed = 1 << 24 + 2 << 16 + 3 << 8 + 4;
+    let seed = ((1 << (24 + 2)) << (16 + 3)) << (8 + 4);
     let mut rng: StdRng = SeedableRng::seed_from_u64(seed);
 
     let mut vector = Vec::<T>::with_capacity(size);
@@ -145,7 +145,7 @@ fn radix_sort_swc<T: Copy, U: Unsigned, F: Fn(&T)->U>(bencher: &mut Bencher, siz
     where Standard: Distribution<T>
 {
 
-    let seed = 1 << 24 + 2 << 16 + 3 << 8 + 4;
+    let seed = ((1 << (24 + 2)) << (16 + 3)) << (8 + 4);
     let mut rng: StdRng = SeedableRng::seed_from_u64(seed);
 
     let mut vector = Vec::<T>::with_capacity(size);
@@ -169,7 +169,7 @@ fn radix_sort_msb<T: Ord+Copy, U: Unsigned, F: Fn(&T)->U>(bencher: &mut Bencher,
     where Standard: Distribution<T>
 {
 
-    let seed = 1 << 24 + 2 << 16 + 3 << 8 + 4;
+    let seed = ((1 << (24 + 2)) << (16 + 3)) << (8 + 4);
     let mut rng: StdRng = SeedableRng::seed_from_u64(seed);
 
     let mut vector = Vec::<T>::with_capacity(size);
@@ -193,7 +193,7 @@ fn radix_sort_msb_swc<T: Ord+Copy, U: Unsigned, F: Fn(&T)->U>(bencher: &mut Benc
     where Standard: Distribution<T>
 {
 
-    let seed = 1 << 24 + 2 << 16 + 3 << 8 + 4;
+    let seed = ((1 << (24 + 2)) << (16 + 3)) << (8 + 4);
     let mut rng: StdRng = SeedableRng::seed_from_u64(seed);
 
     let mut vector = Vec::<T>::with_capacity(size);
@@ -218,7 +218,7 @@ fn merge_sort<T: Ord+Copy>(bencher: &mut Bencher, size: usize)
     where Standard: Distribution<T>
 {
 
-    let seed = 1 << 24 + 2 << 16 + 3 << 8 + 4;
+    let seed = ((1 << (24 + 2)) << (16 + 3)) << (8 + 4);
     let mut rng: StdRng = SeedableRng::seed_from_u64(seed);
 
     let mut vector = Vec::<T>::with_capacity(size);
@@ -238,7 +238,7 @@ fn pdq_sort<T: Ord+Copy>(bencher: &mut Bencher, size: usize)
     where Standard: Distribution<T>
 {
 
-    let seed = 1 << 24 + 2 << 16 + 3 << 8 + 4;
+    let seed = ((1 << (24 + 2)) << (16 + 3)) << (8 + 4);
     let mut rng: StdRng = SeedableRng::seed_from_u64(seed);
 
     let mut vector = Vec::<T>::with_capacity(size);
@@ -60,29 +60,29 @@ impl<'a, T> BatchedVecRef<'a, T> {
     pub unsafe fn push_all(&mut self, elements: &[T], stash: &mut Stash<T>) {
         self.reserve(stash);
 
-        if !(self.tail.capacity() - self.tail.len() >= elements.len()) {
+        if self.tail.capacity() - self.tail.len() < elements.len() {
             panic!("cap: {:?}, len: {:?}, pcl: {:?}", self.tail.capacity(), self.tail.len(), elements.len());
         }
 
         let len = self.tail.len();
-        ptr::copy_nonoverlapping(elements.as_ptr(), self.tail.as_mut_ptr().offset(len as isize), elements.len());
+        ptr::copy_nonoverlapping(elements.as_ptr(), self.tail.as_mut_ptr().add(len), elements.len());
         self.tail.set_len(len + elements.len());
     }
 
     #[inline]
     pub fn finish_into(&mut self, target: &mut Vec<Vec<T>>) {
-        target.extend(self.batches.drain(..));
+        target.append(self.batches);
         if !self.tail.is_empty() {
-            target.push(mem::replace(&mut self.tail, Vec::new()));
+            target.push(mem::take(self.tail));
         }
     }
 
     #[inline]
     pub fn finish(&mut self) -> Vec<Vec<T>> {
         if !self.tail.is_empty() {
-            self.batches.push(mem::replace(&mut self.tail, Vec::new()));
+            self.batches.push(mem::take(self.tail));
         }
-        mem::replace(&mut self.batches, Vec::new())
+        mem::take(self.batches)
     }
 }
 
@@ -107,7 +107,7 @@ impl<T> BatchedVec<T> {
     /// we don't want to implement all the methods on `&mut BatchedVec<T>`, since `BatchedVecX256`
     /// has no way of getting those thin references. Instead, we have a custom "fat pointer" type
     /// that all access is done through, generalizing `BatchedVec` and `BatchedVecX256`.
-    pub fn ref_mut(&mut self) -> BatchedVecRef<T> {
+    pub fn ref_mut(&mut self) -> BatchedVecRef<'_, T> {
         BatchedVecRef {
             tail: &mut self.tail,
             batches: &mut self.batches
@@ -134,14 +134,14 @@ impl<T> BatchedVecX256<T> {
         }
 
         BatchedVecX256 {
-            tails: tails,
-            batches: batches
+            tails,
+            batches
         }
     }
 
     /// Access the `BatchedVec` at the `byte` position.
     #[inline]
-    pub fn get_mut(&mut self, byte: usize) -> BatchedVecRef<T> {
+    pub fn get_mut(&mut self, byte: usize) -> BatchedVecRef<'_, T> {
         unsafe {
             BatchedVecRef {
                 tail: self.tails.get_unchecked_mut(byte),
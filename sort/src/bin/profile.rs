@@ -47,7 +47,7 @@ fn test_radix<T: Ord+Copy+Unsigned, R: RadixSorter<(T,T),T>>(size: usize, iters:
     where Standard: Distribution<T>
 {
 
-    let seed = 1 << 24 + 2 << 16 + 3 << 8 + 4;
+    let seed = ((1 << (24 + 2)) << (16 + 3)) << (8 + 4);
     let mut rng: StdRng = SeedableRng::seed_from_u64(seed);
 
     let mut sorter = R::new();
@@ -67,7 +67,7 @@ fn test_sort_by<T: Ord>(size: usize, iters: usize)
     where Standard: Distribution<T>
 {
 
-    let seed = 1 << 24 + 2 << 16 + 3 << 8 + 4;
+    let seed = ((1 << (24 + 2)) << (16 + 3)) << (8 + 4);
     let mut rng: StdRng = SeedableRng::seed_from_u64(seed);
 
     let mut vector = Vec::<(T, T)>::with_capacity(size);
@@ -85,7 +85,7 @@ fn test_sort_unstable_by<T: Ord>(size: usize, iters: usize)
     where Standard: Distribution<T>
 {
 
-    let seed = 1 << 24 + 2 << 16 + 3 << 8 + 4;
+    let seed = ((1 << (24 + 2)) << (16 + 3)) << (8 + 4);
     let mut rng: StdRng = SeedableRng::seed_from_u64(seed);
 
     let mut vector = Vec::<(T, T)>::with_capacity(size);
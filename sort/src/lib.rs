@@ -31,7 +31,7 @@ pub trait Unsigned : Ord {
 impl Unsigned for  u8 { #[inline]fn bytes() -> usize { 1 } #[inline] fn as_u64(&self) -> u64 { *self as u64 } }
 impl Unsigned for u16 { #[inline]fn bytes() -> usize { 2 } #[inline] fn as_u64(&self) -> u64 { *self as u64 } }
 impl Unsigned for u32 { #[inline]fn bytes() -> usize { 4 } #[inline] fn as_u64(&self) -> u64 { *self as u64 } }
-impl Unsigned for u64 { #[inline]fn bytes() -> usize { 8 } #[inline] fn as_u64(&self) -> u64 { *self as u64 } }
+impl Unsigned for u64 { #[inline]fn bytes() -> usize { 8 } #[inline] fn as_u64(&self) -> u64 { *self } }
 impl Unsigned for usize { #[inline]fn bytes() -> usize { ::std::mem::size_of::<usize>() } #[inline]fn as_u64(&self) -> u64 { *self as u64 } }
 
 /// Functionality provided by a radix sorter.
@@ -84,7 +84,7 @@ pub trait RadixSorterBase<T> {
     fn new() -> Self;
     /// Provides empty buffers for the radix sorter to use.
     fn recycle(&mut self, buffers: &mut Vec<Vec<T>>) {
-        self.rebalance(buffers, usize::max_value());
+        self.rebalance(buffers, usize::MAX);
     }
     /// Provides empty buffers for the radix sorter to use, with the intent that it should own at most `intended`.
     fn rebalance(&mut self, buffers: &mut Vec<Vec<T>>, intended: usize);
@@ -122,14 +122,14 @@ mod test {
         let mut sorter = super::Sorter::new();
 
         for &element in &vector {
-            sorter.push(element, &|&x| x as usize);
+            sorter.push(element, &|&x| x);
         }
 
         vector.sort();
 
         let mut result = Vec::new();
-        for batch in sorter.finish(&|&x| x as usize) {
-            result.extend(batch.into_iter());
+        for batch in sorter.finish(&|&x| x) {
+            result.extend(batch);
         }
 
         assert_eq!(result, vector);
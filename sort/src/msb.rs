@@ -13,7 +13,7 @@
 //! (i.e. 256) overhead for each invocation. Once we have less than some fixed amount of work (e.g. one
 //! buffer's full of elements) we should fall into the final sort.
 
-use std::mem::replace;
+use std::mem::take;
 
 use crate::{Unsigned, RadixSorter, RadixSorterBase};
 use crate::stash::Stash;
@@ -66,11 +66,11 @@ impl<T, U: Unsigned> RadixSorter<T, U> for Sorter<T> {
     }
 
     fn finish_into<F: Fn(&T)->U>(&mut self, target: &mut Vec<Vec<T>>, bytes: &F) {
-        self.finish_into_and(target, bytes, |slice| slice.sort_by(|x,y| bytes(x).cmp(&bytes(y))));
+        self.finish_into_and(target, bytes, |slice| slice.sort_by_key(|x| bytes(x)));
     }
 
     fn sort<F: Fn(&T)->U>(&mut self, batches: &mut Vec<Vec<T>>, bytes: &F) {
-        self.sort_and(batches, bytes, |slice| slice.sort_by(|x,y| bytes(x).cmp(&bytes(y))));
+        self.sort_and(batches, bytes, |slice| slice.sort_by_key(|x| bytes(x)));
     }
 }
 
@@ -117,7 +117,7 @@ impl<T> Sorter<T> {
     /// like to hook this clean-up method anyhow.
     pub fn sort_and<U: Unsigned, F: Fn(&T)->U, L: Fn(&mut Vec<T>)>(&mut self, source: &mut Vec<Vec<T>>, bytes: F, action: L) {
         if source.len() > 1 {
-            self.work.push((U::bytes(), replace(source, Vec::new())));
+            self.work.push((U::bytes(), take(source)));
             while let Some((depth, mut list)) = self.work.pop() {
                 self.ingest(&mut list, &bytes, depth, &action);
             }
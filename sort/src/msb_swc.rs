@@ -13,7 +13,7 @@
 //! (i.e. 256) overhead for each invocation. Once we have less than some fixed amount of work (e.g. one
 //! buffer's full of elements) we should fall into the final sort.
 
-use std::mem::replace;
+use std::mem::take;
 // use ::std::collections::VecDeque;
 
 use crate::{Unsigned, RadixSorter, RadixSorterBase};
@@ -84,11 +84,11 @@ impl<T, U: Unsigned> RadixSorter<T, U> for Sorter<T> {
     }
 
     fn finish_into<F: Fn(&T)->U>(&mut self, target: &mut Vec<Vec<T>>, bytes: &F) {
-        self.finish_into_and(target, bytes, |vec| if vec.len() > 1 { vec.sort_by(|x,y| bytes(x).cmp(&bytes(y))) });
+        self.finish_into_and(target, bytes, |vec| if vec.len() > 1 { vec.sort_by_key(|x| bytes(x)) });
     }
 
     fn sort<F: Fn(&T)->U>(&mut self, batches: &mut Vec<Vec<T>>, bytes: &F) {
-        self.sort_and(batches, bytes, |vec| vec.sort_by(|x,y| bytes(x).cmp(&bytes(y))));
+        self.sort_and(batches, bytes, |vec| vec.sort_by_key(|x| bytes(x)));
     }
 }
 
@@ -150,7 +150,7 @@ impl<T> Sorter<T> {
     #[inline]
     pub fn sort_and<U: Unsigned, F: Fn(&T)->U, L: Fn(&mut Vec<T>)>(&mut self, source: &mut Vec<Vec<T>>, bytes: F, action: L) {
         if source.len() > 1 {
-            self.work.push(Work::Sort(U::bytes(), replace(source, Vec::new())));
+            self.work.push(Work::Sort(U::bytes(), take(source)));
             self.grind(&bytes, &action);
             self.done.ref_mut().finish_into(source);
         }
@@ -7,7 +7,7 @@ impl<T> Stash<T> {
     pub fn new(default_capacity: usize) -> Stash<T> {
         Stash {
             stashed: Vec::new(),
-            default_capacity: default_capacity
+            default_capacity
         }
     }
 
@@ -26,7 +26,7 @@ impl<T> Stash<T> {
         while self.stashed.len() > intended {
             buffers.push(self.stashed.pop().unwrap());
         }
-        while self.stashed.len() < intended && buffers.len() > 0 {
+        while self.stashed.len() < intended && !buffers.is_empty() {
             let mut buffer = buffers.pop().unwrap();
             buffer.clear();
             self.stashed.push(buffer);
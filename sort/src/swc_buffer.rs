@@ -8,8 +8,9 @@ fn per_cache_line<T>() -> usize {
     std::cmp::max(64 / size_of::<T>(), 4)
 }
 
+/// A cache-aligned block of memory; its contents are only accessed through pointers.
 #[repr(align(64))]
-pub struct CacheLine([u8; 64]);
+pub struct CacheLine(#[allow(dead_code)] [u8; 64]);
 
 pub struct SWCBuffer<T> {
     counts: [u8; 256],
@@ -17,6 +18,10 @@ pub struct SWCBuffer<T> {
     phantom: std::marker::PhantomData<T>,
 }
 
+impl<T> Default for SWCBuffer<T> {
+    fn default() -> Self { Self::new() }
+}
+
 impl<T> SWCBuffer<T> {
     pub fn new() -> Self {
         let nlines = (256 * per_cache_line::<T>() * size_of::<T>()) / 64;
@@ -27,7 +32,7 @@ impl<T> SWCBuffer<T> {
         assert_eq!(addr % 64, 0);
 
         SWCBuffer {
-            staged: staged,
+            staged,
             counts: [0u8; 256],
             phantom: std::marker::PhantomData,
         }
@@ -64,7 +69,7 @@ impl<T> SWCBuffer<T> {
     #[inline]
     pub fn push(&mut self, element: T, byte: usize) {
         unsafe {
-            let offset = per_cache_line::<T>() as isize * byte as isize + *self.counts.get_unchecked(byte as usize) as isize;
+            let offset = per_cache_line::<T>() as isize * byte as isize + *self.counts.get_unchecked(byte) as isize;
             std::ptr::write(self.staged_mut_ptr().offset(offset), element);
             *self.counts.get_unchecked_mut(byte) += 1;
         }
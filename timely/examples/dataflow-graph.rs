@@ -0,0 +1,22 @@
+extern crate timely;
+
+use std::fs::File;
+
+use timely::logging::graph::DataflowGraph;
+
+/// Renders the dataflow graph of a timely log captured with `EventWriter`.
+///
+/// Usage: `dataflow-graph <log file> [dot|json]`, writing DOT by default.
+fn main() {
+    let path = std::env::args().nth(1).expect("Usage: dataflow-graph <log file> [dot|json]");
+    let format = std::env::args().nth(2).unwrap_or_else(|| "dot".to_owned());
+
+    let file = File::open(&path).unwrap_or_else(|error| panic!("Failed to open {}: {}", path, error));
+    let graph = DataflowGraph::from_reader(file);
+
+    match format.as_str() {
+        "dot" => println!("{}", graph.to_dot()),
+        "json" => println!("{}", graph.to_json()),
+        other => panic!("Unknown format {:?}; expected dot or json", other),
+    }
+}
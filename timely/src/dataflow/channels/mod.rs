@@ -1,6 +1,7 @@
 //! Structured communication between timely dataflow operators.
 
 use crate::communication::Push;
+use crate::communication::codec::{Columnar, Plain};
 
 /// A collection of types that may be pushed at.
 pub mod pushers;
@@ -58,3 +59,17 @@ impl<T, D> Message<T, D> {
             *buffer = Vec::with_capacity(Self::default_length());
         }
     }}
+
+/// Messages of plain timestamps and records may be sent with `ColumnarCodec`.
+unsafe impl<T: Plain, D: Plain> Columnar for Message<T, D> {
+    type Prefix = (T, usize, usize);
+    type Record = D;
+    fn prefix(&self) -> Self::Prefix { (self.time, self.from, self.seq) }
+    fn column(&self) -> &[D] { &self.data[..] }
+    fn from_parts((time, from, seq): Self::Prefix, data: Vec<D>) -> Self {
+        Message { time, data, from, seq }
+    }
+    fn into_parts(self) -> (Self::Prefix, Vec<D>) {
+        ((self.time, self.from, self.seq), self.data)
+    }
+}
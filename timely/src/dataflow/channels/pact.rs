@@ -10,9 +10,13 @@
 use std::marker::PhantomData;
 
 use crate::communication::{Push, Pull, Data};
+use crate::communication::codec::{Codec, DefaultCodec};
 use crate::communication::allocator::thread::{ThreadPusher, ThreadPuller};
+use crate::communication::allocator::zero_copy::compression::Compression;
 
 use crate::worker::AsWorker;
+use crate::progress::Timestamp;
+use crate::synchronization::Membership;
 use crate::dataflow::channels::pushers::Exchange as ExchangePusher;
 use super::{Bundle, Message};
 
@@ -43,27 +47,105 @@ impl<T: 'static, D: 'static> ParallelizationContract<T, D> for Pipeline {
 }
 
 /// An exchange between multiple observers by data
-pub struct Exchange<D, F: FnMut(&D)->u64+'static> { hash_func: F, phantom: PhantomData<D>, }
+///
+/// Data sent to other processes are serialized with the codec `C`, and compressed as the
+/// communication configuration indicates, unless overridden with `compression`.
+///
+/// # Examples
+/// ```
+/// use timely::dataflow::channels::pact::Exchange;
+/// use timely::communication::allocator::zero_copy::compression::{Codec, Compression};
+///
+/// // Compresses payloads of at least 1KiB with LZ4, when sent to other processes.
+/// let pact = Exchange::new(|x: &u64| *x).compression(Compression::new(Codec::Lz4, 1024));
+/// ```
+pub struct Exchange<D, F: FnMut(&D)->u64+'static, C=DefaultCodec> { hash_func: F, codec: C, compression: Option<Compression>, phantom: PhantomData<D>, }
 impl<D, F: FnMut(&D)->u64> Exchange<D, F> {
     /// Allocates a new `Exchange` pact from a distribution function.
     pub fn new(func: F) -> Exchange<D, F> {
+        Self::new_with_codec(func, DefaultCodec::default())
+    }
+}
+impl<D, F: FnMut(&D)->u64, C> Exchange<D, F, C> {
+    /// Allocates a new `Exchange` pact from a distribution function and a codec.
+    pub fn new_with_codec(func: F, codec: C) -> Exchange<D, F, C> {
         Exchange {
             hash_func:  func,
+            codec,
+            compression: None,
             phantom:    PhantomData,
         }
     }
+    /// Compresses data sent to other processes with `compression`, overriding the configured default.
+    ///
+    /// All workers must supply the same compression for the same channel.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
 }
 
 // Exchange uses a `Box<Pushable>` because it cannot know what type of pushable will return from the allocator.
-impl<T: Eq+Data+Clone, D: Data+Clone, F: FnMut(&D)->u64+'static> ParallelizationContract<T, D> for Exchange<D, F> {
+impl<T, D, F, C> ParallelizationContract<T, D> for Exchange<D, F, C>
+where
+    T: Eq+Data+Clone,
+    D: Clone+Send+Sync+'static,
+    F: FnMut(&D)->u64+'static,
+    C: Codec<Message<T, D>>,
+{
     // TODO: The closure in the type prevents us from naming it.
     //       Could specialize `ExchangePusher` to a time-free version.
     type Pusher = Box<dyn Push<Bundle<T, D>>>;
     type Puller = Box<dyn Pull<Bundle<T, D>>>;
-    fn connect<A: AsWorker>(mut self, allocator: &mut A, identifier: usize, address: &[usize], logging: Option<Logger>) -> (Self::Pusher, Self::Puller) {
+    fn connect<A: AsWorker>(self, allocator: &mut A, identifier: usize, address: &[usize], logging: Option<Logger>) -> (Self::Pusher, Self::Puller) {
+        let Exchange { mut hash_func, codec, compression, .. } = self;
+        if let Some(compression) = compression {
+            allocator.set_channel_compression(identifier, compression);
+        }
+        let (senders, receiver) = allocator.allocate_with_codec::<Message<T, D>, C>(identifier, address, codec);
+        let senders = senders.into_iter().enumerate().map(|(i,x)| LogPusher::new(x, allocator.index(), i, identifier, logging.clone())).collect::<Vec<_>>();
+        (Box::new(ExchangePusher::new(senders, move |_, d| hash_func(d))), Box::new(LogPuller::new(receiver, allocator.index(), identifier, logging.clone())))
+    }
+}
+
+/// An exchange among the workers a `Membership` makes active at each time.
+///
+/// Records are distributed among the active workers by their distribution function, and routing
+/// changes whenever the membership changes. Inactive workers receive no records.
+pub struct ElasticExchange<D, F: FnMut(&D)->u64+'static, T> { hash_func: F, membership: Membership<T>, phantom: PhantomData<D>, }
+impl<D, F: FnMut(&D)->u64, T> ElasticExchange<D, F, T> {
+    /// Allocates a new `ElasticExchange` pact from a distribution function and a membership.
+    pub fn new(func: F, membership: Membership<T>) -> ElasticExchange<D, F, T> {
+        ElasticExchange {
+            hash_func:  func,
+            membership,
+            phantom:    PhantomData,
+        }
+    }
+}
+
+impl<T: Timestamp, D: Data+Clone, F: FnMut(&D)->u64+'static> ParallelizationContract<T, D> for ElasticExchange<D, F, T> {
+    type Pusher = Box<dyn Push<Bundle<T, D>>>;
+    type Puller = Box<dyn Pull<Bundle<T, D>>>;
+    fn connect<A: AsWorker>(self, allocator: &mut A, identifier: usize, address: &[usize], logging: Option<Logger>) -> (Self::Pusher, Self::Puller) {
+        let ElasticExchange { mut hash_func, membership, .. } = self;
+        assert_eq!(membership.peers(), allocator.peers(), "membership describes a different number of workers");
         let (senders, receiver) = allocator.allocate::<Message<T, D>>(identifier, address);
         let senders = senders.into_iter().enumerate().map(|(i,x)| LogPusher::new(x, allocator.index(), i, identifier, logging.clone())).collect::<Vec<_>>();
-        (Box::new(ExchangePusher::new(senders, move |_, d| (self.hash_func)(d))), Box::new(LogPuller::new(receiver, allocator.index(), identifier, logging.clone())))
+
+        // The active workers for the most recent time, and the membership version they reflect.
+        let mut cached: Option<(usize, T, Vec<usize>)> = None;
+        let route = move |time: &T, datum: &D| {
+            let version = membership.version();
+            if cached.as_ref().map(|(v, t, _)| *v != version || t != time).unwrap_or(true) {
+                membership.routed(time);
+                cached = Some((version, time.clone(), membership.active_at(time)));
+            }
+            let active = &cached.as_ref().expect("routing cache unexpectedly empty").2;
+            active[(hash_func(datum) % active.len() as u64) as usize] as u64
+        };
+
+        (Box::new(ExchangePusher::new(senders, route)), Box::new(LogPuller::new(receiver, allocator.index(), identifier, logging.clone())))
     }
 }
 
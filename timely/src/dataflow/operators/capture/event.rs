@@ -5,7 +5,7 @@
 //! of timestamps.
 
 /// Data and progress events of the captured stream.
-#[derive(Debug, Clone, Abomonation, Hash, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Debug, Clone, Abomonation, Serialize, Deserialize, Hash, Ord, PartialOrd, Eq, PartialEq)]
 pub enum Event<T, D> {
     /// Progress received via `push_external_progress`.
     Progress(Vec<(T, i64)>),
@@ -194,3 +194,283 @@ pub mod binary {
         }
     }
 }
+
+/// A portable event pusher and iterator, with a versioned header.
+///
+/// Events are encoded with `serde` and `bincode`, which does not depend on the architecture, and
+/// the stream starts with a header naming the format and its version. Unlike the `binary` format,
+/// failures to write or read are reported as errors.
+pub mod portable {
+
+    use std::io::{self, Read, Write};
+    use serde::{Serialize, de::DeserializeOwned};
+    use super::{Event, EventPusher, EventIterator};
+
+    /// The bytes that start a stream in this format.
+    pub const MAGIC: &[u8; 8] = b"TIMELYEV";
+    /// The version of the format that is written.
+    pub const VERSION: u32 = 1;
+    /// The largest encoded event, in bytes, that is written or read.
+    pub const MAX_EVENT_BYTES: u64 = 1 << 30;
+
+    /// A wrapper for `W: Write` implementing `EventPusher<T, D>`, in the portable format.
+    ///
+    /// Each event is written as its length in bytes, as a little-endian `u64`, followed by its
+    /// `bincode` encoding.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::capture::{Event, PortableWriter, PortableReader};
+    ///
+    /// let mut writer = PortableWriter::new(Vec::new()).unwrap();
+    /// writer.write(&Event::Messages(0u64, vec![1u64, 2, 3])).unwrap();
+    /// writer.write(&Event::Progress(vec![(0u64, -1)])).unwrap();
+    /// let bytes = writer.into_inner().unwrap();
+    ///
+    /// let reader = PortableReader::<u64, u64, _>::new(&bytes[..]).unwrap();
+    /// let events = reader.collect::<Result<Vec<_>, _>>().unwrap();
+    /// assert_eq!(events, vec![Event::Messages(0, vec![1, 2, 3]), Event::Progress(vec![(0, -1)])]);
+    /// ```
+    pub struct PortableWriter<T, D, W: Write> {
+        stream: W,
+        buffer: Vec<u8>,
+        /// The first error met by `push`, after which events are dropped.
+        error: Option<io::Error>,
+        phant: ::std::marker::PhantomData<(T,D)>,
+    }
+
+    impl<T: Serialize, D: Serialize, W: Write> PortableWriter<T, D, W> {
+        /// Allocates a new `PortableWriter` wrapping a supplied writer, and writes the header.
+        pub fn new(mut w: W) -> io::Result<PortableWriter<T, D, W>> {
+            w.write_all(MAGIC)?;
+            w.write_all(&VERSION.to_le_bytes())?;
+            Ok(PortableWriter {
+                stream: w,
+                buffer: Vec::new(),
+                error: None,
+                phant: ::std::marker::PhantomData,
+            })
+        }
+        /// Writes an event.
+        pub fn write(&mut self, event: &Event<T, D>) -> io::Result<()> {
+            self.buffer.clear();
+            ::bincode::serialize_into(&mut self.buffer, event).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+            if self.buffer.len() as u64 > MAX_EVENT_BYTES {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("event of {} bytes exceeds the limit", self.buffer.len())));
+            }
+            self.stream.write_all(&(self.buffer.len() as u64).to_le_bytes())?;
+            self.stream.write_all(&self.buffer)
+        }
+        /// Flushes the wrapped writer.
+        pub fn flush(&mut self) -> io::Result<()> {
+            self.stream.flush()
+        }
+        /// The first error met while pushing events, if any.
+        pub fn error(&self) -> Option<&io::Error> {
+            self.error.as_ref()
+        }
+        /// Flushes and returns the wrapped writer, or the first error met while pushing events.
+        pub fn into_inner(mut self) -> io::Result<W> {
+            if let Some(error) = self.error.take() {
+                return Err(error);
+            }
+            self.stream.flush()?;
+            Ok(self.stream)
+        }
+    }
+
+    impl<T: Serialize, D: Serialize, W: Write> EventPusher<T, D> for PortableWriter<T, D, W> {
+        fn push(&mut self, event: Event<T, D>) {
+            // `push` cannot report errors, so the first one is kept for `error` and `into_inner`.
+            if self.error.is_none() {
+                if let Err(error) = self.write(&event) {
+                    self.error = Some(error);
+                }
+            }
+        }
+    }
+
+    /// A wrapper for `R: Read` implementing `EventIterator<T, D>`, in the portable format.
+    ///
+    /// As an `Iterator`, the reader produces events until the end of the stream, or an error. As an
+    /// `EventIterator`, for replay, it produces events until the end of the stream, or until an error
+    /// which is then available from `error`. A non-blocking reader without a next event yet produces
+    /// no event, as the binary `EventReader` does.
+    pub struct PortableReader<T, D, R: Read> {
+        reader: R,
+        buffer: Vec<u8>,
+        event: Option<Event<T, D>>,
+        error: Option<io::Error>,
+    }
+
+    impl<T: DeserializeOwned, D: DeserializeOwned, R: Read> PortableReader<T, D, R> {
+        /// Allocates a new `PortableReader` wrapping a supplied reader, and checks the header.
+        pub fn new(mut r: R) -> io::Result<PortableReader<T, D, R>> {
+            let mut magic = [0u8; 8];
+            r.read_exact(&mut magic)?;
+            if &magic != MAGIC {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "not a portable timely event stream"));
+            }
+            let mut version = [0u8; 4];
+            r.read_exact(&mut version)?;
+            let version = u32::from_le_bytes(version);
+            if version == 0 || version > VERSION {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsupported event stream version {}", version)));
+            }
+            Ok(PortableReader {
+                reader: r,
+                buffer: Vec::new(),
+                event: None,
+                error: None,
+            })
+        }
+        /// Reads the next event, or `None` at the end of the stream.
+        pub fn read(&mut self) -> io::Result<Option<Event<T, D>>> {
+            let mut length = [0u8; 8];
+            if !fill(&mut self.reader, &mut length, false)? {
+                return Ok(None);
+            }
+            let length = u64::from_le_bytes(length);
+            if length > MAX_EVENT_BYTES {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("event of {} bytes exceeds the limit", length)));
+            }
+            // Grow the buffer as bytes arrive, so that a corrupt length cannot force a large allocation.
+            let length = length as usize;
+            self.buffer.clear();
+            while self.buffer.len() < length {
+                let start = self.buffer.len();
+                self.buffer.resize(length.min(start.max(4096) * 2), 0);
+                fill(&mut self.reader, &mut self.buffer[start..], true)?;
+            }
+            ::bincode::deserialize(&self.buffer[..])
+                .map(Some)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+        }
+        /// The error that ended replay, if any.
+        pub fn error(&self) -> Option<&io::Error> {
+            self.error.as_ref()
+        }
+    }
+
+    /// Fills `buf` from `reader`, returning `false` if the stream ends before the first byte.
+    ///
+    /// A reader that would block before the first byte, unless `started`, reports `WouldBlock`.
+    /// Otherwise the event is partly read, and reading waits for the rest of it.
+    fn fill<R: Read>(reader: &mut R, buf: &mut [u8], started: bool) -> io::Result<bool> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match reader.read(&mut buf[filled..]) {
+                Ok(0) if filled == 0 && !started => return Ok(false),
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated event stream")),
+                Ok(read) => filled += read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => { },
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock && (filled > 0 || started) => ::std::thread::yield_now(),
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(true)
+    }
+
+    impl<T: DeserializeOwned, D: DeserializeOwned, R: Read> Iterator for PortableReader<T, D, R> {
+        type Item = io::Result<Event<T, D>>;
+        fn next(&mut self) -> Option<Self::Item> {
+            self.read().transpose()
+        }
+    }
+
+    impl<T: DeserializeOwned, D: DeserializeOwned, R: Read> EventIterator<T, D> for PortableReader<T, D, R> {
+        fn next(&mut self) -> Option<&Event<T, D>> {
+            if self.error.is_some() {
+                return None;
+            }
+            match self.read() {
+                Ok(event) => self.event = event,
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => self.event = None,
+                Err(error) => {
+                    self.event = None;
+                    self.error = Some(error);
+                },
+            }
+            self.event.as_ref()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use std::io;
+
+        use super::{Event, EventIterator, PortableReader, PortableWriter, MAGIC, VERSION, MAX_EVENT_BYTES};
+
+        fn header() -> Vec<u8> {
+            let mut bytes = MAGIC.to_vec();
+            bytes.extend_from_slice(&VERSION.to_le_bytes());
+            bytes
+        }
+
+        fn written(events: &[Event<u64, String>]) -> Vec<u8> {
+            let mut writer = PortableWriter::new(Vec::new()).unwrap();
+            for event in events.iter() {
+                writer.write(event).unwrap();
+            }
+            writer.into_inner().unwrap()
+        }
+
+        #[test]
+        fn round_trip() {
+            let events = vec![
+                Event::Messages(3, vec!["a".to_owned(), "b".to_owned()]),
+                Event::Progress(vec![(4, 1), (3, -1)]),
+            ];
+            let bytes = written(&events);
+            let read = PortableReader::<u64, String, _>::new(&bytes[..]).unwrap().collect::<io::Result<Vec<_>>>().unwrap();
+            assert_eq!(read, events);
+        }
+
+        #[test]
+        fn bad_header() {
+            let mut bytes = header();
+            bytes[0] = b'X';
+            let error = PortableReader::<u64, String, _>::new(&bytes[..]).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+            let mut bytes = header();
+            bytes[8 ..].copy_from_slice(&(VERSION + 1).to_le_bytes());
+            let error = PortableReader::<u64, String, _>::new(&bytes[..]).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+
+        #[test]
+        fn truncated_event() {
+            let bytes = written(&[Event::Messages(3, vec!["abc".to_owned()])]);
+            let mut reader = PortableReader::<u64, String, _>::new(&bytes[.. bytes.len() - 1]).unwrap();
+            assert_eq!(reader.read().err().unwrap().kind(), io::ErrorKind::UnexpectedEof);
+        }
+
+        #[test]
+        fn oversized_length() {
+            let mut bytes = header();
+            bytes.extend_from_slice(&(MAX_EVENT_BYTES + 1).to_le_bytes());
+            let mut reader = PortableReader::<u64, String, _>::new(&bytes[..]).unwrap();
+            assert_eq!(reader.read().err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+            // A length within the limit, but beyond the end of the stream, is not allocated up front.
+            let mut bytes = header();
+            bytes.extend_from_slice(&MAX_EVENT_BYTES.to_le_bytes());
+            bytes.extend_from_slice(&[0u8; 16]);
+            let mut reader = PortableReader::<u64, String, _>::new(&bytes[..]).unwrap();
+            assert_eq!(reader.read().err().unwrap().kind(), io::ErrorKind::UnexpectedEof);
+            assert!(reader.buffer.capacity() < 1 << 20);
+        }
+
+        #[test]
+        fn replay_keeps_error() {
+            let mut bytes = written(&[Event::Progress(vec![(0, 1)])]);
+            bytes.extend_from_slice(&[1, 2, 3]);
+            let mut reader = PortableReader::<u64, String, _>::new(&bytes[..]).unwrap();
+            assert_eq!(EventIterator::next(&mut reader), Some(&Event::Progress(vec![(0, 1)])));
+            assert_eq!(EventIterator::next(&mut reader), None);
+            assert_eq!(reader.error().unwrap().kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+}
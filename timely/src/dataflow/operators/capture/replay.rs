@@ -37,17 +37,98 @@
 //! This means that each timely dataflow replay operator can replay any number of streams,
 //! allowing the replay to occur in a timely dataflow computation with more or fewer workers
 //! than that in which the stream was captured.
+//!
+//! # Options
+//!
+//! The `replay_with` method takes `ReplayOptions`, which restrict the replayed records to a range of
+//! times, pace them by wall clock, and spread the streams across workers. Records outside the range
+//! are dropped, but progress events are replayed as captured, so the protocol above is maintained.
+
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use crate::Data;
 use crate::dataflow::{Scope, Stream};
 use crate::dataflow::channels::pushers::Counter as PushCounter;
 use crate::dataflow::channels::pushers::buffer::Buffer as PushBuffer;
 use crate::dataflow::operators::generic::builder_raw::OperatorBuilder;
-use crate::progress::Timestamp;
+use crate::progress::{Timestamp, ChangeBatch};
 
 use super::Event;
 use super::event::EventIterator;
 
+/// Options for replaying captured streams.
+///
+/// By default, all events are replayed as fast as they are read, and the operator re-activates
+/// itself continually to read more.
+pub struct ReplayOptions<T> {
+    period: Option<Duration>,
+    start: Option<T>,
+    end: Option<T>,
+    pace: Option<(f64, Rc<dyn Fn(&T)->Duration>)>,
+    partition: bool,
+}
+
+impl<T> Default for ReplayOptions<T> {
+    fn default() -> Self {
+        ReplayOptions {
+            period: Some(Duration::new(0, 0)),
+            start: None,
+            end: None,
+            pace: None,
+            partition: false,
+        }
+    }
+}
+
+impl<T> ReplayOptions<T> {
+    /// Options replaying all events as fast as they are read.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets the re-activation period, or `None` for an operator that does not re-activate itself.
+    pub fn period(mut self, period: Option<Duration>) -> Self {
+        self.period = period;
+        self
+    }
+    /// Skips the records of times not greater or equal to `start`.
+    pub fn start(mut self, start: T) -> Self {
+        self.start = Some(start);
+        self
+    }
+    /// Drops the records of times greater or equal to `end`, and stops replaying each stream once
+    /// it can produce only such times.
+    pub fn end(mut self, end: T) -> Self {
+        self.end = Some(end);
+        self
+    }
+    /// Paces the records by wall clock, with `clock` mapping their times to durations.
+    ///
+    /// The first replayed records are produced at once, and each later record is produced once the
+    /// difference of its duration and that of the first records has passed, divided by `speed`.
+    pub fn pace_by<F: Fn(&T)->Duration+'static>(mut self, speed: f64, clock: F) -> Self {
+        assert!(speed > 0.0, "Replay speed must be positive");
+        self.pace = Some((speed, Rc::new(clock)));
+        self
+    }
+    /// Spreads the replayed streams across workers, the `i`th to worker `i % peers`.
+    ///
+    /// Each worker should present the same streams, of which it only reads its own.
+    pub fn partition(mut self) -> Self {
+        self.partition = true;
+        self
+    }
+}
+
+impl ReplayOptions<Duration> {
+    /// Paces the records by wall clock, for streams timestamped by `Duration` such as logging streams.
+    ///
+    /// A `speed` of `1.0` replays in real time, and of `2.0` twice as fast.
+    pub fn pace(self, speed: f64) -> Self {
+        self.pace_by(speed, |time| *time)
+    }
+}
+
 /// Replay a capture stream into a scope with the same timestamp.
 pub trait Replay<T: Timestamp, D: Data> : Sized {
     /// Replays `self` into the provided scope, as a `Stream<S, D>`.
@@ -59,13 +140,56 @@ pub trait Replay<T: Timestamp, D: Data> : Sized {
     /// The `period` argument allows the specification of a re-activation period, where the operator
     /// will re-activate itself every so often. The `None` argument instructs the operator not to
     /// re-activate itself.us
-    fn replay_core<S: Scope<Timestamp=T>>(self, scope: &mut S, period: Option<std::time::Duration>) -> Stream<S, D>;
+    fn replay_core<S: Scope<Timestamp=T>>(self, scope: &mut S, period: Option<std::time::Duration>) -> Stream<S, D> {
+        self.replay_with(scope, ReplayOptions::new().period(period))
+    }
+    /// Replays `self` into the provided scope, as a `Stream<S, D>`, according to `options`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::rc::Rc;
+    /// use std::time::Duration;
+    /// use timely::dataflow::operators::{Capture, ToStream, Inspect};
+    /// use timely::dataflow::operators::capture::{EventLink, Replay, ReplayOptions};
+    ///
+    /// timely::execute(timely::Configuration::Thread, |worker, _| {
+    ///     let handle1 = Rc::new(EventLink::new());
+    ///     let handle2 = Some(handle1.clone());
+    ///
+    ///     worker.dataflow::<u64,_,_>(|scope1|
+    ///         (0..10).to_stream(scope1)
+    ///                .capture_into(handle1)
+    ///     );
+    ///
+    ///     // Replays the records of times 3 through 6, a millisecond of wall clock per time.
+    ///     let options =
+    ///     ReplayOptions::new()
+    ///         .start(3)
+    ///         .end(7)
+    ///         .pace_by(1.0, |time| Duration::from_millis(*time));
+    ///     worker.dataflow(|scope2| {
+    ///         handle2.replay_with(scope2, options)
+    ///                .inspect(|x| println!("replayed: {:?}", x));
+    ///     })
+    /// }).unwrap();
+    /// ```
+    fn replay_with<S: Scope<Timestamp=T>>(self, scope: &mut S, options: ReplayOptions<T>) -> Stream<S, D>;
+}
+
+/// A replayed stream of events, and the state of its replay.
+struct Source<I, T, D> {
+    events: I,
+    /// The capabilities held by the stream, maintained when the replay ends at a time.
+    counts: ChangeBatch<T>,
+    /// An event read but not yet due, when pacing.
+    stashed: Option<Event<T, D>>,
+    done: bool,
 }
 
 impl<T: Timestamp, D: Data, I> Replay<T, D> for I
 where I : IntoIterator,
       <I as IntoIterator>::Item: EventIterator<T, D>+'static {
-    fn replay_core<S: Scope<Timestamp=T>>(self, scope: &mut S, period: Option<std::time::Duration>) -> Stream<S, D>{
+    fn replay_with<S: Scope<Timestamp=T>>(self, scope: &mut S, options: ReplayOptions<T>) -> Stream<S, D>{
 
         let mut builder = OperatorBuilder::new("Replay".to_owned(), scope.clone());
 
@@ -75,8 +199,16 @@ where I : IntoIterator,
         let (targets, stream) = builder.new_output();
 
         let mut output = PushBuffer::new(PushCounter::new(targets));
-        let mut event_streams = self.into_iter().collect::<Vec<_>>();
+        let (index, peers) = (scope.index(), scope.peers());
+        let mut sources =
+        self.into_iter()
+            .enumerate()
+            .filter(|(position, _)| !options.partition || position % peers == index)
+            .map(|(_, events)| Source { events, counts: ChangeBatch::new_from(T::minimum(), 1), stashed: None, done: false })
+            .collect::<Vec<_>>();
         let mut started = false;
+        // The wall clock instant and duration of the first paced records.
+        let mut anchor: Option<(Instant, Duration)> = None;
 
         builder.build(
             move |progress| {
@@ -85,25 +217,64 @@ where I : IntoIterator,
                     // The first thing we do is modify our capabilities to match the number of streams we manage.
                     // This should be a simple change of `self.event_streams.len() - 1`. We only do this once, as
                     // our very first action.
-                    progress.internals[0].update(S::Timestamp::minimum(), (event_streams.len() as i64) - 1);
+                    progress.internals[0].update(S::Timestamp::minimum(), (sources.len() as i64) - 1);
                     started = true;
                 }
 
-                for event_stream in event_streams.iter_mut() {
-                    while let Some(event) = event_stream.next() {
-                        match *event {
-                            Event::Progress(ref vec) => {
-                                progress.internals[0].extend(vec.iter().cloned());
+                let mut wait: Option<Duration> = None;
+                for source in sources.iter_mut().filter(|source| !source.done) {
+                    loop {
+                        let event = match source.stashed.take() {
+                            Some(event) => event,
+                            None => match source.events.next() {
+                                Some(event) => event.clone(),
+                                None => break,
+                            },
+                        };
+                        match event {
+                            Event::Progress(vec) => {
+                                if let Some(end) = &options.end {
+                                    source.counts.extend(vec.iter().cloned());
+                                    progress.internals[0].extend(vec.into_iter());
+                                    // Once the stream can only produce times beyond the end, release its capabilities.
+                                    if source.counts.iter().all(|(time, _)| end.less_equal(time)) {
+                                        progress.internals[0].extend(source.counts.drain().map(|(time, count)| (time, -count)));
+                                        source.done = true;
+                                        break;
+                                    }
+                                }
+                                else {
+                                    progress.internals[0].extend(vec.into_iter());
+                                }
                             },
-                            Event::Messages(ref time, ref data) => {
-                                output.session(time).give_iterator(data.iter().cloned());
+                            Event::Messages(time, mut data) => {
+                                if options.start.as_ref().map(|start| !start.less_equal(&time)).unwrap_or(false) { continue; }
+                                if options.end.as_ref().map(|end| end.less_equal(&time)).unwrap_or(false) { continue; }
+                                if let Some((speed, clock)) = &options.pace {
+                                    let captured = clock(&time);
+                                    let (instant, first) = *anchor.get_or_insert_with(|| (Instant::now(), captured));
+                                    let due = instant + captured.checked_sub(first).unwrap_or_default().div_f64(*speed);
+                                    let now = Instant::now();
+                                    if due > now {
+                                        let delay = due - now;
+                                        wait = Some(wait.map(|wait| wait.min(delay)).unwrap_or(delay));
+                                        source.stashed = Some(Event::Messages(time, data));
+                                        break;
+                                    }
+                                }
+                                output.session(&time).give_vec(&mut data);
                             }
                         }
                     }
                 }
 
-                // A `None` period indicates that we do not re-activate here.
-                if let Some(delay) = period {
+                // A `None` period indicates that we do not re-activate here, unless records are not yet due.
+                if let Some(delay) = options.period {
+                    if sources.iter().any(|source| !source.done && source.stashed.is_none()) {
+                        activator.activate_after(delay);
+                    }
+                }
+                if let Some(delay) = wait {
                     activator.activate_after(delay);
                 }
 
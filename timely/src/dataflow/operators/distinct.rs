@@ -0,0 +1,196 @@
+//! Removal of duplicate records.
+//!
+//! The `Distinct` operators exchange records by hash, so that all copies of a record meet at the
+//! same worker. Per-timestamp deduplication produces the first copy of each record at each time as
+//! soon as it arrives, and frees the state for a time once the input frontier passes it.
+//!
+//! Deduplication across times produces each record once, at the first time it occurs. It holds
+//! records until their time is complete, and then checks them against the records already produced,
+//! either exactly or with a Bloom filter of bounded size.
+
+use std::hash::{Hash, Hasher};
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+
+use crate::ExchangeData;
+use crate::order::TotalOrder;
+use crate::dataflow::{Stream, Scope};
+use crate::dataflow::channels::pact::Exchange;
+use crate::dataflow::operators::generic::operator::Operator;
+use crate::dataflow::operators::join::hash_key;
+
+/// Methods to remove duplicate records.
+pub trait Distinct<G: Scope, D: ExchangeData+Hash+Eq> {
+    /// Produces the first copy of each record at each time.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Inspect};
+    /// use timely::dataflow::operators::distinct::Distinct;
+    ///
+    /// timely::example(|scope| {
+    ///     vec![0, 1, 1, 2, 2, 2].to_stream(scope)
+    ///         .distinct()
+    ///         .inspect(|x| println!("seen: {:?}", x));
+    /// });
+    /// ```
+    fn distinct(&self) -> Stream<G, D>;
+
+    /// Produces each record once, at the first time it occurs.
+    ///
+    /// Records are produced once their time is complete. All distinct records are retained.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Delay, Map, Inspect};
+    /// use timely::dataflow::operators::distinct::Distinct;
+    ///
+    /// timely::example(|scope| {
+    ///     // each record `x` arrives at time `x % 3`.
+    ///     (0..9u64).to_stream(scope)
+    ///         .delay(|x, _| x % 3)
+    ///         .map(|x| x / 2)
+    ///         .distinct_total()
+    ///         .inspect_time(|time, x| println!("{:?} first seen at {:?}", x, time));
+    /// });
+    /// ```
+    fn distinct_total(&self) -> Stream<G, D> where G::Timestamp: TotalOrder;
+
+    /// Produces each record at most once, at the first time it occurs, in bounded memory.
+    ///
+    /// Produced records are recorded in a Bloom filter of `bits` bits using `hashes` hash functions
+    /// on each worker. A record that collides with the records already produced is mistaken for a
+    /// duplicate and suppressed; the rate of such false positives grows with the number of distinct
+    /// records. Records are produced once their time is complete.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Inspect};
+    /// use timely::dataflow::operators::distinct::Distinct;
+    ///
+    /// timely::example(|scope| {
+    ///     (0..1000u64).to_stream(scope)
+    ///         .distinct_approx(1 << 16, 4)
+    ///         .inspect(|x| assert!(*x < 1000));
+    /// });
+    /// ```
+    fn distinct_approx(&self, bits: usize, hashes: usize) -> Stream<G, D> where G::Timestamp: TotalOrder;
+}
+
+impl<G: Scope, D: ExchangeData+Hash+Eq> Distinct<G, D> for Stream<G, D> {
+
+    fn distinct(&self) -> Stream<G, D> {
+        let mut seen_by_time = HashMap::new();
+        let mut vector = Vec::new();
+        self.unary_frontier(Exchange::new(|x: &D| hash_key(x)), "Distinct", move |_, _| move |input, output| {
+            input.for_each(|time, data| {
+                data.swap(&mut vector);
+                let seen = seen_by_time.entry(time.time().clone()).or_insert_with(HashSet::new);
+                let mut session = output.session(&time);
+                for datum in vector.drain(..) {
+                    if !seen.contains(&datum) {
+                        seen.insert(datum.clone());
+                        session.give(datum);
+                    }
+                }
+            });
+
+            // Discard the records of times the input can no longer produce.
+            let frontier = input.frontier();
+            seen_by_time.retain(|time, _| frontier.less_equal(time));
+        })
+    }
+
+    fn distinct_total(&self) -> Stream<G, D> where G::Timestamp: TotalOrder {
+        distinct_across_time(self, "DistinctTotal", HashSet::new())
+    }
+
+    fn distinct_approx(&self, bits: usize, hashes: usize) -> Stream<G, D> where G::Timestamp: TotalOrder {
+        distinct_across_time(self, "DistinctApprox", BloomFilter::new(bits, hashes))
+    }
+}
+
+/// A record of the records already produced.
+trait Seen<D> {
+    /// Records `datum`, and indicates whether it was not recorded before.
+    fn insert_new(&mut self, datum: &D) -> bool;
+}
+
+impl<D: Hash+Eq+Clone> Seen<D> for HashSet<D> {
+    fn insert_new(&mut self, datum: &D) -> bool {
+        !self.contains(datum) && self.insert(datum.clone())
+    }
+}
+
+/// A Bloom filter, with no false negatives and a bounded size.
+struct BloomFilter {
+    bits: Vec<u64>,
+    length: u64,
+    hashes: usize,
+}
+
+impl BloomFilter {
+    fn new(bits: usize, hashes: usize) -> Self {
+        assert!(bits > 0 && hashes > 0, "Bloom filter requires bits and hash functions");
+        BloomFilter {
+            bits: vec![0; (bits + 63) / 64],
+            length: bits as u64,
+            hashes,
+        }
+    }
+}
+
+impl<D: Hash> Seen<D> for BloomFilter {
+    fn insert_new(&mut self, datum: &D) -> bool {
+        // Derive the hash functions from two independent hashes.
+        let mut hasher = DefaultHasher::new();
+        datum.hash(&mut hasher);
+        let first = hasher.finish();
+        0x9E37_79B9_7F4A_7C15u64.hash(&mut hasher);
+        let second = hasher.finish() | 1;
+
+        let mut new = false;
+        for index in 0 .. self.hashes as u64 {
+            let bit = first.wrapping_add(index.wrapping_mul(second)) % self.length;
+            let (word, mask) = ((bit / 64) as usize, 1u64 << (bit % 64));
+            if self.bits[word] & mask == 0 {
+                self.bits[word] |= mask;
+                new = true;
+            }
+        }
+        new
+    }
+}
+
+/// Produces records not already in `seen`, in order of their times once complete.
+fn distinct_across_time<G, D, S>(stream: &Stream<G, D>, name: &str, mut seen: S) -> Stream<G, D>
+where
+    G: Scope,
+    G::Timestamp: TotalOrder,
+    D: ExchangeData+Hash+Eq,
+    S: Seen<D>+'static,
+{
+    let mut pending = HashMap::new();
+    let mut vector = Vec::new();
+    stream.unary_notify(Exchange::new(|x: &D| hash_key(x)), name, vec![], move |input, output, notificator| {
+        input.for_each(|time, data| {
+            data.swap(&mut vector);
+            pending
+                .entry(time.time().clone())
+                .or_insert_with(|| { notificator.notify_at(time.retain()); HashSet::new() })
+                .extend(vector.drain(..));
+        });
+
+        // Completed times are presented in order, so each record is produced at its first time.
+        notificator.for_each(|time, _, _| {
+            if let Some(records) = pending.remove(time.time()) {
+                let mut session = output.session(&time);
+                for datum in records {
+                    if seen.insert_new(&datum) {
+                        session.give(datum);
+                    }
+                }
+            }
+        });
+    })
+}
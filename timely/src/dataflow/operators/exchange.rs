@@ -1,12 +1,14 @@
 //! Exchange records between workers.
 
-use crate::ExchangeData;
+use crate::{Data, ExchangeData};
+use crate::communication::codec::{Codec, DefaultCodec};
+use crate::dataflow::channels::Message;
 use crate::dataflow::channels::pact::Exchange as ExchangePact;
 use crate::dataflow::{Stream, Scope};
 use crate::dataflow::operators::generic::operator::Operator;
 
 /// Exchange records between workers.
-pub trait Exchange<T, D: ExchangeData> {
+pub trait Exchange<T, D: Data> {
     /// Exchange records between workers.
     ///
     /// The closure supplied should map a reference to a record to a `u64`,
@@ -22,14 +24,34 @@ pub trait Exchange<T, D: ExchangeData> {
     ///            .inspect(|x| println!("seen: {:?}", x));
     /// });
     /// ```
-    fn exchange(&self, route: impl Fn(&D)->u64+'static) -> Self;
+    fn exchange(&self, route: impl Fn(&D)->u64+'static) -> Self where D: ExchangeData;
+
+    /// Exchange records between workers, serializing them with `codec`.
+    ///
+    /// The codec is only used for records sent to workers in other processes.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Exchange, Inspect};
+    /// use timely::communication::codec::ColumnarCodec;
+    ///
+    /// timely::example(|scope| {
+    ///     (0..10u64).to_stream(scope)
+    ///               .exchange_with_codec(|x| *x, ColumnarCodec)
+    ///               .inspect(|x| println!("seen: {:?}", x));
+    /// });
+    /// ```
+    fn exchange_with_codec<C: Codec<Message<T, D>>>(&self, route: impl Fn(&D)->u64+'static, codec: C) -> Self;
 }
 
 // impl<T: Timestamp, G: Scope<Timestamp=T>, D: ExchangeData> Exchange<T, D> for Stream<G, D> {
-impl<G: Scope, D: ExchangeData> Exchange<G::Timestamp, D> for Stream<G, D> {
-    fn exchange(&self, route: impl Fn(&D)->u64+'static) -> Stream<G, D> {
+impl<G: Scope, D: Data+Send+Sync> Exchange<G::Timestamp, D> for Stream<G, D> {
+    fn exchange(&self, route: impl Fn(&D)->u64+'static) -> Stream<G, D> where D: ExchangeData {
+        self.exchange_with_codec(route, DefaultCodec::default())
+    }
+    fn exchange_with_codec<C: Codec<Message<G::Timestamp, D>>>(&self, route: impl Fn(&D)->u64+'static, codec: C) -> Stream<G, D> {
         let mut vector = Vec::new();
-        self.unary(ExchangePact::new(route), "Exchange", move |_,_| move |input, output| {
+        self.unary(ExchangePact::new_with_codec(route, codec), "Exchange", move |_,_| move |input, output| {
             input.for_each(|time, data| {
                 data.swap(&mut vector);
                 output.session(&time).give_vec(&mut vector);
@@ -0,0 +1,416 @@
+//! Sources reading files, and sinks writing them, partitioned across workers.
+//!
+//! The sources split a file into contiguous byte ranges, one for each worker. Each worker reads the
+//! lines that start within its range, assigns each record a time with a user-supplied function, and
+//! downgrades its capability as it reads, so that downstream operators see progress before the file is
+//! exhausted. Lines may not contain embedded line breaks, including within quoted CSV fields, and the
+//! CSV sink refuses to write fields containing them.
+//!
+//! The sinks write the records each worker receives to its own part file in a directory, named
+//! `part-00000` for worker zero and so on, and flush the file whenever the input frontier advances.
+
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::Data;
+use crate::order::{PartialOrder, TotalOrder};
+use crate::progress::Antichain;
+use crate::dataflow::{Stream, Scope};
+use crate::dataflow::channels::pact::Pipeline;
+use crate::dataflow::operators::generic::operator::{source, Operator};
+
+/// The number of lines each source reads before yielding to other operators.
+const LINES_PER_ACTIVATION: usize = 1024;
+
+/// The format of delimited text files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvFormat {
+    /// The byte separating fields.
+    pub delimiter: u8,
+    /// Whether the first line of the file names the fields, rather than holding a record.
+    pub header: bool,
+}
+
+impl Default for CsvFormat {
+    fn default() -> Self {
+        CsvFormat { delimiter: b',', header: false }
+    }
+}
+
+impl CsvFormat {
+    /// Separates fields by `delimiter`.
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+    /// Indicates whether the first line of the file is a header.
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Splits `line` into fields, removing quotes around fields and unescaping doubled quotes.
+    pub fn parse(&self, line: &str) -> Vec<String> {
+        let delimiter = self.delimiter as char;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => { field.push('"'); chars.next(); },
+                '"' => { quoted = !quoted; },
+                c if c == delimiter && !quoted => { fields.push(::std::mem::take(&mut field)); },
+                c => { field.push(c); },
+            }
+        }
+        fields.push(field);
+        fields
+    }
+
+    /// Joins `fields` into a line, quoting fields that contain delimiters or quotes.
+    ///
+    /// Returns an error if a field contains a line break, as sources read files line by line and could
+    /// not read the record back.
+    pub fn format<S: AsRef<str>>(&self, fields: &[S]) -> io::Result<String> {
+        let delimiter = self.delimiter as char;
+        let mut line = String::new();
+        for (index, field) in fields.iter().enumerate() {
+            if index > 0 { line.push(delimiter); }
+            let field = field.as_ref();
+            if field.contains('\n') || field.contains('\r') {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("field {} contains a line break: {:?}", index, field)));
+            }
+            if field.contains(delimiter) || field.contains('"') {
+                line.push('"');
+                line.push_str(&field.replace('"', "\"\""));
+                line.push('"');
+            }
+            else {
+                line.push_str(field);
+            }
+        }
+        Ok(line)
+    }
+}
+
+/// Sources reading a file, each worker reading a part.
+pub trait ReadFiles<G: Scope> where G::Timestamp: TotalOrder {
+    /// Reads the lines of the file at `path`, at times assigned by `time`.
+    ///
+    /// Times should not decrease along the file. A line with a time earlier than that of a previous
+    /// line of the same worker is produced at the later time. The operator panics if the file cannot
+    /// be read.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::Inspect;
+    /// use timely::dataflow::operators::file::ReadFiles;
+    ///
+    /// let path = std::env::temp_dir().join("timely-read-lines-example.txt");
+    /// std::fs::write(&path, "0 alpha\n0 beta\n1 gamma\n").unwrap();
+    ///
+    /// timely::example(move |scope| {
+    ///     scope.read_lines(path, |line| line.split(' ').next().unwrap().parse().unwrap())
+    ///          .inspect_time(|time, line| assert!(line.starts_with(&time.to_string())));
+    /// });
+    /// ```
+    fn read_lines<P, F>(&self, path: P, time: F) -> Stream<G, String>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&str)->G::Timestamp+'static;
+
+    /// Reads the records of the delimited text file at `path`, at times assigned by `time`.
+    ///
+    /// Each record is the list of its fields. A header line, if the format has one, is skipped.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::Inspect;
+    /// use timely::dataflow::operators::file::{CsvFormat, ReadFiles};
+    ///
+    /// let path = std::env::temp_dir().join("timely-read-csv-example.csv");
+    /// std::fs::write(&path, "time,name\n0,\"smith, alice\"\n1,bob\n").unwrap();
+    ///
+    /// timely::example(move |scope| {
+    ///     scope.read_csv(path, CsvFormat::default().header(true), |fields| fields[0].parse().unwrap())
+    ///          .inspect(|fields| assert!(fields[1] == "smith, alice" || fields[1] == "bob"));
+    /// });
+    /// ```
+    fn read_csv<P, F>(&self, path: P, format: CsvFormat, time: F) -> Stream<G, Vec<String>>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&[String])->G::Timestamp+'static;
+}
+
+impl<G: Scope> ReadFiles<G> for G where G::Timestamp: TotalOrder {
+
+    fn read_lines<P, F>(&self, path: P, mut time: F) -> Stream<G, String>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&str)->G::Timestamp+'static,
+    {
+        read_partitioned(self, "ReadLines", path.as_ref(), false, |line| line, move |line| time(line))
+    }
+
+    fn read_csv<P, F>(&self, path: P, format: CsvFormat, mut time: F) -> Stream<G, Vec<String>>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&[String])->G::Timestamp+'static,
+    {
+        let header = format.header;
+        read_partitioned(self, "ReadCsv", path.as_ref(), header, move |line| format.parse(&line), move |fields| time(fields))
+    }
+}
+
+/// Reads this worker's part of the file at `path`, producing records parsed from each line.
+fn read_partitioned<G, D, R, F>(scope: &G, name: &str, path: &Path, header: bool, mut parse: R, mut time: F) -> Stream<G, D>
+where
+    G: Scope,
+    G::Timestamp: TotalOrder,
+    D: Data,
+    R: FnMut(String)->D+'static,
+    F: FnMut(&D)->G::Timestamp+'static,
+{
+    let path = path.to_owned();
+    let part =
+    Part::open(&path, scope.index(), scope.peers(), header)
+        .unwrap_or_else(|error| panic!("failed to open {}: {}", path.display(), error));
+
+    source(scope, name, move |capability, info| {
+
+        let activator = scope.activator_for(&info.address[..]);
+        let mut capability = Some(capability);
+        let mut part = Some(part);
+        let mut buffer = Vec::new();
+
+        move |output| {
+
+            let mut done = false;
+            if let (Some(cap), Some(reader)) = (capability.as_mut(), part.as_mut()) {
+                for _ in 0 .. LINES_PER_ACTIVATION {
+                    match reader.next_line().unwrap_or_else(|error| panic!("failed to read {}: {}", path.display(), error)) {
+                        Some(line) => {
+                            let record = parse(line);
+                            let record_time = time(&record);
+                            if cap.time().less_than(&record_time) {
+                                output.session(&cap).give_vec(&mut buffer);
+                                cap.downgrade(&record_time);
+                            }
+                            buffer.push(record);
+                        },
+                        None => { done = true; break; },
+                    }
+                }
+                output.session(&cap).give_vec(&mut buffer);
+            }
+
+            if done {
+                capability = None;
+                part = None;
+            }
+            else if capability.is_some() {
+                activator.activate();
+            }
+        }
+    })
+}
+
+/// The lines of a file starting within a range of bytes.
+struct Part {
+    reader: BufReader<File>,
+    position: u64,
+    end: u64,
+    line: Vec<u8>,
+}
+
+impl Part {
+    /// Opens the range of the file at `path` read by worker `index` of `peers`.
+    ///
+    /// If the file has a `header` line, the worker whose range holds the start of the file skips it.
+    fn open(path: &Path, index: usize, peers: usize, header: bool) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let length = file.metadata()?.len();
+        let start = length * index as u64 / peers as u64;
+        let end = length * (index + 1) as u64 / peers as u64;
+
+        let mut part = Part { reader: BufReader::new(file), position: 0, end, line: Vec::new() };
+        if start > 0 {
+            // Skip the remainder of the line in progress at `start`, which belongs to the previous part.
+            part.reader.seek(SeekFrom::Start(start - 1))?;
+            part.position = start - 1 + part.reader.read_until(b'\n', &mut part.line)? as u64;
+        }
+        else if header && end > 0 {
+            part.next_line()?;
+        }
+        Ok(part)
+    }
+
+    /// The next line starting within the range, without its line terminator.
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        if self.position >= self.end {
+            return Ok(None);
+        }
+        self.line.clear();
+        let read = self.reader.read_until(b'\n', &mut self.line)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        self.position += read as u64;
+        while self.line.last().map(|&byte| byte == b'\n' || byte == b'\r').unwrap_or(false) {
+            self.line.pop();
+        }
+        Ok(Some(String::from_utf8_lossy(&self.line).into_owned()))
+    }
+}
+
+/// Sinks writing each worker's records to its own part file.
+pub trait WriteFiles<G: Scope, D: Data> {
+    /// Writes each record as a line to a part file in `directory`, which is created if absent.
+    ///
+    /// The operator panics if the file cannot be written.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::ToStream;
+    /// use timely::dataflow::operators::file::WriteFiles;
+    ///
+    /// let directory = std::env::temp_dir().join("timely-write-lines-example");
+    /// timely::example(move |scope| {
+    ///     (0..10).to_stream(scope)
+    ///            .write_lines(directory);
+    /// });
+    /// ```
+    fn write_lines<P: AsRef<Path>>(&self, directory: P) where D: Display;
+
+    /// Writes each record as a line of delimited text to a part file in `directory`, which is created if absent.
+    ///
+    /// The operator panics if the file cannot be written, or if a field contains a line break.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Map};
+    /// use timely::dataflow::operators::file::{CsvFormat, WriteFiles};
+    ///
+    /// let directory = std::env::temp_dir().join("timely-write-csv-example");
+    /// timely::example(move |scope| {
+    ///     (0..10).to_stream(scope)
+    ///            .map(|x| vec![x.to_string(), format!("record {}", x)])
+    ///            .write_csv(directory, CsvFormat::default());
+    /// });
+    /// ```
+    fn write_csv<P: AsRef<Path>>(&self, directory: P, format: CsvFormat) where D: AsRef<[String]>;
+}
+
+impl<G: Scope, D: Data> WriteFiles<G, D> for Stream<G, D> {
+
+    fn write_lines<P: AsRef<Path>>(&self, directory: P) where D: Display {
+        write_partitioned(self, "WriteLines", directory.as_ref(), |record, writer| writeln!(writer, "{}", record))
+    }
+
+    fn write_csv<P: AsRef<Path>>(&self, directory: P, format: CsvFormat) where D: AsRef<[String]> {
+        write_partitioned(self, "WriteCsv", directory.as_ref(), move |record, writer| writeln!(writer, "{}", format.format(record.as_ref())?))
+    }
+}
+
+/// Writes the records this worker receives to its part file in `directory`, flushing at frontier changes.
+fn write_partitioned<G, D, W>(stream: &Stream<G, D>, name: &str, directory: &Path, mut write: W)
+where
+    G: Scope,
+    D: Data,
+    W: FnMut(&D, &mut BufWriter<File>)->io::Result<()>+'static,
+{
+    let path: PathBuf = directory.join(format!("part-{:05}", stream.scope().index()));
+    let mut writer =
+    ::std::fs::create_dir_all(directory)
+        .and_then(|()| File::create(&path))
+        .map(BufWriter::new)
+        .unwrap_or_else(|error| panic!("failed to create {}: {}", path.display(), error));
+
+    let mut frontier = Antichain::new();
+    let mut vector = Vec::new();
+    stream.sink(Pipeline, name, move |input| {
+        input.for_each(|_time, data| {
+            data.swap(&mut vector);
+            for record in vector.drain(..) {
+                write(&record, &mut writer).unwrap_or_else(|error| panic!("failed to write {}: {}", path.display(), error));
+            }
+        });
+
+        let current = input.frontier().frontier().to_owned();
+        if current != frontier {
+            writer.flush().unwrap_or_else(|error| panic!("failed to flush {}: {}", path.display(), error));
+            frontier = current;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CsvFormat, Part};
+
+    #[test]
+    fn round_trip() {
+        let format = CsvFormat::default();
+        let fields = vec!["plain".to_owned(), "smith, alice".to_owned(), "say \"hi\"".to_owned(), String::new()];
+        let line = format.format(&fields).unwrap();
+        assert_eq!(line, "plain,\"smith, alice\",\"say \"\"hi\"\"\",");
+        assert_eq!(format.parse(&line), fields);
+
+        let format = CsvFormat::default().delimiter(b'\t');
+        assert_eq!(format.parse(&format.format(&["a\tb", "c"]).unwrap()), vec!["a\tb", "c"]);
+    }
+
+    #[test]
+    fn rejects_line_breaks() {
+        let format = CsvFormat::default();
+        assert!(format.format(&["first\nsecond"]).is_err());
+        assert!(format.format(&["ok", "carriage\rreturn"]).is_err());
+    }
+
+    /// Reads the lines of worker `index` of `peers` from the file at `path`.
+    fn read_part(path: &::std::path::Path, index: usize, peers: usize, header: bool) -> Vec<String> {
+        let mut part = Part::open(path, index, peers, header).unwrap();
+        let mut lines = Vec::new();
+        while let Some(line) = part.next_line().unwrap() {
+            lines.push(line);
+        }
+        lines
+    }
+
+    #[test]
+    fn partitioned_lines() {
+        let contents = [
+            ("lines", "header\nfirst\nsecond line\n\na much longer third line\r\nx\nlast without newline"),
+            ("short", "h\r\nab\n"),
+            // With four peers, the first worker's range is empty and the second holds the first byte.
+            ("tiny", "h\nx"),
+        ];
+        for (name, text) in contents.iter() {
+            let path = ::std::env::temp_dir().join(format!("timely-file-partitioned-{}-{}.txt", name, ::std::process::id()));
+            ::std::fs::write(&path, text).unwrap();
+            let expected: Vec<String> = text.lines().map(|line| line.to_owned()).collect();
+
+            for peers in 1 ..= 4 {
+                for &header in [false, true].iter() {
+                    let parts: Vec<Vec<String>> = (0 .. peers).map(|index| read_part(&path, index, peers, header)).collect();
+                    // Lines are read in order, each by exactly one worker, without line terminators.
+                    let read: Vec<String> = parts.concat();
+                    let skip = if header { 1 } else { 0 };
+                    assert_eq!(read, expected[skip ..].to_vec(), "{} with {} peers, header {}", name, peers, header);
+                    assert!(read.iter().all(|line| !line.ends_with('\r')));
+                    // Only the worker whose range holds the first byte skips the header.
+                    if header {
+                        let owner = (0 .. peers).find(|&index| (index + 1) * text.len() / peers > 0).unwrap();
+                        for index in 0 .. peers {
+                            assert_eq!(read_part(&path, index, peers, false).len() - parts[index].len(), if index == owner { 1 } else { 0 });
+                        }
+                    }
+                }
+            }
+            ::std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Iteration of a dataflow fragment until convergence or a bound on rounds.
+//!
+//! The `iterate` method builds the loop that `examples/bfs.rs` and `examples/pagerank.rs` otherwise
+//! assemble by hand: it creates an iterative scope, enters the input, applies the body in each round,
+//! feeds the body's output back as the input of the next round, and leaves the scope.
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use crate::Data;
+use crate::progress::Timestamp;
+use crate::dataflow::{Stream, Scope};
+use crate::dataflow::scopes::child::Iterative;
+use crate::dataflow::operators::{Enter, Leave, Concat, Inspect, BranchWhen, Filter};
+use crate::dataflow::operators::feedback::{LoopVariable, ConnectLoop};
+
+/// Counts of records produced in each round of an iteration, for each time.
+///
+/// Clones refer to the same counts. Each worker counts the records it produces.
+pub struct RoundCounts<T> {
+    counts: Rc<RefCell<BTreeMap<(T, u64), usize>>>,
+}
+
+impl<T> Clone for RoundCounts<T> {
+    fn clone(&self) -> Self {
+        RoundCounts { counts: self.counts.clone() }
+    }
+}
+
+impl<T: Timestamp> RoundCounts<T> {
+    fn new() -> Self {
+        RoundCounts { counts: Rc::new(RefCell::new(BTreeMap::new())) }
+    }
+    fn record(&self, time: &T, round: u64, count: usize) {
+        *self.counts.borrow_mut().entry((time.clone(), round)).or_insert(0) += count;
+    }
+    /// The number of records produced in `round` for `time`.
+    pub fn count(&self, time: &T, round: u64) -> usize {
+        self.counts.borrow().get(&(time.clone(), round)).cloned().unwrap_or(0)
+    }
+    /// The number of rounds that produced records for `time`.
+    pub fn rounds(&self, time: &T) -> u64 {
+        self.counts.borrow().keys().filter(|(t, _)| t == time).map(|(_, round)| round + 1).max().unwrap_or(0)
+    }
+    /// All counts, ordered by time and round.
+    pub fn counts(&self) -> Vec<(T, u64, usize)> {
+        self.counts.borrow().iter().map(|((time, round), count)| (time.clone(), *round, *count)).collect()
+    }
+    /// Discards the counts for times strictly less than `time`.
+    pub fn discard_before(&self, time: &T) {
+        self.counts.borrow_mut().retain(|(t, _), _| !t.less_than(time));
+    }
+}
+
+/// Methods to iterate a dataflow fragment.
+pub trait Iterate<G: Scope, D: Data> {
+    /// Applies `body` in rounds, starting from the records of `self`.
+    ///
+    /// The output of `body` in each round is the input of the next round. Iteration for a time stops
+    /// once a round produces no records, or after `max_rounds` rounds if bounded; a bound of zero applies
+    /// no rounds. Every record the body produces leaves the loop, at the time it entered. The returned
+    /// counts report the records produced in each round.
+    ///
+    /// # Examples
+    ///
+    /// The following example halves each number until it reaches zero, producing the sequence of halves.
+    ///
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Map, Filter, Inspect};
+    /// use timely::dataflow::operators::iterate::Iterate;
+    ///
+    /// timely::example(|scope| {
+    ///     let (halves, _counts) =
+    ///     (1..10u64)
+    ///         .to_stream(scope)
+    ///         .iterate(Some(10), |round| round.map(|x| x / 2).filter(|x| *x > 0));
+    ///
+    ///     halves.inspect(|x| println!("half: {:?}", x));
+    /// });
+    /// ```
+    fn iterate<F>(&self, max_rounds: Option<u64>, body: F) -> (Stream<G, D>, RoundCounts<G::Timestamp>)
+    where
+        F: for<'a> FnOnce(&Stream<Iterative<'a, G, u64>, D>)->Stream<Iterative<'a, G, u64>, D>;
+
+    /// Applies `body` in rounds as `iterate` does, except that records for which `converged` holds
+    /// leave the loop without being fed back into the next round.
+    ///
+    /// Iteration for a time stops once a round produces only converged records.
+    ///
+    /// # Examples
+    ///
+    /// The following example doubles each number until it exceeds one hundred, producing only the final values.
+    ///
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Map, Filter, Inspect};
+    /// use timely::dataflow::operators::iterate::Iterate;
+    ///
+    /// timely::example(|scope| {
+    ///     let (doubled, _counts) =
+    ///     (1..10u64)
+    ///         .to_stream(scope)
+    ///         .iterate_until(None, |x| *x > 100, |round| round.map(|x| x * 2));
+    ///
+    ///     doubled.filter(|x| *x > 100)
+    ///            .inspect(|x| println!("final: {:?}", x));
+    /// });
+    /// ```
+    fn iterate_until<P, F>(&self, max_rounds: Option<u64>, converged: P, body: F) -> (Stream<G, D>, RoundCounts<G::Timestamp>)
+    where
+        P: FnMut(&D)->bool+'static,
+        F: for<'a> FnOnce(&Stream<Iterative<'a, G, u64>, D>)->Stream<Iterative<'a, G, u64>, D>;
+}
+
+impl<G: Scope, D: Data> Iterate<G, D> for Stream<G, D> {
+    fn iterate<F>(&self, max_rounds: Option<u64>, body: F) -> (Stream<G, D>, RoundCounts<G::Timestamp>)
+    where
+        F: for<'a> FnOnce(&Stream<Iterative<'a, G, u64>, D>)->Stream<Iterative<'a, G, u64>, D>,
+    {
+        self.iterate_until(max_rounds, |_| false, body)
+    }
+
+    fn iterate_until<P, F>(&self, max_rounds: Option<u64>, mut converged: P, body: F) -> (Stream<G, D>, RoundCounts<G::Timestamp>)
+    where
+        P: FnMut(&D)->bool+'static,
+        F: for<'a> FnOnce(&Stream<Iterative<'a, G, u64>, D>)->Stream<Iterative<'a, G, u64>, D>,
+    {
+        let counts = RoundCounts::new();
+        let recorder = counts.clone();
+
+        let mut scope = self.scope();
+        let output = scope.iterative::<u64, _, _>(|inner| {
+
+            let (handle, cycle) = inner.loop_variable(1);
+
+            // Records that would start a round beyond the bound are dropped before reaching the body.
+            let (input, _exhausted) =
+            self.enter(inner)
+                .concat(&cycle)
+                .branch_when(move |time| max_rounds.map(|max_rounds| time.inner >= max_rounds).unwrap_or(false));
+
+            let results =
+            body(&input)
+                .inspect_batch(move |time, data| recorder.record(&time.outer, time.inner, data.len()));
+
+            results
+                .filter(move |record| !converged(record))
+                .connect_loop(handle);
+
+            results.leave()
+        });
+
+        (output, counts)
+    }
+}
@@ -0,0 +1,319 @@
+//! Hash joins of keyed streams.
+//!
+//! The `Join` operators exchange both inputs by key and maintain the records of each input in a
+//! hash map, so that each record is matched against the records of the other input that arrived
+//! before it. A match of records at times `t1` and `t2` is produced at the later of the two times,
+//! which makes the output independent of the order in which records arrive.
+//!
+//! Records are retained only as long as they can still match. The time-to-live and windowed joins
+//! use the frontier of the other input to discard records whose partners must have already arrived.
+
+use std::hash::{Hash, Hasher};
+use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_map::DefaultHasher;
+
+use crate::{Data, ExchangeData};
+use crate::order::{PartialOrder, TotalOrder};
+use crate::progress::Timestamp;
+use crate::progress::timestamp::PathSummary;
+use crate::progress::frontier::AntichainRef;
+use crate::dataflow::{Stream, Scope};
+use crate::dataflow::channels::pact::Exchange;
+use crate::dataflow::channels::pushers::Tee;
+use crate::dataflow::operators::{Capability, CapabilityRef};
+use crate::dataflow::operators::generic::OutputHandle;
+use crate::dataflow::operators::generic::operator::Operator;
+
+/// Joins of streams of `(key, val)` pairs.
+pub trait Join<G: Scope, K: ExchangeData+Hash+Eq, V1: ExchangeData> {
+    /// Matches each record with all records of `other` with the same key.
+    ///
+    /// All records are retained indefinitely.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Map, Inspect};
+    /// use timely::dataflow::operators::join::Join;
+    ///
+    /// timely::example(|scope| {
+    ///     let parities = vec![(0, 10), (1, 11)].to_stream(scope);
+    ///     (0..4).to_stream(scope)
+    ///           .map(|x| (x % 2, x))
+    ///           .join(&parities)
+    ///           .inspect(|&(key, val, parity)| assert_eq!((val % 2, parity), (key, 10 + key)));
+    /// });
+    /// ```
+    fn join<V2: ExchangeData>(&self, other: &Stream<G, (K, V2)>) -> Stream<G, (K, V1, V2)>;
+
+    /// Matches records with records of `other` with the same key and whose times are within `ttl` of each other.
+    ///
+    /// Records at times `t1` and `t2` match if `t2` is less than `ttl` applied to `t1`, and vice versa.
+    /// Records are discarded once the frontier of the other input reaches their time advanced by `ttl`.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Map, Delay, Inspect};
+    /// use timely::dataflow::operators::join::Join;
+    ///
+    /// timely::example(|scope| {
+    ///     // each record `x` arrives at time `x`.
+    ///     let left = (0..10u64).to_stream(scope).delay(|x, _| *x).map(|x| ((), x));
+    ///     let right = (0..10u64).to_stream(scope).delay(|x, _| *x).map(|x| ((), x));
+    ///     left.join_ttl(&right, 2)
+    ///         .inspect(|&((), x, y)| assert!(x <= y + 1 && y <= x + 1));
+    /// });
+    /// ```
+    fn join_ttl<V2: ExchangeData>(&self, other: &Stream<G, (K, V2)>, ttl: <G::Timestamp as Timestamp>::Summary) -> Stream<G, (K, V1, V2)>;
+
+    /// Matches records as `join_ttl` does, and also produces each record of `self` that matched no record.
+    ///
+    /// Unmatched records are produced with `None` at their own time, once they are discarded.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Map, Inspect};
+    /// use timely::dataflow::operators::join::Join;
+    ///
+    /// timely::example(|scope| {
+    ///     let right = vec![(0, 10)].to_stream(scope);
+    ///     (0..2).to_stream(scope)
+    ///           .map(|x| (x, x))
+    ///           .left_join_ttl(&right, 1)
+    ///           .inspect(|x| assert!(*x == (0, 0, Some(10)) || *x == (1, 1, None)));
+    /// });
+    /// ```
+    fn left_join_ttl<V2: ExchangeData>(&self, other: &Stream<G, (K, V2)>, ttl: <G::Timestamp as Timestamp>::Summary) -> Stream<G, (K, V1, Option<V2>)>;
+
+    /// Matches records with records of `other` with the same key in the same window.
+    ///
+    /// The `window` function maps each time to the end of its window, exclusive, and records match
+    /// if their times map to the same end. Records are discarded once the frontier of the other input
+    /// reaches the end of their window.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Map, Delay, Inspect};
+    /// use timely::dataflow::operators::join::Join;
+    ///
+    /// timely::example(|scope| {
+    ///     // each record `x` arrives at time `x`, and windows span five times.
+    ///     let left = (0..10u64).to_stream(scope).delay(|x, _| *x).map(|x| ((), x));
+    ///     let right = (0..10u64).to_stream(scope).delay(|x, _| *x).map(|x| ((), x));
+    ///     left.join_window(&right, |time| (time / 5 + 1) * 5)
+    ///         .inspect(|&((), x, y)| assert_eq!(x / 5, y / 5));
+    /// });
+    /// ```
+    fn join_window<V2: ExchangeData, W: Fn(&G::Timestamp)->G::Timestamp+'static>(&self, other: &Stream<G, (K, V2)>, window: W) -> Stream<G, (K, V1, V2)>;
+
+    /// Joins with general conditions on times, from which the other joins are built.
+    ///
+    /// Records at times `t1` and `t2` match if `matches(t1, t2)` holds. Each record is discarded once
+    /// the frontier of the other input reaches `expiry` applied to its time, or is retained indefinitely
+    /// if `expiry` returns `None`; no record at or beyond the expiry should match it. If `left_outer` is
+    /// set, records of `self` that matched no record are produced at their time once discarded. The
+    /// `emit` function produces output from a key, a value of `self`, and the matching value of `other`.
+    fn join_core<V2, R, M, X, E>(&self, other: &Stream<G, (K, V2)>, matches: M, expiry: X, left_outer: bool, emit: E) -> Stream<G, R>
+    where
+        V2: ExchangeData,
+        R: Data,
+        M: Fn(&G::Timestamp, &G::Timestamp)->bool+'static,
+        X: Fn(&G::Timestamp)->Option<G::Timestamp>+'static,
+        E: Fn(&K, &V1, Option<&V2>)->R+'static;
+}
+
+impl<G: Scope, K: ExchangeData+Hash+Eq, V1: ExchangeData> Join<G, K, V1> for Stream<G, (K, V1)>
+where G::Timestamp: TotalOrder {
+
+    fn join<V2: ExchangeData>(&self, other: &Stream<G, (K, V2)>) -> Stream<G, (K, V1, V2)> {
+        self.join_core(other, |_, _| true, |_| None, false, |key, val1, val2| {
+            (key.clone(), val1.clone(), val2.expect("inner join without match").clone())
+        })
+    }
+
+    fn join_ttl<V2: ExchangeData>(&self, other: &Stream<G, (K, V2)>, ttl: <G::Timestamp as Timestamp>::Summary) -> Stream<G, (K, V1, V2)> {
+        let expiry = ttl.clone();
+        self.join_core(other, move |t1, t2| within(&ttl, t1, t2), move |t| expiry.results_in(t), false, |key, val1, val2| {
+            (key.clone(), val1.clone(), val2.expect("inner join without match").clone())
+        })
+    }
+
+    fn left_join_ttl<V2: ExchangeData>(&self, other: &Stream<G, (K, V2)>, ttl: <G::Timestamp as Timestamp>::Summary) -> Stream<G, (K, V1, Option<V2>)> {
+        let expiry = ttl.clone();
+        self.join_core(other, move |t1, t2| within(&ttl, t1, t2), move |t| expiry.results_in(t), true, |key, val1, val2| {
+            (key.clone(), val1.clone(), val2.cloned())
+        })
+    }
+
+    fn join_window<V2: ExchangeData, W: Fn(&G::Timestamp)->G::Timestamp+'static>(&self, other: &Stream<G, (K, V2)>, window: W) -> Stream<G, (K, V1, V2)> {
+        let window = ::std::rc::Rc::new(window);
+        let expiry = window.clone();
+        self.join_core(other, move |t1, t2| window(t1) == window(t2), move |t| Some(expiry(t)), false, |key, val1, val2| {
+            (key.clone(), val1.clone(), val2.expect("inner join without match").clone())
+        })
+    }
+
+    fn join_core<V2, R, M, X, E>(&self, other: &Stream<G, (K, V2)>, matches: M, expiry: X, left_outer: bool, emit: E) -> Stream<G, R>
+    where
+        V2: ExchangeData,
+        R: Data,
+        M: Fn(&G::Timestamp, &G::Timestamp)->bool+'static,
+        X: Fn(&G::Timestamp)->Option<G::Timestamp>+'static,
+        E: Fn(&K, &V1, Option<&V2>)->R+'static,
+    {
+        let exchange1 = Exchange::new(|(key, _): &(K, V1)| hash_key(key));
+        let exchange2 = Exchange::new(|(key, _): &(K, V2)| hash_key(key));
+
+        self.binary_frontier(other, exchange1, exchange2, "Join", move |_capability, _info| {
+
+            let mut state1 = JoinState::<G::Timestamp, K, V1>::new();
+            let mut state2 = JoinState::<G::Timestamp, K, V2>::new();
+
+            // Capabilities for the times of retained records of `self`, if they may be produced unmatched.
+            let mut held: HashMap<G::Timestamp, (Capability<G::Timestamp>, usize)> = HashMap::new();
+
+            let mut vector1 = Vec::new();
+            let mut vector2 = Vec::new();
+            let mut results = Vec::new();
+
+            move |input1, input2, output| {
+
+                // Probe the records of the second input, then retain the records of the first.
+                input1.for_each(|time, data| {
+                    data.swap(&mut vector1);
+                    let t1 = time.time().clone();
+                    for (key, val1) in vector1.drain(..) {
+                        let mut matched = false;
+                        if let Some(records) = state2.records.get_mut(&key) {
+                            for (t2, val2, matched2) in records.iter_mut() {
+                                if matches(&t1, t2) {
+                                    let later = if t1.less_equal(t2) { t2.clone() } else { t1.clone() };
+                                    results.push((later, emit(&key, &val1, Some(val2))));
+                                    matched = true;
+                                    *matched2 = true;
+                                }
+                            }
+                        }
+                        if left_outer {
+                            held.entry(t1.clone()).or_insert_with(|| (time.delayed(&t1), 0)).1 += 1;
+                        }
+                        state1.insert(key, t1.clone(), val1, matched, expiry(&t1));
+                    }
+                    send(&time, output, &mut results);
+                });
+
+                // Probe the records of the first input, then retain the records of the second.
+                input2.for_each(|time, data| {
+                    data.swap(&mut vector2);
+                    let t2 = time.time().clone();
+                    for (key, val2) in vector2.drain(..) {
+                        let mut matched = false;
+                        if let Some(records) = state1.records.get_mut(&key) {
+                            for (t1, val1, matched1) in records.iter_mut() {
+                                if matches(t1, &t2) {
+                                    let later = if t2.less_equal(t1) { t1.clone() } else { t2.clone() };
+                                    results.push((later, emit(&key, val1, Some(&val2))));
+                                    matched = true;
+                                    *matched1 = true;
+                                }
+                            }
+                        }
+                        state2.insert(key, t2.clone(), val2, matched, expiry(&t2));
+                    }
+                    send(&time, output, &mut results);
+                });
+
+                // Discard records that can no longer match, producing unmatched records if required.
+                state1.expire(input2.frontier().frontier(), &expiry, |key, t1, val1, matched| {
+                    if left_outer {
+                        if !matched {
+                            let capability = &held.get(&t1).expect("unmatched record without capability").0;
+                            output.session(capability).give(emit(&key, &val1, None));
+                        }
+                        let remaining = {
+                            let entry = held.get_mut(&t1).expect("retained record without capability");
+                            entry.1 -= 1;
+                            entry.1
+                        };
+                        if remaining == 0 { held.remove(&t1); }
+                    }
+                });
+                state2.expire(input1.frontier().frontier(), &expiry, |_, _, _, _| { });
+            }
+        })
+    }
+}
+
+/// Indicates whether `t1` and `t2` are each before the other advanced by `ttl`.
+fn within<T: Timestamp>(ttl: &T::Summary, t1: &T, t2: &T) -> bool {
+    let before = |a: &T, b: &T| ttl.results_in(b).map(|limit| a.less_than(&limit)).unwrap_or(true);
+    before(t1, t2) && before(t2, t1)
+}
+
+/// Sends buffered results, each at a time no earlier than `time`.
+fn send<T: Timestamp, R: Data>(time: &CapabilityRef<T>, output: &mut OutputHandle<T, R, Tee<T, R>>, results: &mut Vec<(T, R)>) {
+    results.sort_by(|x, y| x.0.cmp(&y.0));
+    let mut drain = results.drain(..).peekable();
+    while let Some((later, result)) = drain.next() {
+        let capability = time.delayed(&later);
+        let mut session = output.session(&capability);
+        session.give(result);
+        while drain.peek().map(|(next, _)| next == &later).unwrap_or(false) {
+            session.give(drain.next().expect("peeked result absent").1);
+        }
+    }
+}
+
+/// Routes keys to workers.
+pub(crate) fn hash_key<K: Hash>(key: &K) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The retained records of one input.
+struct JoinState<T, K, V> {
+    /// Records by key, with their times and whether they have matched.
+    records: HashMap<K, Vec<(T, V, bool)>>,
+    /// Keys with records that expire at each time.
+    expiries: BTreeMap<T, Vec<K>>,
+}
+
+impl<T: Timestamp+TotalOrder, K: Hash+Eq+Clone, V> JoinState<T, K, V> {
+    fn new() -> Self {
+        JoinState { records: HashMap::new(), expiries: BTreeMap::new() }
+    }
+    fn insert(&mut self, key: K, time: T, val: V, matched: bool, expiry: Option<T>) {
+        if let Some(expiry) = expiry {
+            self.expiries.entry(expiry).or_insert_with(Vec::new).push(key.clone());
+        }
+        self.records.entry(key).or_insert_with(Vec::new).push((time, val, matched));
+    }
+    /// Removes records whose expiry the `frontier` has reached, and passes them to `action`.
+    fn expire<X, F>(&mut self, frontier: AntichainRef<T>, expiry: &X, mut action: F)
+    where
+        X: Fn(&T)->Option<T>,
+        F: FnMut(K, T, V, bool),
+    {
+        let expired = |time: &T| expiry(time).map(|limit| !frontier.less_than(&limit)).unwrap_or(false);
+        while let Some(limit) = self.expiries.keys().next().cloned() {
+            if frontier.less_than(&limit) { break; }
+            for key in self.expiries.remove(&limit).expect("expiry unexpectedly absent") {
+                if let Some(mut records) = self.records.remove(&key) {
+                    let mut index = 0;
+                    while index < records.len() {
+                        if expired(&records[index].0) {
+                            let (time, val, matched) = records.swap_remove(index);
+                            action(key.clone(), time, val, matched);
+                        }
+                        else {
+                            index += 1;
+                        }
+                    }
+                    if !records.is_empty() {
+                        self.records.insert(key, records);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -34,6 +34,11 @@ pub use self::generic::{Notificator, FrontierNotificator};
 
 pub use self::reclock::Reclock;
 pub use self::count::Accumulate;
+pub use self::join::Join;
+pub use self::distinct::Distinct;
+pub use self::sort::{SortByKey, TopK};
+pub use self::file::{ReadFiles, WriteFiles};
+pub use self::iterate::Iterate;
 
 pub mod enterleave;
 pub mod input;
@@ -61,6 +66,11 @@ pub mod generic;
 
 pub mod reclock;
 pub mod count;
+pub mod join;
+pub mod distinct;
+pub mod sort;
+pub mod file;
+pub mod iterate;
 
 // keep "mint" module-private
 mod capability;
@@ -188,7 +188,7 @@ mod tests {
     fn probe() {
 
         // initializes and runs a timely dataflow computation
-        crate::execute(Configuration::Thread, |worker| {
+        crate::execute(Configuration::Thread, |worker, _| {
 
             // create a new input, and inspect its output
             let (mut input, probe) = worker.dataflow(move |scope| {
@@ -0,0 +1,112 @@
+//! Operators producing records of each time in order of a key.
+//!
+//! The operators buffer the records of each time until the input frontier passes it, and then sort
+//! them with a least-significant byte radix sorter from `timely_sort`, by a key of unsigned integer type.
+
+use std::collections::HashMap;
+
+use timely_sort::{LSBRadixSorter, RadixSorter, RadixSorterBase, Unsigned};
+
+use crate::{Data, ExchangeData};
+use crate::dataflow::{Stream, Scope};
+use crate::dataflow::channels::pact::{Exchange, ParallelizationContract, Pipeline};
+use crate::dataflow::operators::generic::operator::Operator;
+
+/// Methods to sort the records of each time.
+pub trait SortByKey<G: Scope, D: Data> {
+    /// Produces the records of each time in increasing order of `key`, once the time is complete.
+    ///
+    /// Records are sorted within each worker, and records with equal keys keep their order of arrival.
+    /// To sort all records of a time, first exchange them to a single worker.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Exchange, Inspect};
+    /// use timely::dataflow::operators::sort::SortByKey;
+    ///
+    /// timely::example(|scope| {
+    ///     vec![3u64, 1, 4, 1, 5, 9, 2, 6].to_stream(scope)
+    ///         .exchange(|_| 0)
+    ///         .sort_by_key(|x| *x)
+    ///         .inspect_batch(|_time, data| assert!(data.windows(2).all(|w| w[0] <= w[1])));
+    /// });
+    /// ```
+    fn sort_by_key<U: Unsigned+'static, F: Fn(&D)->U+'static>(&self, key: F) -> Stream<G, D>;
+}
+
+impl<G: Scope, D: Data> SortByKey<G, D> for Stream<G, D> {
+    fn sort_by_key<U: Unsigned+'static, F: Fn(&D)->U+'static>(&self, key: F) -> Stream<G, D> {
+        sort_per_time(self, Pipeline, "SortByKey", key, None)
+    }
+}
+
+/// Methods to select the records of each time with the largest keys.
+pub trait TopK<G: Scope, D: ExchangeData> {
+    /// Produces the `k` records of each time with the largest `key`, in decreasing order of `key`.
+    ///
+    /// Each worker first selects its own `k` records, which are then exchanged to worker zero to select
+    /// the `k` records across all workers. Among records with equal keys, the selection is arbitrary.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Inspect};
+    /// use timely::dataflow::operators::sort::TopK;
+    ///
+    /// timely::example(|scope| {
+    ///     (0..100u64).to_stream(scope)
+    ///         .top_k(3, |x| *x)
+    ///         .inspect_batch(|_time, data| assert_eq!(&data[..], &[99, 98, 97]));
+    /// });
+    /// ```
+    fn top_k<U: Unsigned+'static, F: Fn(&D)->U+Clone+'static>(&self, k: usize, key: F) -> Stream<G, D>;
+}
+
+impl<G: Scope, D: ExchangeData> TopK<G, D> for Stream<G, D> {
+    fn top_k<U: Unsigned+'static, F: Fn(&D)->U+Clone+'static>(&self, k: usize, key: F) -> Stream<G, D> {
+        let local = sort_per_time(self, Pipeline, "TopKLocal", key.clone(), Some(k));
+        sort_per_time(&local, Exchange::new(|_: &D| 0), "TopK", key, Some(k))
+    }
+}
+
+/// Sorts the records of each time once complete, producing all of them in increasing order of `key`,
+/// or at most `limit` of them in decreasing order.
+fn sort_per_time<G, D, U, F, P>(stream: &Stream<G, D>, pact: P, name: &str, key: F, limit: Option<usize>) -> Stream<G, D>
+where
+    G: Scope,
+    D: Data,
+    U: Unsigned+'static,
+    F: Fn(&D)->U+'static,
+    P: ParallelizationContract<G::Timestamp, D>,
+{
+    let mut stash = HashMap::new();
+    let mut sorter = LSBRadixSorter::<D>::new();
+    stream.unary_notify(pact, name, vec![], move |input, output, notificator| {
+        input.for_each(|time, data| {
+            stash.entry(time.time().clone())
+                 .or_insert_with(|| { notificator.notify_at(time.retain()); Vec::new() })
+                 .push(data.replace(Vec::new()));
+        });
+
+        notificator.for_each(|time, _, _| {
+            if let Some(batches) = stash.remove(time.time()) {
+                for batch in batches {
+                    sorter.push_batch(batch, &key);
+                }
+                let mut sorted = sorter.finish(&key);
+                let mut session = output.session(&time);
+                match limit {
+                    None => {
+                        for batch in sorted.iter_mut() {
+                            session.give_vec(batch);
+                        }
+                    },
+                    Some(limit) => {
+                        let largest = sorted.iter_mut().rev().flat_map(|batch| batch.drain(..).rev()).take(limit);
+                        session.give_iterator(largest);
+                    },
+                }
+                sorter.recycle(&mut sorted);
+            }
+        });
+    })
+}
@@ -2,9 +2,12 @@
 
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::any::Any;
 
-use crate::communication::{Data, Push, Pull};
+use crate::communication::{Push, Pull};
+use crate::communication::codec::Codec;
 use crate::communication::allocator::thread::{ThreadPusher, ThreadPuller};
+use crate::communication::allocator::zero_copy::compression::Compression;
 use crate::scheduling::Scheduler;
 use crate::scheduling::activate::Activations;
 use crate::progress::{Timestamp, Operate, SubgraphBuilder};
@@ -54,8 +57,11 @@ where
 {
     fn index(&self) -> usize { self.parent.index() }
     fn peers(&self) -> usize { self.parent.peers() }
-    fn allocate<D: Data>(&mut self, identifier: usize, address: &[usize]) -> (Vec<Box<dyn Push<Message<D>>>>, Box<dyn Pull<Message<D>>>) {
-        self.parent.allocate(identifier, address)
+    fn allocate_with_codec<D: Send+Sync+Any+'static, C: Codec<D>>(&mut self, identifier: usize, address: &[usize], codec: C) -> (Vec<Box<dyn Push<Message<D>>>>, Box<dyn Pull<Message<D>>>) {
+        self.parent.allocate_with_codec(identifier, address, codec)
+    }
+    fn set_channel_compression(&mut self, identifier: usize, compression: Compression) {
+        self.parent.set_channel_compression(identifier, compression)
     }
     fn pipeline<D: 'static>(&mut self, identifier: usize, address: &[usize]) -> (ThreadPusher<Message<D>>, ThreadPuller<Message<D>>) {
         self.parent.pipeline(identifier, address)
@@ -66,6 +72,9 @@ where
     fn log_register(&self) -> ::std::cell::RefMut<crate::logging_core::Registry<crate::logging::WorkerIdentifier>> {
         self.parent.log_register()
     }
+    fn stats_registry(&self) -> ::std::cell::RefMut<crate::progress::stats::StatsRegistry> {
+        self.parent.stats_registry()
+    }
 }
 
 impl<'a, G, T> Scheduler for Child<'a, G, T>
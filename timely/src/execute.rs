@@ -1,6 +1,7 @@
 //! Starts a timely dataflow execution from configuration information and per-worker logic.
 
 use crate::communication::{initialize_from, Configuration, Allocator, allocator::AllocateBuilder, WorkerGuards};
+use crate::communication::logging::{CommunicationSetup, CommunicationEvent};
 use crate::dataflow::scopes::Child;
 use crate::worker::Worker;
 
@@ -160,35 +161,24 @@ where
     T:Send+'static,
     F: Fn(&mut Worker<Allocator>, *const HardwareCommon)->T+Send+Sync+'static {
 
-    if let Configuration::Cluster { ref mut log_fn, .. } = config {
+    // Metrics aggregate the logs of all threads, if requested.
+    let (metrics, exporter) = match crate::logging::prometheus::Metrics::from_env() {
+        Some((metrics, exporter)) => (Some(metrics), Some(exporter)),
+        None => (None, None),
+    };
+    // The communication metrics of the process are exported once, by the first worker to start.
+    let comm_exported = ::std::sync::atomic::AtomicBool::new(false);
 
-        *log_fn = Box::new(|events_setup| {
-
-            let mut result = None;
-            if let Ok(addr) = ::std::env::var("TIMELY_COMM_LOG_ADDR") {
-
-                use ::std::net::TcpStream;
-                use crate::logging::BatchLogger;
-                use crate::dataflow::operators::capture::EventWriter;
-
-                eprintln!("enabled COMM logging to {}", addr);
-
-                if let Ok(stream) = TcpStream::connect(&addr) {
-                    let writer = EventWriter::new(stream);
-                    let mut logger = BatchLogger::new(writer);
-                    result = Some(crate::logging_core::Logger::new(
-                        ::std::time::Instant::now(),
-                        ::std::time::Duration::default(),
-                        events_setup,
-                        move |time, data| logger.publish_batch(time, data)
-                    ));
-                }
-                else {
-                    panic!("Could not connect to communication log address: {:?}", addr);
-                }
-            }
-            result
-        });
+    // Communication threads of a cluster, and placed worker threads, report to the communication loggers.
+    if let Configuration::Placed { ref mut log_fn, .. } = config {
+        *log_fn = communication_log_fn(metrics.clone());
+    }
+    let cluster = match config {
+        Configuration::Placed { ref mut config, .. } => &mut **config,
+        ref mut other => other,
+    };
+    if let Configuration::Cluster { ref mut log_fn, .. } = cluster {
+        *log_fn = communication_log_fn(metrics.clone());
     }
 
     let (allocators, other) = config.try_build()?;
@@ -209,16 +199,46 @@ where
             use crate::logging::{BatchLogger, TimelyEvent};
             use crate::dataflow::operators::capture::EventWriter;
 
-            if let Ok(stream) = TcpStream::connect(&addr) {
-                let writer = EventWriter::new(stream);
-                let mut logger = BatchLogger::new(writer);
-                worker.log_register()
-                    .insert::<TimelyEvent,_>("timely", move |time, data|
-                        logger.publish_batch(time, data)
-                    );
+            match TcpStream::connect(&addr) {
+                Ok(stream) => {
+                    let writer = EventWriter::new(stream);
+                    let mut logger = BatchLogger::new(writer);
+                    worker.log_register()
+                        .insert::<TimelyEvent,_>("timely", move |time, data|
+                            logger.publish_batch(time, data)
+                        );
+                },
+                Err(error) => {
+                    eprintln!("timely: could not connect logging stream to {:?}: {}; logging disabled", addr, error);
+                },
+            }
+        }
+        else if let Ok(dir) = ::std::env::var("TIMELY_WORKER_LOG_DIR") {
+
+            use crate::logging::{BatchLogger, TimelyEvent};
+            use crate::logging::file::{FileWriter, Rotation};
+
+            let name = format!("worker-{}", worker.index());
+            match FileWriter::new(&dir, &name, Rotation::from_env()) {
+                Ok(writer) => {
+                    let mut logger = BatchLogger::new(writer);
+                    worker.log_register()
+                        .insert::<TimelyEvent,_>("timely", move |time, data|
+                            logger.publish_batch(time, data)
+                        );
+                },
+                Err(error) => {
+                    eprintln!("timely: could not create worker log {:?} in {:?}: {}; logging disabled", name, dir, error);
+                },
             }
-            else {
-                panic!("Could not connect logging stream to: {:?}", addr);
+        }
+        else if let Some(metrics) = metrics.as_ref() {
+            worker.log_register()
+                .insert::<crate::logging::TimelyEvent,_>("timely", metrics.timely());
+        }
+        if let (Some(exporter), Some(comm_metrics)) = (exporter.as_ref(), worker.metrics()) {
+            if !comm_exported.swap(true, ::std::sync::atomic::Ordering::SeqCst) {
+                comm_metrics.register(exporter);
             }
         }
 
@@ -255,8 +275,16 @@ where
 /// `-p, --process`: identity of this process; from 0 to n-1.
 ///
 /// `-h, --hostfile`: a text file whose lines are "hostname:port" in order of process identity.
-/// If not specified, `localhost` will be used, with port numbers increasing from 2101 (chosen
-/// arbitrarily).
+/// Each line may be followed by the number of worker threads of that process (e.g. "host:2101 64"),
+/// which otherwise defaults to the `-w` value. If not specified, `localhost` will be used, with port
+/// numbers increasing from 2101 (chosen arbitrarily).
+///
+/// `--compression`: codec for large payloads sent between processes: `none`, `lz4`, or `zstd[:LEVEL]`.
+///
+/// `--compression-threshold`: serialized size in bytes at which payloads are compressed (default 4096).
+///
+/// `--placement`: cores to pin worker threads and then communication threads to, as a list such as
+/// `0-7,16-23`, or `numa` to spread threads across the NUMA nodes of the machine.
 ///
 /// # Examples
 ///
@@ -320,3 +348,75 @@ where
         result
     })
 }
+
+/// Constructs loggers for communication events, as directed by environment variables.
+///
+/// Events are written to `TIMELY_COMM_LOG_ADDR` or `TIMELY_COMM_LOG_DIR`, if set, and otherwise aggregated into `metrics`.
+fn communication_log_fn(metrics: Option<crate::logging::prometheus::Metrics>)
+    -> Box<dyn Fn(CommunicationSetup)->Option<crate::logging_core::Logger<CommunicationEvent, CommunicationSetup>>+Send+Sync>
+{
+    Box::new(move |events_setup| {
+        let mut result = None;
+        if let Ok(addr) = ::std::env::var("TIMELY_COMM_LOG_ADDR") {
+
+            use ::std::net::TcpStream;
+            use crate::logging::BatchLogger;
+            use crate::dataflow::operators::capture::EventWriter;
+
+            match TcpStream::connect(&addr) {
+                Ok(stream) => {
+                    eprintln!("enabled COMM logging to {}", addr);
+                    let writer = EventWriter::new(stream);
+                    let mut logger = BatchLogger::new(writer);
+                    result = Some(crate::logging_core::Logger::new(
+                        ::std::time::Instant::now(),
+                        ::std::time::Duration::default(),
+                        events_setup,
+                        move |time, data| logger.publish_batch(time, data)
+                    ));
+                },
+                Err(error) => {
+                    eprintln!("timely: could not connect to communication log address {:?}: {}; logging disabled", addr, error);
+                },
+            }
+        }
+        else if let Ok(dir) = ::std::env::var("TIMELY_COMM_LOG_DIR") {
+
+            use crate::logging::BatchLogger;
+            use crate::logging::file::{FileWriter, Rotation};
+
+            let name = match events_setup.worker {
+                Some(worker) => format!("communication-{}-worker-{}", events_setup.process, worker),
+                None => format!(
+                    "communication-{}-{}-{}",
+                    events_setup.process,
+                    events_setup.remote.map(|remote| remote.to_string()).unwrap_or_else(|| "local".to_owned()),
+                    if events_setup.sender { "send" } else { "recv" },
+                ),
+            };
+            match FileWriter::new(&dir, &name, Rotation::from_env()) {
+                Ok(writer) => {
+                    let mut logger = BatchLogger::new(writer);
+                    result = Some(crate::logging_core::Logger::new(
+                        ::std::time::Instant::now(),
+                        ::std::time::Duration::default(),
+                        events_setup,
+                        move |time, data| logger.publish_batch(time, data)
+                    ));
+                },
+                Err(error) => {
+                    eprintln!("timely: could not create communication log {:?} in {:?}: {}; logging disabled", name, dir, error);
+                },
+            }
+        }
+        else if let Some(metrics) = metrics.as_ref() {
+            result = Some(crate::logging_core::Logger::new(
+                ::std::time::Instant::now(),
+                ::std::time::Duration::default(),
+                events_setup,
+                metrics.communication()
+            ));
+        }
+        result
+    })
+}
@@ -66,6 +66,8 @@ extern crate serde_derive;
 extern crate timely_communication;
 extern crate timely_bytes;
 extern crate timely_logging;
+extern crate timely_sort;
+extern crate bincode;
 
 pub use execute::{execute, execute_directly, execute_from_args, example};
 pub use order::PartialOrder;
@@ -7,6 +7,12 @@ pub type Logger<Event> = crate::logging_core::Logger<Event, WorkerIdentifier>;
 /// Logger for timely dataflow system events.
 pub type TimelyLogger = Logger<TimelyEvent>;
 
+pub mod graph;
+pub mod trace;
+pub mod file;
+pub mod prometheus;
+pub mod filter;
+
 use std::time::Duration;
 use crate::dataflow::operators::capture::{Event, EventPusher};
 
@@ -218,6 +224,15 @@ impl ParkEvent {
     pub fn unpark() -> Self { ParkEvent::Unpark }
 }
 
+/// Reports that no frontier of the worker's dataflows has advanced for a while.
+#[derive(Serialize, Deserialize, Abomonation, Debug, Clone, Hash, PartialEq, Eq, Ord, PartialOrd)]
+pub struct StallEvent {
+    /// How long the frontiers have not advanced for.
+    pub elapsed: Duration,
+    /// Minimal outstanding pointstamps, as (operator address, operator name, port, time, count).
+    pub pointstamps: Vec<(Vec<usize>, String, crate::progress::Port, String, i64)>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Abomonation, Hash, Eq, PartialEq, Ord, PartialOrd)]
 /// An event in a timely worker
 pub enum TimelyEvent {
@@ -249,6 +264,37 @@ pub enum TimelyEvent {
     Park(ParkEvent),
     /// Unstructured event.
     Text(String),
+    /// Stalled frontiers.
+    Stall(StallEvent),
+}
+
+impl TimelyEvent {
+    /// The names of the event variants, as reported by `kind`.
+    pub const KINDS: &'static [&'static str] = &[
+        "Operates", "Channels", "Progress", "PushProgress", "Messages", "Schedule", "Shutdown", "Application",
+        "GuardedMessage", "GuardedProgress", "CommChannels", "Input", "Park", "Text", "Stall",
+    ];
+
+    /// The name of the variant of the event.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TimelyEvent::Operates(_) => "Operates",
+            TimelyEvent::Channels(_) => "Channels",
+            TimelyEvent::Progress(_) => "Progress",
+            TimelyEvent::PushProgress(_) => "PushProgress",
+            TimelyEvent::Messages(_) => "Messages",
+            TimelyEvent::Schedule(_) => "Schedule",
+            TimelyEvent::Shutdown(_) => "Shutdown",
+            TimelyEvent::Application(_) => "Application",
+            TimelyEvent::GuardedMessage(_) => "GuardedMessage",
+            TimelyEvent::GuardedProgress(_) => "GuardedProgress",
+            TimelyEvent::CommChannels(_) => "CommChannels",
+            TimelyEvent::Input(_) => "Input",
+            TimelyEvent::Park(_) => "Park",
+            TimelyEvent::Text(_) => "Text",
+            TimelyEvent::Stall(_) => "Stall",
+        }
+    }
 }
 
 impl From<OperatesEvent> for TimelyEvent {
@@ -302,3 +348,7 @@ impl From<InputEvent> for TimelyEvent {
 impl From<ParkEvent> for TimelyEvent {
     fn from(v: ParkEvent) -> TimelyEvent { TimelyEvent::Park(v) }
 }
+
+impl From<StallEvent> for TimelyEvent {
+    fn from(v: StallEvent) -> TimelyEvent { TimelyEvent::Stall(v) }
+}
@@ -0,0 +1,241 @@
+//! Logging to files in a directory, and reading the logs back.
+//!
+//! A `FileWriter` is an event pusher for `BatchLogger` that writes a log to a sequence of files,
+//! `<name>.<part>.log`, starting a new part when the current one reaches a size or an age. The
+//! parts of a log are read back in order as one stream of events, with `open_log`, or with
+//! `open_worker_logs` for the worker logs that `execute` writes when `TIMELY_WORKER_LOG_DIR` is set.
+//!
+//! A `FileWriter` that fails to write warns once and drops all further events, rather than
+//! interrupting the computation it logs.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::collections::VecDeque;
+
+use abomonation::Abomonation;
+
+use crate::dataflow::operators::capture::{Event, EventPusher, EventReader};
+use crate::logging::{TimelyEvent, WorkerIdentifier};
+
+/// When a `FileWriter` starts a new part of its log.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rotation {
+    /// Starts a new part once the current part holds at least this many bytes.
+    pub max_bytes: Option<u64>,
+    /// Starts a new part once the current part has been written to for at least this long.
+    pub max_age: Option<Duration>,
+}
+
+impl Rotation {
+    /// Rotation that keeps the whole log in one part.
+    pub fn never() -> Self {
+        Self::default()
+    }
+    /// Starts a new part once the current part holds at least `max_bytes` bytes.
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+    /// Starts a new part once the current part has been written to for at least `max_age`.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+    /// Reads rotation from the `TIMELY_LOG_ROTATE_BYTES` and `TIMELY_LOG_ROTATE_SECS` environment
+    /// variables, ignoring values that do not parse.
+    pub fn from_env() -> Self {
+        let variable = |name| ::std::env::var(name).ok().and_then(|value| value.parse::<u64>().ok());
+        Rotation {
+            max_bytes: variable("TIMELY_LOG_ROTATE_BYTES"),
+            max_age: variable("TIMELY_LOG_ROTATE_SECS").map(Duration::from_secs),
+        }
+    }
+}
+
+/// Writes events to the parts of a log in a directory.
+///
+/// # Examples
+/// ```
+/// use timely::logging::{BatchLogger, TimelyEvent};
+/// use timely::logging::file::{FileWriter, Rotation};
+///
+/// timely::execute_from_args(std::env::args(), |worker, _| {
+///     let dir = std::env::temp_dir().join("timely-logs");
+///     let name = format!("worker-{}", worker.index());
+///     let writer = FileWriter::new(&dir, &name, Rotation::never().max_bytes(1 << 26)).unwrap();
+///     let mut logger = BatchLogger::new(writer);
+///     worker.log_register().insert::<TimelyEvent,_>("timely", move |time, data| {
+///         logger.publish_batch(time, data)
+///     });
+/// }).unwrap();
+/// ```
+pub struct FileWriter<T, D> {
+    dir: PathBuf,
+    name: String,
+    rotation: Rotation,
+    part: usize,
+    /// The current part, or `None` once writing has failed.
+    file: Option<File>,
+    bytes: u64,
+    opened: Instant,
+    buffer: Vec<u8>,
+    phant: ::std::marker::PhantomData<(T, D)>,
+}
+
+impl<T, D> FileWriter<T, D> {
+    /// Creates the first part of the log `name` in `dir`, creating `dir` if needed.
+    pub fn new<P: AsRef<Path>>(dir: P, name: &str, rotation: Rotation) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let file = File::create(part_path(&dir, name, 0))?;
+        Ok(FileWriter {
+            dir,
+            name: name.to_owned(),
+            rotation,
+            part: 0,
+            file: Some(file),
+            bytes: 0,
+            opened: Instant::now(),
+            buffer: Vec::new(),
+            phant: ::std::marker::PhantomData,
+        })
+    }
+
+    /// Starts the next part if the current part is due for rotation.
+    fn rotate(&mut self) -> io::Result<()> {
+        let full = self.rotation.max_bytes.map(|max| self.bytes >= max).unwrap_or(false);
+        let old = self.rotation.max_age.map(|max| self.opened.elapsed() >= max).unwrap_or(false);
+        if self.bytes > 0 && (full || old) {
+            self.part += 1;
+            self.file = Some(File::create(part_path(&self.dir, &self.name, self.part))?);
+            self.bytes = 0;
+            self.opened = Instant::now();
+        }
+        Ok(())
+    }
+}
+
+impl<T: Abomonation, D: Abomonation> EventPusher<T, D> for FileWriter<T, D> {
+    fn push(&mut self, event: Event<T, D>) {
+        if self.file.is_none() {
+            return;
+        }
+        self.buffer.clear();
+        unsafe { ::abomonation::encode(&event, &mut self.buffer).expect("Event abomonation failed"); }
+        // Parts end at event boundaries, so that they can be read back one after the other.
+        let result = self.rotate().and_then(|()| self.file.as_mut().unwrap().write_all(&self.buffer));
+        match result {
+            Ok(()) => self.bytes += self.buffer.len() as u64,
+            Err(error) => {
+                eprintln!("timely: failed to write log {:?} in {:?}: {}; logging disabled", self.name, self.dir, error);
+                self.file = None;
+            },
+        }
+    }
+}
+
+/// Reads the parts of a log one after the other.
+pub struct LogParts {
+    parts: VecDeque<PathBuf>,
+    current: Option<File>,
+}
+
+impl Read for LogParts {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(file) = self.current.as_mut() {
+                let read = file.read(buf)?;
+                if read > 0 || buf.is_empty() {
+                    return Ok(read);
+                }
+            }
+            match self.parts.pop_front() {
+                Some(path) => { self.current = Some(File::open(path)?); },
+                None => { self.current = None; return Ok(0); },
+            }
+        }
+    }
+}
+
+/// The names of the logs in `dir`, in order, with the paths of their parts in order.
+pub fn log_names<P: AsRef<Path>>(dir: P) -> io::Result<Vec<(String, Vec<PathBuf>)>> {
+    let mut logs = ::std::collections::BTreeMap::<String, Vec<(usize, PathBuf)>>::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let file_name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(file_name) => file_name.to_owned(),
+            None => continue,
+        };
+        if let Some(stem) = file_name.strip_suffix(".log") {
+            if let Some(dot) = stem.rfind('.') {
+                if let Ok(part) = stem[dot + 1 ..].parse::<usize>() {
+                    logs.entry(stem[.. dot].to_owned()).or_insert_with(Vec::new).push((part, path));
+                }
+            }
+        }
+    }
+    Ok(logs.into_iter().map(|(name, mut parts)| {
+        parts.sort();
+        (name, parts.into_iter().map(|(_, path)| path).collect())
+    }).collect())
+}
+
+/// Opens the log `name` in `dir` as an event iterator over all of its parts.
+pub fn open_log<T, D, P: AsRef<Path>>(dir: P, name: &str) -> io::Result<EventReader<T, D, LogParts>> {
+    let parts = log_names(dir)?
+        .into_iter()
+        .find(|(log, _)| log == name)
+        .map(|(_, parts)| parts)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no log named {:?}", name)))?;
+    Ok(EventReader::new(LogParts { parts: parts.into(), current: None }))
+}
+
+/// Opens the worker logs in `dir`, as written by `execute` with `TIMELY_WORKER_LOG_DIR`, in order of
+/// worker index.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use timely::dataflow::operators::Inspect;
+/// use timely::dataflow::operators::capture::Replay;
+/// use timely::logging::{BatchLogger, TimelyEvent};
+/// use timely::logging::file::{open_worker_logs, FileWriter, Rotation};
+///
+/// // Write a log for replay, as `execute` would with `TIMELY_WORKER_LOG_DIR` set.
+/// let dir = std::env::temp_dir().join(format!("timely-logs-{}", std::process::id()));
+/// let writer = FileWriter::new(&dir, "worker-0", Rotation::never()).unwrap();
+/// let mut logger = BatchLogger::new(writer);
+/// logger.publish_batch(&Duration::from_secs(30), &mut vec![(Duration::from_secs(15), 0, TimelyEvent::Text("hello".to_owned()))]);
+/// drop(logger);
+///
+/// let logs_dir = dir.clone();
+/// timely::execute_from_args(std::env::args(), move |worker, _| {
+///     let (index, peers) = (worker.index(), worker.peers());
+///     let logs = open_worker_logs(&logs_dir).unwrap();
+///     let replayers = logs.into_iter().enumerate().filter(|(i, _)| i % peers == index).map(|(_, log)| log);
+///     worker.dataflow(|scope| {
+///         replayers
+///             .replay_into(scope)
+///             .inspect(|x| println!("replayed: {:?}", x));
+///     });
+/// }).unwrap();
+///
+/// std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn open_worker_logs<P: AsRef<Path>>(dir: P) -> io::Result<Vec<EventReader<Duration, (Duration, WorkerIdentifier, TimelyEvent), LogParts>>> {
+    let mut workers = log_names(dir)?
+        .into_iter()
+        .filter_map(|(name, parts)| {
+            let index = name.strip_prefix("worker-")?.parse::<usize>().ok()?;
+            Some((index, parts))
+        })
+        .collect::<Vec<_>>();
+    workers.sort_by_key(|(index, _)| *index);
+    Ok(workers.into_iter().map(|(_, parts)| EventReader::new(LogParts { parts: parts.into(), current: None })).collect())
+}
+
+fn part_path(dir: &Path, name: &str, part: usize) -> PathBuf {
+    dir.join(format!("{}.{:05}.log", name, part))
+}
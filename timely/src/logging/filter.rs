@@ -0,0 +1,229 @@
+//! Filtering and sampling of timely logging events before they are buffered.
+//!
+//! A `TimelyFilter` decides which `TimelyEvent`s a logger keeps, by event variant, by the address of
+//! the operator or scope an event concerns, and by channel, and keeps a random sample of the events
+//! of high-rate variants. It is installed with `Registry::filter`, so that dropped events are never
+//! buffered nor passed to the logging action.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::logging::{StartStop, TimelyEvent};
+
+/// Decides which timely logging events to keep.
+///
+/// # Examples
+/// ```
+/// use timely::logging::{MessagesEvent, OperatesEvent, ScheduleEvent, TimelyEvent};
+/// use timely::logging::filter::TimelyFilter;
+///
+/// let mut filter = TimelyFilter::new()
+///     .except(&["GuardedMessage", "GuardedProgress"])
+///     .within(&[0, 2])
+///     .sample("Schedule", 0.5);
+///
+/// let inside = OperatesEvent { id: 7, addr: vec![0, 2, 1], name: "Map".to_owned() };
+/// let outside = OperatesEvent { id: 8, addr: vec![0, 3], name: "Probe".to_owned() };
+/// assert!(filter.keep(&TimelyEvent::Operates(inside)));
+/// assert!(!filter.keep(&TimelyEvent::Operates(outside)));
+/// assert!(!filter.keep(&TimelyEvent::Schedule(ScheduleEvent::start(8))));
+///
+/// // Sampled schedules keep or drop their start and stop events together.
+/// let kept = (0 .. 100).filter(|_| {
+///     let start = filter.keep(&TimelyEvent::Schedule(ScheduleEvent::start(7)));
+///     let stop = filter.keep(&TimelyEvent::Schedule(ScheduleEvent::stop(7)));
+///     assert_eq!(start, stop);
+///     start
+/// }).count();
+/// assert!(0 < kept && kept < 100);
+/// ```
+///
+/// To filter the events of a worker's "timely" logger:
+/// ```
+/// use timely::logging::TimelyEvent;
+/// use timely::logging::filter::TimelyFilter;
+///
+/// timely::execute_from_args(std::env::args(), |worker, _| {
+///     worker.log_register().insert::<TimelyEvent,_>("timely", |_time, data| {
+///         for event in data.iter() { println!("{:?}", event); }
+///     });
+///     let mut filter = TimelyFilter::new().sample("Messages", 0.01);
+///     worker.log_register().filter::<TimelyEvent,_>("timely", move |event| filter.keep(event));
+/// }).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimelyFilter {
+    /// Variants to keep, or `None` for all.
+    kinds: Option<HashSet<&'static str>>,
+    /// Address prefix of the operators and scopes to keep events of, if any.
+    prefix: Option<Vec<usize>>,
+    /// Channels to keep events of, if not all.
+    channels: Option<HashSet<usize>>,
+    /// Fractions of events to keep, by variant.
+    rates: HashMap<&'static str, f64>,
+    /// State of the random number generator.
+    random: u64,
+    /// Identifiers of the operators within `prefix`.
+    operators: HashSet<usize>,
+    /// Identifiers of the channels within `prefix`.
+    scoped_channels: HashSet<usize>,
+    /// Sampling decisions for started activities, by variant and identifier, for their stops.
+    started: HashMap<(&'static str, usize), bool>,
+}
+
+impl TimelyFilter {
+    /// A filter that keeps all events.
+    pub fn new() -> Self {
+        TimelyFilter {
+            kinds: None,
+            prefix: None,
+            channels: None,
+            rates: HashMap::new(),
+            random: 0x9E37_79B9_7F4A_7C15,
+            operators: HashSet::new(),
+            scoped_channels: HashSet::new(),
+            started: HashMap::new(),
+        }
+    }
+
+    /// Keeps only events of the variants named in `kinds`, as listed in `TimelyEvent::KINDS`.
+    pub fn only(mut self, kinds: &[&str]) -> Self {
+        self.kinds = Some(kinds.iter().map(|kind| variant(kind)).collect());
+        self
+    }
+
+    /// Drops events of the variants named in `kinds`, as listed in `TimelyEvent::KINDS`.
+    pub fn except(mut self, kinds: &[&str]) -> Self {
+        let mut keep = self.kinds.take().unwrap_or_else(|| TimelyEvent::KINDS.iter().cloned().collect());
+        for kind in kinds.iter() {
+            keep.remove(variant(kind));
+        }
+        self.kinds = Some(keep);
+        self
+    }
+
+    /// Keeps only events about operators, channels and scopes whose address starts with `prefix`.
+    ///
+    /// Operators are recognized by their `Operates` events, and channels by their `Channels`
+    /// events, so the filter must be installed before the dataflows to observe are built. Events
+    /// not about an operator, channel or scope are kept.
+    pub fn within(mut self, prefix: &[usize]) -> Self {
+        self.prefix = Some(prefix.to_vec());
+        self
+    }
+
+    /// Keeps only `Channels` and `Messages` events about the channels identified by `channels`.
+    pub fn channels<I: IntoIterator<Item=usize>>(mut self, channels: I) -> Self {
+        self.channels = Some(channels.into_iter().collect());
+        self
+    }
+
+    /// Keeps a random fraction `rate` of the events of the variant `kind`.
+    ///
+    /// Events that start an activity, like the scheduling of an operator, are sampled, and the
+    /// events that stop the activity follow the decision.
+    pub fn sample(mut self, kind: &str, rate: f64) -> Self {
+        self.rates.insert(variant(kind), rate);
+        self
+    }
+
+    /// Seeds the random choices of `sample`, which are otherwise the same for every filter.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.random = seed.max(1);
+        self
+    }
+
+    /// Indicates whether to keep `event`.
+    pub fn keep(&mut self, event: &TimelyEvent) -> bool {
+
+        // Track operators and channels within the prefix, even if their events are dropped.
+        if let Some(prefix) = &self.prefix {
+            match event {
+                TimelyEvent::Operates(operates) if operates.addr.starts_with(prefix) => {
+                    self.operators.insert(operates.id);
+                },
+                TimelyEvent::Channels(channels) if channels.scope_addr.starts_with(prefix) => {
+                    self.scoped_channels.insert(channels.id);
+                },
+                _ => { },
+            }
+        }
+
+        let kind = event.kind();
+        if let Some(kinds) = &self.kinds {
+            if !kinds.contains(kind) { return false; }
+        }
+
+        if let Some(prefix) = &self.prefix {
+            let within = match event {
+                TimelyEvent::Operates(operates) => operates.addr.starts_with(prefix),
+                TimelyEvent::Channels(channels) => channels.scope_addr.starts_with(prefix),
+                TimelyEvent::Progress(progress) => progress.addr.starts_with(prefix),
+                TimelyEvent::Messages(messages) => self.scoped_channels.contains(&messages.channel),
+                TimelyEvent::Schedule(schedule) => self.operators.contains(&schedule.id),
+                TimelyEvent::Shutdown(shutdown) => self.operators.contains(&shutdown.id),
+                TimelyEvent::PushProgress(push) => self.operators.contains(&push.op_id),
+                TimelyEvent::Stall(stall) => stall.pointstamps.iter().any(|(addr, _, _, _, _)| addr.starts_with(prefix)),
+                _ => true,
+            };
+            if !within { return false; }
+        }
+
+        if let Some(channels) = &self.channels {
+            let channel = match event {
+                TimelyEvent::Channels(channels) => Some(channels.id),
+                TimelyEvent::Messages(messages) => Some(messages.channel),
+                _ => None,
+            };
+            if channel.map(|channel| !channels.contains(&channel)).unwrap_or(false) { return false; }
+        }
+
+        match self.rates.get(kind).cloned() {
+            Some(rate) => {
+                match activity(event) {
+                    Some((id, false)) => self.started.remove(&(kind, id)).unwrap_or(false),
+                    Some((id, true)) => {
+                        let keep = self.random() < rate;
+                        self.started.insert((kind, id), keep);
+                        keep
+                    },
+                    None => self.random() < rate,
+                }
+            },
+            None => true,
+        }
+    }
+
+    /// A uniformly random number in [0, 1), from an xorshift generator.
+    fn random(&mut self) -> f64 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+        (self.random >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for TimelyFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The variant named `kind`, which must be listed in `TimelyEvent::KINDS`.
+fn variant(kind: &str) -> &'static str {
+    TimelyEvent::KINDS
+        .iter()
+        .find(|known| **known == kind)
+        .unwrap_or_else(|| panic!("unknown timely event variant: {:?}", kind))
+}
+
+/// For events that start or stop an activity, an identifier of the activity and whether it starts.
+fn activity(event: &TimelyEvent) -> Option<(usize, bool)> {
+    match event {
+        TimelyEvent::Schedule(schedule) => Some((schedule.id, schedule.start_stop == StartStop::Start)),
+        TimelyEvent::Application(application) => Some((application.id, application.is_start)),
+        TimelyEvent::GuardedMessage(guarded) => Some((0, guarded.is_start)),
+        TimelyEvent::GuardedProgress(guarded) => Some((0, guarded.is_start)),
+        TimelyEvent::Input(input) => Some((0, input.start_stop == StartStop::Start)),
+        _ => None,
+    }
+}
@@ -0,0 +1,481 @@
+//! Export of the dataflow graph described by timely logging events.
+//!
+//! A `DataflowGraph` collects the operators and channels announced by `OperatesEvent` and
+//! `ChannelsEvent`, annotated with the scheduling and message events that follow them. It can be
+//! built from a captured log, or from a live worker, and rendered as Graphviz DOT or as JSON.
+//!
+//! Operators are drawn inside clusters for their enclosing scopes. The ghost operators that an FPGA
+//! wrapper registers in place of the operators it runs on the device are drawn together with their
+//! wrapper, in one cluster.
+
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::io::Read;
+use std::fmt::Write;
+use std::time::Duration;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::communication::Allocate;
+use crate::worker::Worker;
+use crate::dataflow::operators::capture::{EventReader, Event};
+use crate::dataflow::operators::capture::event::EventIterator;
+use crate::logging::{TimelyEvent, StartStop, WorkerIdentifier};
+
+/// The name of the operator that runs a dataflow fragment on the FPGA.
+const WRAPPER_NAME: &str = "Wrapper";
+
+/// An operator of a dataflow graph.
+#[derive(Debug, Clone)]
+pub struct OperatorNode {
+    /// Worker-unique identifier for the operator.
+    pub id: usize,
+    /// Sequence of scope identifiers from the root to the operator.
+    ///
+    /// For the ghost operators of an FPGA wrapper, this is the address of the enclosing scope.
+    pub addr: Vec<usize>,
+    /// The name of the operator.
+    pub name: String,
+    /// The FPGA wrapper this operator is a ghost operator of, if any.
+    pub ghost_of: Option<usize>,
+    /// The ghost operators of this operator, if it is an FPGA wrapper.
+    pub ghosts: Vec<usize>,
+    /// The number of times the operator was scheduled, across all workers.
+    pub schedule_count: usize,
+    /// The total time the operator was scheduled for, across all workers.
+    pub schedule_time: Duration,
+    /// The number of records sent on the operator's output channels.
+    pub records_sent: usize,
+    /// The number of records received on the operator's input channels.
+    pub records_received: usize,
+}
+
+/// A channel between two operators of a scope.
+#[derive(Debug, Clone)]
+pub struct ChannelEdge {
+    /// Worker-unique identifier for the channel.
+    pub id: usize,
+    /// Address of the scope containing the channel.
+    pub scope_addr: Vec<usize>,
+    /// Source operator index within the scope and output port; index zero is the scope's input.
+    pub source: (usize, usize),
+    /// Target operator index within the scope and input port; index zero is the scope's output.
+    pub target: (usize, usize),
+    /// The number of records sent on the channel, across all workers.
+    pub records_sent: usize,
+    /// The number of records received from the channel, across all workers.
+    pub records_received: usize,
+}
+
+/// The operators and channels of the dataflows described by timely logging events.
+///
+/// Workers construct the same dataflows with the same identifiers, so the structure is taken from
+/// the first worker to describe it, and statistics are summed over all workers.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use timely::logging::{TimelyEvent, OperatesEvent, ChannelsEvent};
+/// use timely::logging::graph::DataflowGraph;
+///
+/// let mut graph = DataflowGraph::new();
+/// graph.observe(Duration::from_secs(0), 0, &TimelyEvent::Operates(OperatesEvent { id: 1, addr: vec![0, 1], name: "Input".to_owned() }));
+/// graph.observe(Duration::from_secs(0), 0, &TimelyEvent::Operates(OperatesEvent { id: 2, addr: vec![0, 2], name: "Inspect".to_owned() }));
+/// graph.observe(Duration::from_secs(0), 0, &TimelyEvent::Operates(OperatesEvent { id: 0, addr: vec![0], name: "Dataflow".to_owned() }));
+/// graph.observe(Duration::from_secs(0), 0, &TimelyEvent::Channels(ChannelsEvent { id: 0, scope_addr: vec![0], source: (1, 0), target: (2, 0) }));
+///
+/// assert_eq!(graph.operators().count(), 3);
+/// assert!(graph.to_dot().contains("op1 -> op2"));
+/// assert!(graph.to_json().starts_with("{\"scopes\":"));
+/// ```
+#[derive(Debug, Default)]
+pub struct DataflowGraph {
+    operators: BTreeMap<usize, OperatorNode>,
+    channels: BTreeMap<usize, ChannelEdge>,
+    scheduled: HashMap<(WorkerIdentifier, usize), Duration>,
+}
+
+impl DataflowGraph {
+    /// Creates an empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a graph from a log captured with `EventWriter`, until the end of `reader`.
+    pub fn from_reader<R: Read>(reader: R) -> Self {
+        let finished = Rc::new(Cell::new(false));
+        let reader = UntilEnd { reader, finished: finished.clone() };
+        let mut events = EventReader::<Duration, (Duration, WorkerIdentifier, TimelyEvent), _>::new(reader);
+        let mut graph = Self::new();
+        loop {
+            match events.next() {
+                Some(Event::Messages(_, data)) => graph.observe_batch(data),
+                Some(Event::Progress(_)) => { },
+                None => if finished.get() { break; },
+            }
+        }
+        graph
+    }
+
+    /// Records the graph of the dataflows `worker` constructs from now on.
+    ///
+    /// This binds a new "timely" logger, which forwards events to the logger it replaces, if any, so
+    /// that sinks installed before, such as those `execute` installs, continue to receive them.
+    /// Install the graph's logger last, as loggers bound later replace it. The graph reflects the
+    /// events the worker has flushed, which it does as it constructs dataflows and as it steps.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Inspect};
+    /// use timely::logging::graph::DataflowGraph;
+    ///
+    /// timely::execute_from_args(std::env::args(), |worker, _| {
+    ///     let graph = DataflowGraph::record(worker);
+    ///     worker.dataflow::<u64,_,_>(|scope| {
+    ///         (0 .. 10).to_stream(scope).inspect(|x| println!("seen: {:?}", x));
+    ///     });
+    ///     while worker.step() { }
+    ///     println!("{}", graph.borrow().to_dot());
+    /// }).unwrap();
+    /// ```
+    pub fn record<A: Allocate>(worker: &mut Worker<A>) -> Rc<RefCell<Self>> {
+        let graph = Rc::new(RefCell::new(Self::new()));
+        let recorder = graph.clone();
+        let previous = worker.log_register().get::<TimelyEvent>("timely");
+        worker.log_register().insert::<TimelyEvent,_>("timely", move |time, data| {
+            recorder.borrow_mut().observe_batch(data);
+            if let Some(previous) = previous.as_ref() {
+                previous.push_batch(time, data);
+            }
+        });
+        graph
+    }
+
+    /// Updates the graph with a batch of logged events.
+    pub fn observe_batch(&mut self, data: &[(Duration, WorkerIdentifier, TimelyEvent)]) {
+        for (time, worker, event) in data.iter() {
+            self.observe(*time, *worker, event);
+        }
+    }
+
+    /// Updates the graph with an event logged by `worker` at `time`.
+    pub fn observe(&mut self, time: Duration, worker: WorkerIdentifier, event: &TimelyEvent) {
+        match event {
+            TimelyEvent::Operates(operates) => {
+                if !self.operators.contains_key(&operates.id) {
+                    let mut node = OperatorNode {
+                        id: operates.id,
+                        addr: operates.addr.clone(),
+                        name: operates.name.clone(),
+                        ghost_of: None,
+                        ghosts: Vec::new(),
+                        schedule_count: 0,
+                        schedule_time: Duration::default(),
+                        records_sent: 0,
+                        records_received: 0,
+                    };
+                    // A wrapper's ghost operators are logged just before it, with the address of the
+                    // scope rather than an address of their own. The scope itself is logged only once
+                    // all of its operators have been.
+                    if node.name == WRAPPER_NAME && !node.addr.is_empty() {
+                        let scope = &node.addr[.. node.addr.len() - 1];
+                        for ghost in self.operators.values_mut() {
+                            if ghost.addr == scope && ghost.ghost_of.is_none() && ghost.ghosts.is_empty() {
+                                ghost.ghost_of = Some(node.id);
+                                node.ghosts.push(ghost.id);
+                            }
+                        }
+                    }
+                    self.operators.insert(node.id, node);
+                }
+            },
+            TimelyEvent::Channels(channels) => {
+                self.channels.entry(channels.id).or_insert_with(|| ChannelEdge {
+                    id: channels.id,
+                    scope_addr: channels.scope_addr.clone(),
+                    source: channels.source,
+                    target: channels.target,
+                    records_sent: 0,
+                    records_received: 0,
+                });
+            },
+            TimelyEvent::Messages(messages) => {
+                if let Some(channel) = self.channels.get_mut(&messages.channel) {
+                    if messages.is_send {
+                        channel.records_sent += messages.length;
+                    }
+                    else {
+                        channel.records_received += messages.length;
+                    }
+                }
+            },
+            TimelyEvent::Schedule(schedule) => {
+                match schedule.start_stop {
+                    StartStop::Start => {
+                        self.scheduled.insert((worker, schedule.id), time);
+                    },
+                    StartStop::Stop => {
+                        if let Some(start) = self.scheduled.remove(&(worker, schedule.id)) {
+                            if let Some(operator) = self.operators.get_mut(&schedule.id) {
+                                operator.schedule_count += 1;
+                                operator.schedule_time += time.checked_sub(start).unwrap_or_default();
+                            }
+                        }
+                    },
+                }
+            },
+            _ => { },
+        }
+    }
+
+    /// The operators of the graph, in order of identifier, with their statistics.
+    pub fn operators(&self) -> impl Iterator<Item=OperatorNode>+'_ {
+        let (sent, received) = self.operator_records();
+        self.operators.values().map(move |operator| {
+            let mut operator = operator.clone();
+            operator.records_sent = sent.get(&operator.id).cloned().unwrap_or(0);
+            operator.records_received = received.get(&operator.id).cloned().unwrap_or(0);
+            operator
+        })
+    }
+
+    /// The channels of the graph, in order of identifier.
+    pub fn channels(&self) -> impl Iterator<Item=&ChannelEdge> {
+        self.channels.values()
+    }
+
+    /// Renders the graph in the Graphviz DOT language.
+    ///
+    /// Operators are labeled with their schedule counts and times and the records they sent and
+    /// received, and channels with the records sent on them.
+    pub fn to_dot(&self) -> String {
+        let layout = Layout::new(self);
+        let mut dot = String::new();
+        writeln!(dot, "digraph dataflow {{").unwrap();
+        writeln!(dot, "  node [shape=box];").unwrap();
+        self.write_dot_scope(&layout, &[], 1, &mut dot);
+        for channel in self.channels.values() {
+            if let (Some(source), Some(target)) = (layout.endpoint(&channel.scope_addr, channel.source.0), layout.endpoint(&channel.scope_addr, channel.target.0)) {
+                writeln!(dot, "  {} -> {} [label=\"{}\"];", source, target, channel.records_sent).unwrap();
+            }
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+    fn write_dot_scope(&self, layout: &Layout, addr: &[usize], depth: usize, dot: &mut String) {
+        let indent = "  ".repeat(depth);
+        // Channels to and from a scope connect to a point standing for its inputs and outputs.
+        if let Some(id) = layout.by_addr.get(addr) {
+            writeln!(dot, "{}op{}_ports [shape=point];", indent, id).unwrap();
+        }
+        for operator in layout.operators_in(addr) {
+            if layout.scopes.contains_key(&operator.addr) {
+                continue;
+            }
+            if operator.ghosts.is_empty() {
+                writeln!(dot, "{}op{} [label=\"{}\"];", indent, operator.id, dot_label(operator)).unwrap();
+            }
+            else {
+                writeln!(dot, "{}subgraph cluster_fpga{} {{", indent, operator.id).unwrap();
+                writeln!(dot, "{}  label=\"FPGA\"; style=filled; fillcolor=lightgrey;", indent).unwrap();
+                writeln!(dot, "{}  op{} [label=\"{}\"];", indent, operator.id, dot_label(operator)).unwrap();
+                for ghost in operator.ghosts.iter().filter_map(|id| layout.operators.get(id)) {
+                    writeln!(dot, "{}  op{} [label=\"{}\", style=dashed];", indent, ghost.id, dot_label(ghost)).unwrap();
+                }
+                writeln!(dot, "{}}}", indent).unwrap();
+            }
+        }
+        for scope in layout.scopes_in(addr) {
+            let label = match layout.by_addr.get(scope).and_then(|id| layout.operators.get(id)) {
+                Some(operator) => dot_label(operator),
+                None => format!("{:?}", scope),
+            };
+            writeln!(dot, "{}subgraph cluster_{} {{", indent, join(scope, "_")).unwrap();
+            writeln!(dot, "{}  label=\"{}\";", indent, label).unwrap();
+            self.write_dot_scope(layout, scope, depth + 1, dot);
+            writeln!(dot, "{}}}", indent).unwrap();
+        }
+    }
+
+    /// Renders the graph as JSON.
+    ///
+    /// The document has a list of top-level `scopes`. Each scope has its address, the `operator`
+    /// implementing it if known, and lists of its `operators`, `channels`, `fpga_groups` of wrappers
+    /// and their ghost operators, and nested `scopes`. Schedule times are in nanoseconds.
+    pub fn to_json(&self) -> String {
+        let layout = Layout::new(self);
+        let mut json = String::new();
+        self.write_json_scopes(&layout, &[], &mut json);
+        format!("{{\"scopes\":{}}}", json)
+    }
+
+    fn write_json_scopes(&self, layout: &Layout, addr: &[usize], json: &mut String) {
+        json.push('[');
+        for (index, scope) in layout.scopes_in(addr).enumerate() {
+            if index > 0 { json.push(','); }
+            write!(json, "{{\"addr\":[{}],\"operator\":", join(scope, ",")).unwrap();
+            match layout.by_addr.get(scope).and_then(|id| layout.operators.get(id)) {
+                Some(operator) => json_operator(operator, json),
+                None => json.push_str("null"),
+            }
+
+            json.push_str(",\"operators\":[");
+            let operators = layout.operators_in(scope).filter(|operator| !layout.scopes.contains_key(&operator.addr));
+            let operators = operators.flat_map(|operator| Some(operator.id).into_iter().chain(operator.ghosts.iter().cloned()));
+            for (index, operator) in operators.filter_map(|id| layout.operators.get(&id)).enumerate() {
+                if index > 0 { json.push(','); }
+                json_operator(operator, json);
+            }
+
+            json.push_str("],\"channels\":[");
+            let channels = self.channels.values().filter(|channel| channel.scope_addr.as_slice() == scope.as_slice());
+            for (index, channel) in channels.enumerate() {
+                if index > 0 { json.push(','); }
+                write!(json, "{{\"id\":{},\"source\":[{},{}],\"target\":[{},{}],\"records_sent\":{},\"records_received\":{}}}",
+                    channel.id, channel.source.0, channel.source.1, channel.target.0, channel.target.1,
+                    channel.records_sent, channel.records_received).unwrap();
+            }
+
+            json.push_str("],\"fpga_groups\":[");
+            let wrappers = layout.operators_in(scope).filter(|operator| !operator.ghosts.is_empty());
+            for (index, wrapper) in wrappers.enumerate() {
+                if index > 0 { json.push(','); }
+                write!(json, "{{\"wrapper\":{},\"ghosts\":[{}]}}", wrapper.id, join(&wrapper.ghosts, ",")).unwrap();
+            }
+
+            json.push_str("],\"scopes\":");
+            self.write_json_scopes(layout, scope, json);
+            json.push('}');
+        }
+        json.push(']');
+    }
+
+    /// The records sent and received by each operator, summed over its channels.
+    fn operator_records(&self) -> (HashMap<usize, usize>, HashMap<usize, usize>) {
+        let by_addr = self.by_addr();
+        let mut sent = HashMap::new();
+        let mut received = HashMap::new();
+        for channel in self.channels.values() {
+            if let Some(id) = by_addr.get(&child_addr(&channel.scope_addr, channel.source.0)) {
+                *sent.entry(*id).or_insert(0) += channel.records_sent;
+            }
+            if let Some(id) = by_addr.get(&child_addr(&channel.scope_addr, channel.target.0)) {
+                *received.entry(*id).or_insert(0) += channel.records_received;
+            }
+        }
+        (sent, received)
+    }
+
+    /// Identifiers of operators other than ghost operators, by address.
+    fn by_addr(&self) -> HashMap<Vec<usize>, usize> {
+        self.operators
+            .values()
+            .filter(|operator| operator.ghost_of.is_none())
+            .map(|operator| (operator.addr.clone(), operator.id))
+            .collect()
+    }
+}
+
+/// The nesting of operators in scopes, with operator statistics.
+struct Layout {
+    operators: BTreeMap<usize, OperatorNode>,
+    by_addr: HashMap<Vec<usize>, usize>,
+    /// Addresses of scopes, with the addresses of their parents.
+    scopes: BTreeMap<Vec<usize>, Vec<usize>>,
+}
+
+impl Layout {
+    fn new(graph: &DataflowGraph) -> Self {
+        let operators: BTreeMap<_, _> = graph.operators().map(|operator| (operator.id, operator)).collect();
+        let by_addr = graph.by_addr();
+        let mut scopes = BTreeMap::new();
+        for operator in operators.values().filter(|operator| operator.ghost_of.is_none()) {
+            for length in 1 .. operator.addr.len() {
+                scopes.insert(operator.addr[.. length].to_vec(), operator.addr[.. length - 1].to_vec());
+            }
+        }
+        Layout { operators, by_addr, scopes }
+    }
+
+    /// Operators in the scope at `addr`, other than ghost operators.
+    fn operators_in<'a>(&'a self, addr: &'a [usize]) -> impl Iterator<Item=&'a OperatorNode>+'a {
+        self.operators.values().filter(move |operator| {
+            operator.ghost_of.is_none() && !operator.addr.is_empty() && &operator.addr[.. operator.addr.len() - 1] == addr
+        })
+    }
+
+    /// Scopes nested directly in the scope at `addr`.
+    fn scopes_in<'a>(&'a self, addr: &'a [usize]) -> impl Iterator<Item=&'a Vec<usize>>+'a {
+        self.scopes.iter().filter(move |(_, parent)| parent.as_slice() == addr).map(|(scope, _)| scope)
+    }
+
+    /// The DOT node for operator `index` in the scope at `scope`.
+    fn endpoint(&self, scope: &[usize], index: usize) -> Option<String> {
+        let addr = child_addr(scope, index);
+        let id = self.by_addr.get(&addr)?;
+        if index == 0 || self.scopes.contains_key(&addr) {
+            Some(format!("op{}_ports", id))
+        }
+        else {
+            Some(format!("op{}", id))
+        }
+    }
+}
+
+/// The address of operator `index` in the scope at `scope`, or of the scope itself for index zero.
+fn child_addr(scope: &[usize], index: usize) -> Vec<usize> {
+    let mut addr = scope.to_vec();
+    if index > 0 {
+        addr.push(index);
+    }
+    addr
+}
+
+fn join(values: &[usize], separator: &str) -> String {
+    values.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(separator)
+}
+
+fn dot_label(operator: &OperatorNode) -> String {
+    format!("{} (id {})\\nscheduled {}x, {:?}\\nsent {}, received {}",
+        escape(&operator.name), operator.id, operator.schedule_count, operator.schedule_time,
+        operator.records_sent, operator.records_received)
+}
+
+fn json_operator(operator: &OperatorNode, json: &mut String) {
+    write!(json, "{{\"id\":{},\"addr\":[{}],\"name\":\"{}\",\"schedule_count\":{},\"schedule_ns\":{},\"records_sent\":{},\"records_received\":{}}}",
+        operator.id, join(&operator.addr, ","), escape(&operator.name), operator.schedule_count,
+        operator.schedule_time.as_nanos(), operator.records_sent, operator.records_received).unwrap();
+}
+
+/// Escapes a string for a double-quoted DOT or JSON string.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            character if character.is_control() => { write!(escaped, "\\u{:04x}", character as u32).unwrap(); },
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+/// A reader that notes when it has reached its end.
+struct UntilEnd<R> {
+    reader: R,
+    finished: Rc<Cell<bool>>,
+}
+
+impl<R: Read> Read for UntilEnd<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let result = self.reader.read(buf);
+        match &result {
+            Ok(0) => self.finished.set(true),
+            Err(error) if error.kind() != std::io::ErrorKind::Interrupted => self.finished.set(true),
+            _ => { },
+        }
+        result
+    }
+}
@@ -0,0 +1,325 @@
+//! Aggregating logged events into metrics in the Prometheus text format.
+//!
+//! `Metrics` consumes `TimelyEvent` and `CommunicationEvent` logs and maintains counters and
+//! histograms from them: operator schedule durations, messages and records per channel, time spent
+//! parked, progress messages, and network traffic. The metrics register with a communication
+//! `Exporter`, which serves them to scrapers from a local HTTP endpoint or writes them to a file for
+//! a textfile collector, together with the metrics of the communication layer. `execute` does either
+//! when `TIMELY_METRICS_ADDR` or `TIMELY_METRICS_FILE` is set.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use hdrhist::HDRHist;
+
+use crate::communication::logging::{CommunicationEvent, CommunicationSetup};
+use crate::communication::metrics::Exporter;
+use crate::logging::{ParkEvent, StartStop, TimelyEvent, WorkerIdentifier};
+
+/// A histogram of durations, in nanoseconds.
+struct Histogram {
+    hist: HDRHist,
+    count: u64,
+    sum: Duration,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Histogram { hist: HDRHist::new(), count: 0, sum: Duration::default() }
+    }
+    fn add(&mut self, duration: Duration) {
+        self.hist.add_value(duration.as_nanos() as u64);
+        self.count += 1;
+        self.sum += duration;
+    }
+    /// Cumulative counts of values less than each bucket bound.
+    ///
+    /// Bounds are powers of four nanoseconds, from about a microsecond to about seventeen seconds,
+    /// which fall on the boundaries of `HDRHist` buckets and so are counted exactly.
+    fn buckets(&self) -> Vec<(u64, u64)> {
+        let bounds = (5 .. 18).map(|power| 1u64 << (2 * power));
+        bounds.map(|bound| {
+            let count = self.hist.ccdf().take_while(|(value, _, _)| *value < bound).map(|(_, _, count)| count).sum();
+            (bound, count)
+        }).collect()
+    }
+}
+
+/// Aggregated metrics.
+#[derive(Default)]
+struct State {
+    /// Operator names, by worker and operator identifier.
+    names: HashMap<(WorkerIdentifier, usize), String>,
+    /// Start times of running schedules, by worker and operator identifier.
+    scheduled: HashMap<(WorkerIdentifier, usize), Duration>,
+    /// Schedule durations, by worker and operator identifier.
+    schedules: BTreeMap<(WorkerIdentifier, usize), Histogram>,
+    /// Message and record counts, by worker, channel, and whether sent.
+    messages: BTreeMap<(WorkerIdentifier, usize, bool), (u64, u64)>,
+    /// Start times of running parks, by worker.
+    parked: HashMap<WorkerIdentifier, Duration>,
+    /// Park counts and time spent parked, by worker.
+    parks: BTreeMap<WorkerIdentifier, (u64, Duration)>,
+    /// Progress message and update counts, by worker and whether sent.
+    progress: BTreeMap<(WorkerIdentifier, bool), (u64, u64)>,
+    /// Network message and byte counts, by process, remote process, and whether sent.
+    network: BTreeMap<(usize, Option<usize>, bool), (u64, u64)>,
+}
+
+impl State {
+    fn observe_timely(&mut self, time: Duration, worker: WorkerIdentifier, event: &TimelyEvent) {
+        match event {
+            TimelyEvent::Operates(operates) => {
+                self.names.insert((worker, operates.id), operates.name.clone());
+            },
+            TimelyEvent::Schedule(schedule) => {
+                match schedule.start_stop {
+                    StartStop::Start => { self.scheduled.insert((worker, schedule.id), time); },
+                    StartStop::Stop => {
+                        if let Some(start) = self.scheduled.remove(&(worker, schedule.id)) {
+                            self.schedules
+                                .entry((worker, schedule.id))
+                                .or_insert_with(Histogram::new)
+                                .add(time.checked_sub(start).unwrap_or_default());
+                        }
+                    },
+                }
+            },
+            TimelyEvent::Messages(messages) => {
+                let counts = self.messages.entry((worker, messages.channel, messages.is_send)).or_insert((0, 0));
+                counts.0 += 1;
+                counts.1 += messages.length as u64;
+            },
+            TimelyEvent::Park(ParkEvent::Park(_)) => {
+                self.parked.insert(worker, time);
+            },
+            TimelyEvent::Park(ParkEvent::Unpark) => {
+                if let Some(start) = self.parked.remove(&worker) {
+                    let parks = self.parks.entry(worker).or_insert((0, Duration::default()));
+                    parks.0 += 1;
+                    parks.1 += time.checked_sub(start).unwrap_or_default();
+                }
+            },
+            TimelyEvent::Progress(progress) => {
+                let counts = self.progress.entry((worker, progress.is_send)).or_insert((0, 0));
+                counts.0 += 1;
+                counts.1 += (progress.messages.len() + progress.internal.len()) as u64;
+            },
+            _ => { },
+        }
+    }
+
+    fn observe_communication(&mut self, setup: &CommunicationSetup, event: &CommunicationEvent) {
+        if let CommunicationEvent::Message(message) = event {
+            let counts = self.network.entry((setup.process, setup.remote, message.is_send)).or_insert((0, 0));
+            counts.0 += 1;
+            counts.1 += message.header.length as u64;
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut text = String::new();
+        let direction = |is_send: bool| if is_send { "send" } else { "recv" };
+
+        header(&mut text, "timely_schedule_duration_seconds", "histogram", "Time operators were scheduled for.");
+        for ((worker, id), histogram) in self.schedules.iter() {
+            let name = self.names.get(&(*worker, *id)).map(|name| escape(name)).unwrap_or_default();
+            let labels = format!("worker=\"{}\",operator=\"{}\",name=\"{}\"", worker, id, name);
+            for (bound, count) in histogram.buckets() {
+                writeln!(text, "timely_schedule_duration_seconds_bucket{{{},le=\"{}\"}} {}", labels, bound as f64 / 1e9, count).unwrap();
+            }
+            writeln!(text, "timely_schedule_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", labels, histogram.count).unwrap();
+            writeln!(text, "timely_schedule_duration_seconds_sum{{{}}} {}", labels, histogram.sum.as_secs_f64()).unwrap();
+            writeln!(text, "timely_schedule_duration_seconds_count{{{}}} {}", labels, histogram.count).unwrap();
+        }
+
+        header(&mut text, "timely_messages_total", "counter", "Data messages sent or received, by channel.");
+        for ((worker, channel, is_send), (messages, _)) in self.messages.iter() {
+            writeln!(text, "timely_messages_total{{worker=\"{}\",channel=\"{}\",direction=\"{}\"}} {}", worker, channel, direction(*is_send), messages).unwrap();
+        }
+        header(&mut text, "timely_records_total", "counter", "Records sent or received, by channel.");
+        for ((worker, channel, is_send), (_, records)) in self.messages.iter() {
+            writeln!(text, "timely_records_total{{worker=\"{}\",channel=\"{}\",direction=\"{}\"}} {}", worker, channel, direction(*is_send), records).unwrap();
+        }
+
+        header(&mut text, "timely_parks_total", "counter", "Times workers parked.");
+        for (worker, (parks, _)) in self.parks.iter() {
+            writeln!(text, "timely_parks_total{{worker=\"{}\"}} {}", worker, parks).unwrap();
+        }
+        header(&mut text, "timely_park_seconds_total", "counter", "Time workers spent parked.");
+        for (worker, (_, parked)) in self.parks.iter() {
+            writeln!(text, "timely_park_seconds_total{{worker=\"{}\"}} {}", worker, parked.as_secs_f64()).unwrap();
+        }
+
+        header(&mut text, "timely_progress_messages_total", "counter", "Progress messages sent or received.");
+        for ((worker, is_send), (messages, _)) in self.progress.iter() {
+            writeln!(text, "timely_progress_messages_total{{worker=\"{}\",direction=\"{}\"}} {}", worker, direction(*is_send), messages).unwrap();
+        }
+        header(&mut text, "timely_progress_updates_total", "counter", "Pointstamp updates in progress messages sent or received.");
+        for ((worker, is_send), (_, updates)) in self.progress.iter() {
+            writeln!(text, "timely_progress_updates_total{{worker=\"{}\",direction=\"{}\"}} {}", worker, direction(*is_send), updates).unwrap();
+        }
+
+        header(&mut text, "timely_network_messages_total", "counter", "Messages sent to or received from other processes.");
+        for ((process, remote, is_send), (messages, _)) in self.network.iter() {
+            writeln!(text, "timely_network_messages_total{{process=\"{}\",remote=\"{}\",direction=\"{}\"}} {}", process, remote_label(*remote), direction(*is_send), messages).unwrap();
+        }
+        header(&mut text, "timely_network_bytes_total", "counter", "Message bytes sent to or received from other processes.");
+        for ((process, remote, is_send), (_, bytes)) in self.network.iter() {
+            writeln!(text, "timely_network_bytes_total{{process=\"{}\",remote=\"{}\",direction=\"{}\"}} {}", process, remote_label(*remote), direction(*is_send), bytes).unwrap();
+        }
+
+        text
+    }
+}
+
+/// Metrics aggregated from logged events, shared by the loggers that feed them.
+///
+/// # Examples
+/// ```
+/// use timely::communication::metrics::Exporter;
+/// use timely::logging::TimelyEvent;
+/// use timely::logging::prometheus::Metrics;
+///
+/// let metrics = Metrics::new();
+/// let exporter = Exporter::new();
+/// metrics.register(&exporter);
+/// exporter.serve("127.0.0.1:0").unwrap();
+///
+/// timely::execute_from_args(std::env::args(), move |worker, _| {
+///     worker.log_register().insert::<TimelyEvent,_>("timely", metrics.timely());
+/// }).unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct Metrics {
+    state: Arc<Mutex<State>>,
+}
+
+impl Metrics {
+    /// Creates metrics with no events observed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates metrics registered with an exporter as the `TIMELY_METRICS_ADDR` and
+    /// `TIMELY_METRICS_FILE` environment variables direct, or `None` if neither is set.
+    ///
+    /// `TIMELY_METRICS_ADDR` is an address to serve the metrics from, and `TIMELY_METRICS_FILE` a
+    /// file to write them to every `TIMELY_METRICS_PERIOD_SECS` seconds, by default ten. Exports that
+    /// fail to start are reported and skipped. Other sources, such as the communication metrics of
+    /// the process, can register with the returned exporter.
+    pub fn from_env() -> Option<(Self, Exporter)> {
+        let addr = ::std::env::var("TIMELY_METRICS_ADDR").ok();
+        let file = ::std::env::var("TIMELY_METRICS_FILE").ok();
+        if addr.is_none() && file.is_none() {
+            return None;
+        }
+        let metrics = Metrics::new();
+        let exporter = Exporter::new();
+        metrics.register(&exporter);
+        if let Some(addr) = addr {
+            if let Err(error) = exporter.serve(&addr) {
+                eprintln!("timely: could not serve metrics at {:?}: {}", addr, error);
+            }
+        }
+        if let Some(file) = file {
+            let period = ::std::env::var("TIMELY_METRICS_PERIOD_SECS").ok().and_then(|secs| secs.parse().ok()).unwrap_or(10);
+            if let Err(error) = exporter.export_every(&file, Duration::from_secs(period)) {
+                eprintln!("timely: could not write metrics to {:?}: {}", file, error);
+            }
+        }
+        Some((metrics, exporter))
+    }
+
+    /// A logging action for `TimelyEvent`, to register as the worker's "timely" logger.
+    pub fn timely(&self) -> impl FnMut(&Duration, &mut Vec<(Duration, WorkerIdentifier, TimelyEvent)>)+'static {
+        let state = self.state.clone();
+        move |_time, data| {
+            let mut state = state.lock().expect("metrics poisoned");
+            for (time, worker, event) in data.iter() {
+                state.observe_timely(*time, *worker, event);
+            }
+        }
+    }
+
+    /// A logging action for `CommunicationEvent`, for the loggers of communication threads.
+    pub fn communication(&self) -> impl FnMut(&Duration, &mut Vec<(Duration, CommunicationSetup, CommunicationEvent)>)+Send+'static {
+        let state = self.state.clone();
+        move |_time, data| {
+            let mut state = state.lock().expect("metrics poisoned");
+            for (_time, setup, event) in data.iter() {
+                state.observe_communication(setup, event);
+            }
+        }
+    }
+
+    /// The metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        self.state.lock().expect("metrics poisoned").render()
+    }
+
+    /// Writes the metrics in the Prometheus text format.
+    pub fn write_prometheus<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self.render().as_bytes())
+    }
+
+    /// Registers these metrics as a source of `exporter`.
+    pub fn register(&self, exporter: &Exporter) {
+        let metrics = self.clone();
+        exporter.register(move |writer| metrics.write_prometheus(writer));
+    }
+}
+
+fn header(text: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(text, "# HELP {} {}", name, help).unwrap();
+    writeln!(text, "# TYPE {} {}", name, kind).unwrap();
+}
+
+fn remote_label(remote: Option<usize>) -> String {
+    remote.map(|remote| remote.to_string()).unwrap_or_else(|| "local".to_owned())
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+
+    use crate::communication::metrics::Exporter;
+    use crate::logging::{OperatesEvent, ParkEvent, ScheduleEvent, TimelyEvent};
+
+    use super::Metrics;
+
+    #[test]
+    fn schedules_and_parks() {
+        let metrics = Metrics::new();
+        let mut log = metrics.timely();
+        let micros = Duration::from_micros;
+        log(&micros(0), &mut vec![
+            (micros(0), 0, TimelyEvent::Operates(OperatesEvent { id: 3, addr: vec![0, 1], name: "Map".to_owned() })),
+            (micros(10), 0, TimelyEvent::Schedule(ScheduleEvent::start(3))),
+            (micros(12), 0, TimelyEvent::Schedule(ScheduleEvent::stop(3))),
+            (micros(20), 0, TimelyEvent::Park(ParkEvent::park(None))),
+            (micros(520), 0, TimelyEvent::Park(ParkEvent::unpark())),
+        ]);
+
+        let exporter = Exporter::new();
+        metrics.register(&exporter);
+        let mut text = Vec::new();
+        exporter.write_prometheus(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+
+        let labels = "worker=\"0\",operator=\"3\",name=\"Map\"";
+        assert!(text.contains(&format!("timely_schedule_duration_seconds_bucket{{{},le=\"0.000001024\"}} 0", labels)));
+        assert!(text.contains(&format!("timely_schedule_duration_seconds_bucket{{{},le=\"0.000004096\"}} 1", labels)));
+        assert!(text.contains(&format!("timely_schedule_duration_seconds_count{{{}}} 1", labels)));
+        assert!(text.contains("timely_park_seconds_total{worker=\"0\"} 0.0005"));
+    }
+}
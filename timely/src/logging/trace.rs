@@ -0,0 +1,183 @@
+//! Export of timely logging events as a Chrome trace.
+//!
+//! A `ChromeTrace` receives the events of a worker's "timely" logger, through a `BatchLogger`, and
+//! writes them in the Chrome trace-event JSON format, which `chrome://tracing` and the Perfetto UI
+//! load directly. Each worker appears as its own process, with a span for each scheduling of an
+//! operator, nested as the operators are, and a span for each time it parks. Messages between
+//! workers are drawn as flow arrows from the operator that sends them to the one that receives them,
+//! when the traces of both workers are loaded together.
+
+use std::io::Write;
+use std::time::Duration;
+use std::hash::{Hash, Hasher};
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+
+use crate::dataflow::operators::capture::{Event, EventPusher};
+use crate::logging::{TimelyEvent, StartStop, ParkEvent, WorkerIdentifier};
+use crate::logging::graph::escape;
+
+/// Writes timely logging events as Chrome trace-event JSON.
+///
+/// The trace is a JSON array, which is closed when the `ChromeTrace` is dropped. Trace viewers also
+/// load traces that were not closed, for example because the computation did not shut down.
+///
+/// A `ChromeTrace` that fails to write warns once and drops all further events, as `FileWriter`
+/// does, rather than interrupting the computation it traces.
+///
+/// # Examples
+/// ```
+/// use std::fs::File;
+/// use timely::logging::{BatchLogger, TimelyEvent};
+/// use timely::logging::trace::ChromeTrace;
+///
+/// timely::execute_from_args(std::env::args(), |worker, _| {
+///     let file = File::create(format!("timely-{}.trace.json", worker.index())).unwrap();
+///     let mut logger = BatchLogger::new(ChromeTrace::new(file));
+///     worker.log_register().insert::<TimelyEvent,_>("timely", move |time, data| {
+///         logger.publish_batch(time, data)
+///     });
+/// }).unwrap();
+/// ```
+pub struct ChromeTrace<W: Write> {
+    writer: W,
+    /// Names and addresses of operators, by identifier.
+    operators: HashMap<usize, (String, Vec<usize>)>,
+    /// Workers whose process names have been written.
+    workers: HashSet<WorkerIdentifier>,
+    empty: bool,
+    /// Set once writing has failed.
+    failed: bool,
+}
+
+impl<W: Write> ChromeTrace<W> {
+    /// Creates a trace written to `writer`.
+    pub fn new(writer: W) -> Self {
+        let mut trace = ChromeTrace {
+            writer,
+            operators: HashMap::new(),
+            workers: HashSet::new(),
+            empty: true,
+            failed: false,
+        };
+        trace.write_bytes(b"[");
+        trace
+    }
+
+    /// Writes the trace events describing `event`, logged by `worker` at `time`.
+    fn record(&mut self, time: Duration, worker: WorkerIdentifier, event: &TimelyEvent) {
+        if self.workers.insert(worker) {
+            self.write(format!("{{\"name\":\"process_name\",\"ph\":\"M\",\"pid\":{0},\"args\":{{\"name\":\"worker {0}\"}}}}", worker));
+        }
+        let ts = micros(time);
+        match event {
+            TimelyEvent::Operates(operates) => {
+                self.operators.insert(operates.id, (operates.name.clone(), operates.addr.clone()));
+            },
+            TimelyEvent::Schedule(schedule) => {
+                let event = match schedule.start_stop {
+                    StartStop::Start => {
+                        let (name, addr) = match self.operators.get(&schedule.id) {
+                            Some((name, addr)) => (escape(name), format!("{:?}", addr)),
+                            None => (format!("operator {}", schedule.id), "[]".to_owned()),
+                        };
+                        format!("{{\"name\":\"{}\",\"cat\":\"schedule\",\"ph\":\"B\",\"ts\":{},\"pid\":{},\"tid\":{},\"args\":{{\"id\":{},\"addr\":{}}}}}",
+                            name, ts, worker, worker, schedule.id, addr)
+                    },
+                    StartStop::Stop => {
+                        format!("{{\"cat\":\"schedule\",\"ph\":\"E\",\"ts\":{},\"pid\":{},\"tid\":{}}}", ts, worker, worker)
+                    },
+                };
+                self.write(event);
+            },
+            TimelyEvent::Park(park) => {
+                let event = match park {
+                    ParkEvent::Park(timeout) => {
+                        let timeout = timeout.map(micros).unwrap_or_else(|| "null".to_owned());
+                        format!("{{\"name\":\"park\",\"cat\":\"park\",\"ph\":\"B\",\"ts\":{},\"pid\":{},\"tid\":{},\"args\":{{\"timeout_us\":{}}}}}",
+                            ts, worker, worker, timeout)
+                    },
+                    ParkEvent::Unpark => {
+                        format!("{{\"cat\":\"park\",\"ph\":\"E\",\"ts\":{},\"pid\":{},\"tid\":{}}}", ts, worker, worker)
+                    },
+                };
+                self.write(event);
+            },
+            TimelyEvent::Messages(messages) if messages.source != messages.target => {
+                // Both workers derive the same flow identifier from the message.
+                let mut hasher = DefaultHasher::new();
+                (messages.channel, messages.source, messages.target, messages.seq_no).hash(&mut hasher);
+                let id = hasher.finish();
+                if messages.is_send && messages.source == worker {
+                    self.write(format!("{{\"name\":\"message\",\"cat\":\"message\",\"ph\":\"s\",\"id\":\"0x{:x}\",\"ts\":{},\"pid\":{},\"tid\":{},\"args\":{{\"channel\":{},\"length\":{}}}}}",
+                        id, ts, worker, worker, messages.channel, messages.length));
+                }
+                if !messages.is_send && messages.target == worker {
+                    self.write(format!("{{\"name\":\"message\",\"cat\":\"message\",\"ph\":\"f\",\"bp\":\"e\",\"id\":\"0x{:x}\",\"ts\":{},\"pid\":{},\"tid\":{}}}",
+                        id, ts, worker, worker));
+                }
+            },
+            _ => { },
+        }
+    }
+
+    fn write(&mut self, event: String) {
+        let separator = if self.empty { "\n" } else { ",\n" };
+        self.empty = false;
+        self.write_bytes(separator.as_bytes());
+        self.write_bytes(event.as_bytes());
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        if !self.failed {
+            let result = self.writer.write_all(bytes);
+            self.check(result);
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.failed {
+            let result = self.writer.flush();
+            self.check(result);
+        }
+    }
+
+    /// Warns of a failed write, and disables further writes.
+    fn check(&mut self, result: std::io::Result<()>) {
+        if let Err(error) = result {
+            eprintln!("timely: failed to write trace: {}; tracing disabled", error);
+            self.failed = true;
+        }
+    }
+}
+
+impl<W: Write> EventPusher<Duration, (Duration, WorkerIdentifier, TimelyEvent)> for ChromeTrace<W> {
+    fn push(&mut self, event: Event<Duration, (Duration, WorkerIdentifier, TimelyEvent)>) {
+        if self.failed {
+            return;
+        }
+        match event {
+            Event::Messages(_, data) => {
+                for (time, worker, event) in data.iter() {
+                    self.record(*time, *worker, event);
+                }
+            },
+            Event::Progress(_) => self.flush(),
+        }
+    }
+}
+
+impl<W: Write> Drop for ChromeTrace<W> {
+    fn drop(&mut self) {
+        // Errors cannot be reported from `drop`, and an unclosed trace still loads.
+        if !self.failed {
+            let _ = self.writer.write_all(b"\n]\n");
+            let _ = self.writer.flush();
+        }
+    }
+}
+
+/// Formats a duration in microseconds, the unit of trace timestamps.
+fn micros(duration: Duration) -> String {
+    format!("{}.{:03}", duration.as_micros(), duration.subsec_nanos() % 1000)
+}
@@ -470,6 +470,11 @@ impl<T: PartialOrder+Ord+Clone> MutableAntichain<T> {
             .map(|td| td.1)
             .sum()
     }
+
+    /// Reveals the accumulated updates, which may repeat times and include zero counts.
+    pub fn updates(&self) -> impl Iterator<Item=&(T, i64)> {
+        self.updates.iter()
+    }
 }
 
 /// Extension trait for filtering time changes through antichains.
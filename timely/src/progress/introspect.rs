@@ -0,0 +1,191 @@
+//! Snapshots of the progress tracking state of installed dataflows.
+//!
+//! Each scope reports, for the operators it hosts, the input frontiers and the outstanding
+//! pointstamps its `reachability::Tracker` maintains, which the worker collects on request with
+//! `Worker::dataflow_frontiers`. Times are reported in their `Debug` form, so that dataflows with
+//! different timestamp types can be inspected alike.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::progress::{Port, Timestamp};
+use crate::progress::reachability::PortInformation;
+
+/// An outstanding pointstamp: a time at a location, with its count.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pointstamp {
+    /// The time, in its `Debug` form.
+    pub time: String,
+    /// The number of outstanding updates at the time.
+    pub count: i64,
+    /// True if this pointstamp alone holds back the frontier at its location.
+    pub global: bool,
+    /// True if no earlier time may reach the location of this pointstamp.
+    pub minimal: bool,
+}
+
+/// The progress tracking state of an operator.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OperatorFrontiers {
+    /// Sequence of scope identifiers from the root to the operator.
+    pub addr: Vec<usize>,
+    /// The name of the operator.
+    pub name: String,
+    /// For each input, the frontier of times the operator may still receive.
+    pub input_frontiers: Vec<Vec<String>>,
+    /// For each input, the times of messages not yet received.
+    pub messages: Vec<Vec<Pointstamp>>,
+    /// For each output, the times of capabilities the operator holds.
+    pub capabilities: Vec<Vec<Pointstamp>>,
+}
+
+/// A channel between two ports within a scope.
+///
+/// Ports are named by operator address and port number. The address of the scope itself names
+/// its inputs, as sources, and its outputs, as targets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrontierEdge {
+    /// Address of the scope containing the channel.
+    pub scope: Vec<usize>,
+    /// The operator output the channel leaves.
+    pub source: (Vec<usize>, usize),
+    /// The operator input the channel enters.
+    pub target: (Vec<usize>, usize),
+}
+
+/// Outstanding pointstamps that may hold back a frontier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    /// Address of the operator at which the pointstamps are outstanding.
+    pub addr: Vec<usize>,
+    /// The name of the operator.
+    pub name: String,
+    /// The input, for messages, or output, for capabilities, of the pointstamps.
+    pub port: Port,
+    /// The outstanding pointstamps.
+    pub pointstamps: Vec<Pointstamp>,
+}
+
+/// The progress tracking state of the operators of a dataflow.
+#[derive(Debug, Clone, Default)]
+pub struct DataflowFrontiers {
+    pub(crate) operators: BTreeMap<Vec<usize>, OperatorFrontiers>,
+    pub(crate) edges: Vec<FrontierEdge>,
+}
+
+/// A location visited while walking back from a frontier.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Visit {
+    /// An input of an operator, seen from the scope containing the operator.
+    Input(Vec<usize>, usize),
+    /// An output of a scope, seen from within the scope.
+    Output(Vec<usize>, usize),
+}
+
+impl DataflowFrontiers {
+    /// Creates an empty report.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The operators of the dataflow, by address.
+    pub fn operators(&self) -> &BTreeMap<Vec<usize>, OperatorFrontiers> {
+        &self.operators
+    }
+
+    /// The channels of the dataflow.
+    pub fn edges(&self) -> &[FrontierEdge] {
+        &self.edges
+    }
+
+    /// The outstanding pointstamps upstream of an input of the operator at `addr`.
+    ///
+    /// The walk follows channels back from the input, into scopes through their outputs and out of
+    /// scopes through their inputs, and conservatively assumes each operator connects all of its
+    /// inputs to all of its outputs. Holders are listed nearest first; those with `global`
+    /// pointstamps are the ones holding back frontiers on their own.
+    pub fn holders(&self, addr: &[usize], input: usize) -> Vec<Holder> {
+        let mut holders = Vec::new();
+        let mut visited = BTreeSet::new();
+        let mut todo = VecDeque::new();
+        todo.push_back(Visit::Input(addr.to_vec(), input));
+
+        while let Some(visit) = todo.pop_front() {
+            if !visited.insert(visit.clone()) {
+                continue;
+            }
+            let (scope, target) = match visit {
+                Visit::Input(addr, port) => {
+                    if let Some(operator) = self.operators.get(&addr) {
+                        self.hold(&mut holders, operator, Port::Target(port));
+                    }
+                    match addr.split_last() {
+                        Some((_, scope)) => (scope.to_vec(), (addr, port)),
+                        None => continue,
+                    }
+                },
+                Visit::Output(scope, port) => (scope.clone(), (scope, port)),
+            };
+            for edge in self.edges.iter().filter(|edge| edge.scope == scope && edge.target == target) {
+                let (source, port) = &edge.source;
+                if source == &scope {
+                    // A scope input, fed by a channel in the enclosing scope.
+                    todo.push_back(Visit::Input(scope.clone(), *port));
+                }
+                else if let Some(operator) = self.operators.get(source) {
+                    self.hold(&mut holders, operator, Port::Source(*port));
+                    if self.edges.iter().any(|edge| &edge.scope == source) {
+                        todo.push_back(Visit::Output(source.clone(), *port));
+                    }
+                    else {
+                        for input in 0 .. operator.input_frontiers.len() {
+                            todo.push_back(Visit::Input(source.clone(), input));
+                        }
+                    }
+                }
+            }
+        }
+
+        holders
+    }
+
+    /// Lists the pointstamps outstanding at a port of `operator`, if any.
+    fn hold(&self, holders: &mut Vec<Holder>, operator: &OperatorFrontiers, port: Port) {
+        let pointstamps = match port {
+            Port::Target(port) => operator.messages.get(port),
+            Port::Source(port) => operator.capabilities.get(port),
+        };
+        if let Some(pointstamps) = pointstamps.filter(|pointstamps| !pointstamps.is_empty()) {
+            if !holders.iter().any(|holder: &Holder| holder.addr == operator.addr && holder.port == port) {
+                holders.push(Holder {
+                    addr: operator.addr.clone(),
+                    name: operator.name.clone(),
+                    port,
+                    pointstamps: pointstamps.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// The frontier of times that may still reach `port`.
+pub(crate) fn frontier<T: Timestamp>(port: &PortInformation<T>) -> Vec<String> {
+    port.implications.frontier().iter().map(|time| format!("{:?}", time)).collect()
+}
+
+/// The pointstamps outstanding at `port`, in order of time.
+pub(crate) fn pointstamps<T: Timestamp>(port: &PortInformation<T>) -> Vec<Pointstamp> {
+    let mut counts = BTreeMap::new();
+    for (time, count) in port.pointstamps.updates() {
+        *counts.entry(time).or_insert(0) += *count;
+    }
+    counts
+        .into_iter()
+        .filter(|(_, count)| *count != 0)
+        .map(|(time, count)| Pointstamp {
+            time: format!("{:?}", time),
+            count,
+            global: port.is_global(time),
+            minimal: !port.implications.frontier().iter().any(|t| t.less_than(time)),
+        })
+        .collect()
+}
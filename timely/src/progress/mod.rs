@@ -4,6 +4,8 @@ pub use self::operate::Operate;
 pub use self::subgraph::{Subgraph, SubgraphBuilder};
 pub use self::timestamp::{Timestamp, PathSummary};
 pub use self::change_batch::ChangeBatch;
+pub use self::stats::OperatorStats;
+pub use self::introspect::DataflowFrontiers;
 pub use self::frontier::Antichain;
 
 pub mod change_batch;
@@ -13,6 +15,8 @@ pub mod operate;
 pub mod broadcast;
 pub mod reachability;
 pub mod subgraph;
+pub mod stats;
+pub mod introspect;
 
 /// A timely dataflow location.
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Abomonation, Serialize, Deserialize)]
@@ -0,0 +1,65 @@
+//! Runtime statistics of operators.
+//!
+//! Each scope keeps counters for the operators it schedules, which the worker collects on request
+//! with `Worker::operator_stats`. The counters are maintained without logging.
+
+use std::rc::{Rc, Weak};
+use std::cell::RefCell;
+use std::time::Duration;
+use std::collections::BTreeMap;
+
+/// Runtime statistics of an operator.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OperatorStats {
+    /// Worker-unique identifier for the operator.
+    pub id: usize,
+    /// Sequence of scope identifiers from the root to the operator.
+    pub addr: Vec<usize>,
+    /// The name of the operator.
+    pub name: String,
+    /// The number of times the operator was scheduled.
+    pub invocations: u64,
+    /// The total time the operator was scheduled for.
+    pub total_time: Duration,
+    /// The longest time the operator was scheduled for at once.
+    pub max_time: Duration,
+    /// The number of records the operator consumed from its inputs.
+    pub records_consumed: u64,
+    /// The number of records the operator produced at its outputs.
+    pub records_produced: u64,
+}
+
+impl OperatorStats {
+    /// Records one scheduling of the operator.
+    pub(crate) fn record(&mut self, elapsed: Duration, consumed: i64, produced: i64) {
+        self.invocations += 1;
+        self.total_time += elapsed;
+        self.max_time = self.max_time.max(elapsed);
+        self.records_consumed += consumed.max(0) as u64;
+        self.records_produced += produced.max(0) as u64;
+    }
+}
+
+/// The statistics of a worker's operators, for as long as the operators exist.
+#[derive(Default)]
+pub struct StatsRegistry {
+    operators: Vec<Weak<RefCell<OperatorStats>>>,
+}
+
+impl StatsRegistry {
+    /// Registers an operator, returning the statistics its scope should update.
+    pub fn register(&mut self, id: usize, addr: Vec<usize>, name: String) -> Rc<RefCell<OperatorStats>> {
+        let stats = Rc::new(RefCell::new(OperatorStats { id, addr, name, ..Default::default() }));
+        self.operators.push(Rc::downgrade(&stats));
+        stats
+    }
+    /// The statistics of the operators that still exist, by address.
+    pub fn snapshot(&mut self) -> BTreeMap<Vec<usize>, OperatorStats> {
+        self.operators.retain(|stats| stats.strong_count() > 0);
+        self.operators
+            .iter()
+            .filter_map(|stats| stats.upgrade())
+            .map(|stats| { let stats = stats.borrow().clone(); (stats.addr.clone(), stats) })
+            .collect()
+    }
+}
@@ -9,6 +9,7 @@ use std::rc::Rc;
 use std::cell::RefCell;
 use std::collections::{BinaryHeap, HashMap};
 use std::cmp::Reverse;
+use std::time::Instant;
 
 use crate::logging::TimelyLogger as Logger;
 
@@ -20,6 +21,8 @@ use crate::progress::{Timestamp, Operate, operate::SharedProgress};
 use crate::progress::{Location, Port, Source, Target};
 
 use crate::progress::ChangeBatch;
+use crate::progress::OperatorStats;
+use crate::progress::introspect::{self, DataflowFrontiers};
 use crate::progress::broadcast::Progcaster;
 use crate::progress::reachability;
 use crate::progress::timestamp::Refines;
@@ -255,6 +258,16 @@ where
 
         let mut builder = reachability::Builder::new();
 
+        // Register the children for runtime statistics; child zero stands for the scope's boundary.
+        {
+            let mut registry = worker.stats_registry();
+            for child in self.children.iter_mut().skip(1) {
+                let mut addr = self.path.clone();
+                addr.push(child.index);
+                child.stats = Some(registry.register(child.id, addr, child.name.clone()));
+            }
+        }
+
         // Child 0 has `inputs` outputs and `outputs` inputs, not yet connected.
         builder.add_node(0, outputs, inputs, vec![vec![Antichain::new(); inputs]; outputs]);
         for (index, child) in self.children.iter().enumerate().skip(1) {
@@ -447,6 +460,39 @@ where
 
         incomplete || tracking
     }
+
+    fn frontiers(&self, report: &mut DataflowFrontiers) {
+        // Child zero stands for the scope itself, whose address names its inputs and outputs.
+        let addr = |index: usize| {
+            let mut addr = self.path.clone();
+            if index > 0 { addr.push(index); }
+            addr
+        };
+        for child in self.children.iter() {
+            for (port, targets) in child.edges.iter().enumerate() {
+                for target in targets.iter() {
+                    report.edges.push(introspect::FrontierEdge {
+                        scope: self.path.clone(),
+                        source: (addr(child.index), port),
+                        target: (addr(target.node), target.port),
+                    });
+                }
+            }
+            if child.index > 0 {
+                let state = self.pointstamp_tracker.node_state(child.index);
+                report.operators.insert(addr(child.index), introspect::OperatorFrontiers {
+                    addr: addr(child.index),
+                    name: child.name.clone(),
+                    input_frontiers: state.targets.iter().map(introspect::frontier).collect(),
+                    messages: state.targets.iter().map(introspect::pointstamps).collect(),
+                    capabilities: state.sources.iter().map(introspect::pointstamps).collect(),
+                });
+                if let Some(operator) = child.operator.as_ref() {
+                    operator.frontiers(report);
+                }
+            }
+        }
+    }
 }
 
 
@@ -774,6 +820,8 @@ struct PerOperatorState<T: Timestamp> {
 
     logging: Option<Logger>,
 
+    stats: Option<Rc<RefCell<OperatorStats>>>,
+
     wrapper_ghost: Rc<RefCell<HashMap<usize, Vec<usize>>>>,
     wrapper_ghost_edges: Rc<RefCell<HashMap<usize, Vec<(usize, usize)>>>>,
 
@@ -797,6 +845,7 @@ impl<T: Timestamp> PerOperatorState<T> {
             ghost_edges: vec![Vec::new(); outputs],
 
             logging: None,
+            stats: None,
 
             shared_progress: Rc::new(RefCell::new(SharedProgress::new(inputs,outputs))),
             internal_summary: Vec::new(),
@@ -840,6 +889,7 @@ impl<T: Timestamp> PerOperatorState<T> {
             edges:              vec![vec![]; outputs],
             ghost_edges:              vec![vec![]; outputs],
             logging,
+            stats: None,
 
             shared_progress,
             internal_summary,
@@ -865,8 +915,23 @@ impl<T: Timestamp> PerOperatorState<T> {
                 l.log(crate::logging::ScheduleEvent::start(self.id));
             }
 
+            let records = |shared: &SharedProgress<T>| {
+                let consumed = shared.consumeds.iter().flat_map(|batch| batch.unstable_internal_updates().iter()).map(|(_, count)| count).sum::<i64>();
+                let produced = shared.produceds.iter().flat_map(|batch| batch.unstable_internal_updates().iter()).map(|(_, count)| count).sum::<i64>();
+                (consumed, produced)
+            };
+            let shared_progress = &self.shared_progress;
+            let before = self.stats.as_ref().map(|_| (Instant::now(), records(&shared_progress.borrow())));
+
             let incomplete = operator.schedule();
 
+            // Counts the records consumed and produced since before the schedule call.
+            if let (Some(stats), Some((start, (consumed, produced)))) = (self.stats.as_ref(), before) {
+                let elapsed = start.elapsed();
+                let (consumed_after, produced_after) = records(&self.shared_progress.borrow());
+                stats.borrow_mut().record(elapsed, consumed_after - consumed, produced_after - produced);
+            }
+
             // Perhaps log information about the stop of the schedule call.
             if let Some(l) = self.logging.as_mut() {
                 l.log(crate::logging::ScheduleEvent::stop(self.id));
@@ -8,6 +8,12 @@ use std::time::{Duration, Instant};
 use std::cmp::Reverse;
 use crossbeam_channel::{Sender, Receiver};
 
+use crate::communication::buzzer::WakerSlot;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+#[cfg(unix)]
+use crate::scheduling::readiness::Readiness;
+
 /// Methods required to act as a timely scheduler.
 ///
 /// The core methods are the activation of "paths", sequences of integers, and
@@ -49,6 +55,10 @@ pub struct Activations {
     // Delayed activations.
     timer: Instant,
     queue: BinaryHeap<Reverse<(Duration, Vec<usize>)>>,
+
+    // Activations by readiness of file descriptors.
+    #[cfg(unix)]
+    readiness: Readiness,
 }
 
 impl Activations {
@@ -65,6 +75,8 @@ impl Activations {
             rx,
             timer,
             queue: BinaryHeap::new(),
+            #[cfg(unix)]
+            readiness: Readiness::default(),
         }
     }
 
@@ -86,6 +98,77 @@ impl Activations {
         }
     }
 
+    /// Activates the task addressed by `path` whenever `fd` is readable.
+    ///
+    /// The worker checks the descriptor at each step, and blocks in `poll` on it rather than
+    /// parking. The descriptor should be deregistered before it is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use std::os::unix::io::AsRawFd;
+    /// use std::os::unix::net::UnixStream;
+    /// use std::time::{Duration, Instant};
+    /// use timely::scheduling::Activations;
+    ///
+    /// let (mut writer, reader) = UnixStream::pair().unwrap();
+    ///
+    /// let mut activations = Activations::new(Instant::now());
+    /// activations.register_fd(reader.as_raw_fd(), &[0, 1]);
+    ///
+    /// writer.write_all(b"ready").unwrap();
+    /// activations.poll_ready(Some(Duration::from_secs(1))).unwrap();
+    /// activations.advance();
+    /// assert_eq!(activations.empty_for(), Some(Duration::new(0, 0)));
+    ///
+    /// activations.deregister_fd(reader.as_raw_fd());
+    /// ```
+    #[cfg(unix)]
+    pub fn register_fd(&mut self, fd: RawFd, path: &[usize]) {
+        self.readiness.register(fd, path);
+    }
+
+    /// Stops activating tasks by readiness of `fd`.
+    #[cfg(unix)]
+    pub fn deregister_fd(&mut self, fd: RawFd) {
+        self.readiness.deregister(fd);
+    }
+
+    /// Indicates whether any file descriptors are registered.
+    #[cfg(unix)]
+    pub fn has_fds(&self) -> bool {
+        !self.readiness.is_empty()
+    }
+
+    /// A waker that interrupts `poll_ready`.
+    #[cfg(unix)]
+    pub fn fd_waker(&mut self) -> ::std::io::Result<::std::task::Waker> {
+        self.readiness.waker()
+    }
+
+    /// Wakes `waker` once a registered file descriptor is readable, or after `timeout`.
+    ///
+    /// This is used in place of `poll_ready` by workers driven as futures, and each call replaces
+    /// the previous request.
+    #[cfg(unix)]
+    pub fn watch_fds(&mut self, timeout: Option<Duration>, waker: &::std::task::Waker) -> ::std::io::Result<()> {
+        self.readiness.watch(timeout, waker)
+    }
+
+    /// Waits up to `timeout` for a registered file descriptor to become readable, and activates
+    /// the tasks of readable descriptors.
+    ///
+    /// A timeout of `None` waits indefinitely, unless interrupted by the waker of `fd_waker`.
+    #[cfg(unix)]
+    pub fn poll_ready(&mut self, timeout: Option<Duration>) -> ::std::io::Result<()> {
+        let Activations { readiness, bounds, slices, .. } = self;
+        readiness.poll(timeout, |path| {
+            bounds.push((slices.len(), path.len()));
+            slices.extend(path);
+        })
+    }
+
     /// Discards the current active set and presents the next active set.
     pub fn advance(&mut self) {
 
@@ -161,6 +244,7 @@ impl Activations {
         SyncActivations {
             tx: self.tx.clone(),
             thread: std::thread::current(),
+            waker: WakerSlot::current(),
         }
     }
 
@@ -187,11 +271,12 @@ impl Activations {
 pub struct SyncActivations {
     tx: Sender<Vec<usize>>,
     thread: Thread,
+    waker: WakerSlot,
 }
 
 impl SyncActivations {
     /// Unparks the task addressed by `path` and unparks the associated worker
-    /// thread.
+    /// thread, or wakes the task driving the worker.
     pub fn activate(&self, path: Vec<usize>) -> Result<(), SyncActivationError> {
         self.activate_batch(std::iter::once(path))
     }
@@ -208,6 +293,7 @@ impl SyncActivations {
         for path in paths.into_iter() {
             self.tx.send(path).map_err(|_| SyncActivationError)?;
         }
+        self.waker.wake();
         self.thread.unpark();
         Ok(())
     }
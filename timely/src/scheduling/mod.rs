@@ -4,6 +4,8 @@ use std::rc::Rc;
 use std::cell::RefCell;
 
 pub mod activate;
+#[cfg(unix)]
+pub mod readiness;
 
 pub use self::activate::{Activations, Activator, ActivateOnDrop, SyncActivator};
 
@@ -18,6 +20,8 @@ pub trait Schedule {
     /// The return value indicates whether `self` has outstanding
     /// work and would be upset if the computation terminated.
     fn schedule(&mut self) -> bool;
+    /// Reports the progress tracking state of the operators `self` hosts, if it is a scope.
+    fn frontiers(&self, _report: &mut crate::progress::DataflowFrontiers) { }
 }
 
 /// Methods for types which schedule fibers.
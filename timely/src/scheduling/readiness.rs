@@ -0,0 +1,337 @@
+//! Activation of operators by readiness of file descriptors.
+//!
+//! Operators that read from sockets, pipes, or eventfds can register their descriptors with the
+//! `Activations` of their worker, rather than polling them with `activate_after`. The worker checks
+//! registered descriptors at each step and activates the operators whose descriptors are readable.
+//! When it has nothing else to do, it blocks in `poll(2)` on the descriptors instead of parking the
+//! thread, and events that would unpark the thread interrupt the poll through a self-pipe.
+//!
+//! Readiness is level-triggered: an operator is activated at each step for as long as its
+//! descriptor remains readable, and it should read until the descriptor would block.
+//!
+//! A worker driven as a future cannot block in `poll(2)`. Instead, a helper thread polls duplicates
+//! of the registered descriptors, and wakes the task driving the worker once one is readable.
+
+use std::io;
+use std::os::unix::io::RawFd;
+use std::sync::Arc;
+use std::task::{Wake, Waker};
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+
+/// File descriptors registered for readiness, and the paths of the operators to activate.
+#[derive(Default)]
+pub struct Readiness {
+    sources: Vec<(RawFd, Vec<usize>)>,
+    pollfds: Vec<libc::pollfd>,
+    pipe: Option<Arc<SelfPipe>>,
+    watcher: Option<Watcher>,
+}
+
+impl Readiness {
+    /// Activates `path` whenever `fd` is readable.
+    ///
+    /// The descriptor should be deregistered before it is closed. Descriptors found to be
+    /// invalid are deregistered automatically.
+    pub fn register(&mut self, fd: RawFd, path: &[usize]) {
+        self.sources.push((fd, path.to_vec()));
+    }
+
+    /// Stops activating operators for `fd`.
+    pub fn deregister(&mut self, fd: RawFd) {
+        self.sources.retain(|(source, _)| *source != fd);
+    }
+
+    /// Indicates whether no descriptors are registered.
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// A waker that interrupts `poll`, created if absent.
+    pub fn waker(&mut self) -> io::Result<Waker> {
+        if self.pipe.is_none() {
+            self.pipe = Some(Arc::new(SelfPipe::new()?));
+        }
+        Ok(Waker::from(self.pipe.clone().expect("self-pipe unexpectedly absent")))
+    }
+
+    /// Waits up to `timeout` for a registered descriptor to become readable, or for the waker.
+    ///
+    /// A timeout of `None` waits indefinitely. Calls `activate` with the path of each readable
+    /// descriptor.
+    pub fn poll(&mut self, timeout: Option<Duration>, mut activate: impl FnMut(&[usize])) -> io::Result<()> {
+
+        self.pollfds.clear();
+        for (fd, _) in self.sources.iter() {
+            self.pollfds.push(libc::pollfd { fd: *fd, events: libc::POLLIN, revents: 0 });
+        }
+        if let Some(pipe) = self.pipe.as_ref() {
+            self.pollfds.push(libc::pollfd { fd: pipe.read, events: libc::POLLIN, revents: 0 });
+        }
+
+        let result = unsafe { libc::poll(self.pollfds.as_mut_ptr(), self.pollfds.len() as libc::nfds_t, poll_timeout(timeout)) };
+        if result < 0 {
+            let error = io::Error::last_os_error();
+            // Interruption by a signal is an ordinary wake-up.
+            return if error.kind() == io::ErrorKind::Interrupted { Ok(()) } else { Err(error) };
+        }
+
+        if let Some(pipe) = self.pipe.as_ref() {
+            if self.pollfds[self.sources.len()].revents != 0 {
+                pipe.drain();
+            }
+        }
+
+        let pollfds = &self.pollfds;
+        let mut index = 0;
+        self.sources.retain(|(_, path)| {
+            let revents = pollfds[index].revents;
+            index += 1;
+            if revents & libc::POLLNVAL != 0 { return false; }
+            if revents != 0 { activate(&path[..]); }
+            true
+        });
+
+        Ok(())
+    }
+
+    /// Wakes `waker` once a registered descriptor is readable, or after `timeout`, from a helper thread.
+    ///
+    /// Each call replaces the previous request. The helper thread polls duplicates of the
+    /// descriptors, so that they may be deregistered and closed at any time.
+    pub fn watch(&mut self, timeout: Option<Duration>, waker: &Waker) -> io::Result<()> {
+        if self.watcher.is_none() {
+            self.watcher = Some(Watcher::new()?);
+        }
+        let mut fds = Vec::with_capacity(self.sources.len());
+        for (fd, _) in self.sources.iter() {
+            let copy = unsafe { libc::fcntl(*fd, libc::F_DUPFD_CLOEXEC, 0) };
+            if copy < 0 {
+                let error = io::Error::last_os_error();
+                close_all(&fds);
+                return Err(error);
+            }
+            fds.push(copy);
+        }
+        self.watcher.as_ref().expect("watcher unexpectedly absent").watch(Watch { fds, timeout, waker: waker.clone() });
+        Ok(())
+    }
+}
+
+/// Round up, so that short delays do not become busy polls.
+fn poll_timeout(timeout: Option<Duration>) -> libc::c_int {
+    match timeout {
+        Some(duration) => {
+            let millis = duration.as_millis() + if duration.subsec_nanos() % 1_000_000 > 0 { 1 } else { 0 };
+            ::std::cmp::min(millis, libc::c_int::max_value() as u128) as libc::c_int
+        },
+        None => -1,
+    }
+}
+
+fn close_all(fds: &[RawFd]) {
+    for fd in fds.iter() {
+        unsafe { libc::close(*fd); }
+    }
+}
+
+/// A request to wake a task once a descriptor is readable or a timeout passes.
+struct Watch {
+    /// Duplicates of the descriptors to poll, owned by the request.
+    fds: Vec<RawFd>,
+    timeout: Option<Duration>,
+    waker: Waker,
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        close_all(&self.fds);
+    }
+}
+
+/// A helper thread that serves `Watch` requests, the most recent first.
+struct Watcher {
+    requests: Option<Sender<Watch>>,
+    pipe: Arc<SelfPipe>,
+}
+
+impl Watcher {
+    fn new() -> io::Result<Self> {
+        let pipe = Arc::new(SelfPipe::new()?);
+        let (requests, receiver) = crossbeam_channel::unbounded();
+        let thread_pipe = pipe.clone();
+        ::std::thread::Builder::new()
+            .name("timely:fd-watch".to_owned())
+            .spawn(move || Self::serve(receiver, thread_pipe))?;
+        Ok(Watcher { requests: Some(requests), pipe })
+    }
+    fn watch(&self, watch: Watch) {
+        // The thread only exits once the watcher is dropped, so the request cannot fail.
+        let _ = self.requests.as_ref().expect("watcher stopped").send(watch);
+        // Interrupt the poll of any previous request.
+        self.pipe.wake_by_ref();
+    }
+    fn serve(receiver: Receiver<Watch>, pipe: Arc<SelfPipe>) {
+        let mut current: Option<Watch> = None;
+        let mut pollfds = Vec::new();
+        loop {
+            // Drain wake-ups before receiving, so that requests sent later interrupt the poll.
+            pipe.drain();
+            loop {
+                match receiver.try_recv() {
+                    Ok(watch) => current = Some(watch),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            }
+            let watch = match current.take() {
+                Some(watch) => watch,
+                None => match receiver.recv() {
+                    Ok(watch) => { current = Some(watch); continue; },
+                    Err(_) => return,
+                },
+            };
+
+            pollfds.clear();
+            for fd in watch.fds.iter() {
+                pollfds.push(libc::pollfd { fd: *fd, events: libc::POLLIN, revents: 0 });
+            }
+            pollfds.push(libc::pollfd { fd: pipe.read, events: libc::POLLIN, revents: 0 });
+            let result = unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, poll_timeout(watch.timeout)) };
+
+            // A signal or a new request interrupts the poll; otherwise the task has work to do.
+            let interrupted = if result < 0 {
+                io::Error::last_os_error().kind() == io::ErrorKind::Interrupted
+            }
+            else {
+                pollfds[watch.fds.len()].revents != 0
+            };
+            if interrupted { current = Some(watch); }
+            else { watch.waker.wake_by_ref(); }
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        // Disconnect before interrupting, so that the thread exits.
+        self.requests.take();
+        self.pipe.wake_by_ref();
+    }
+}
+
+/// A non-blocking pipe, whose write end wakes a thread polling its read end.
+struct SelfPipe {
+    read: RawFd,
+    write: RawFd,
+}
+
+impl SelfPipe {
+    fn new() -> io::Result<Self> {
+        let mut fds = [0 as RawFd; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let pipe = SelfPipe { read: fds[0], write: fds[1] };
+        for fd in fds.iter() {
+            unsafe {
+                let flags = libc::fcntl(*fd, libc::F_GETFL);
+                if flags < 0 || libc::fcntl(*fd, libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                if libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+        Ok(pipe)
+    }
+    /// Reads all pending wake-ups.
+    fn drain(&self) {
+        let mut buffer = [0u8; 64];
+        while unsafe { libc::read(self.read, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) } > 0 { }
+    }
+}
+
+impl Wake for SelfPipe {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+    fn wake_by_ref(self: &Arc<Self>) {
+        // A full pipe already holds a pending wake-up, so failures can be ignored.
+        let byte = 1u8;
+        unsafe { libc::write(self.write, &byte as *const u8 as *const libc::c_void, 1); }
+    }
+}
+
+impl Drop for SelfPipe {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.read);
+            libc::close(self.write);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::Write;
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::UnixStream;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::task::{Wake, Waker};
+    use std::time::{Duration, Instant};
+
+    use super::Readiness;
+
+    struct Flag(AtomicBool);
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) { self.0.store(true, Ordering::SeqCst); }
+    }
+
+    /// Waits up to a second for `flag` to be raised.
+    fn raised(flag: &Flag) -> bool {
+        let start = Instant::now();
+        while !flag.0.load(Ordering::SeqCst) && start.elapsed() < Duration::from_secs(1) {
+            ::std::thread::sleep(Duration::from_millis(1));
+        }
+        flag.0.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn watch_wakes_on_readable() {
+        let (mut writer, reader) = UnixStream::pair().unwrap();
+        let mut readiness = Readiness::default();
+        readiness.register(reader.as_raw_fd(), &[0]);
+
+        let flag = Arc::new(Flag(AtomicBool::new(false)));
+        readiness.watch(None, &Waker::from(flag.clone())).unwrap();
+        ::std::thread::sleep(Duration::from_millis(20));
+        assert!(!flag.0.load(Ordering::SeqCst));
+
+        writer.write_all(b"ready").unwrap();
+        assert!(raised(&flag));
+    }
+
+    #[test]
+    fn watch_replaces_requests() {
+        let (_writer, reader) = UnixStream::pair().unwrap();
+        let mut readiness = Readiness::default();
+        readiness.register(reader.as_raw_fd(), &[0]);
+
+        // The first request waits indefinitely, and is replaced by one with a timeout.
+        let first = Arc::new(Flag(AtomicBool::new(false)));
+        readiness.watch(None, &Waker::from(first.clone())).unwrap();
+        let second = Arc::new(Flag(AtomicBool::new(false)));
+        readiness.watch(Some(Duration::from_millis(10)), &Waker::from(second.clone())).unwrap();
+        assert!(raised(&second));
+        assert!(!first.0.load(Ordering::SeqCst));
+
+        // Descriptors may be closed while watched, as the helper polls duplicates.
+        readiness.deregister(reader.as_raw_fd());
+        drop(reader);
+    }
+}
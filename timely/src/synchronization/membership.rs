@@ -0,0 +1,292 @@
+//! Changes to the set of workers that receive exchanged data.
+//!
+//! The workers of a computation are fixed once communication is initialized. A `Membership`
+//! describes which of them are *active*, receiving data from `ElasticExchange` pacts, and how
+//! this changes at agreed timestamps. A computation can be provisioned with spare processes,
+//! which are admitted by making their workers active, and workers can be retired by making
+//! them inactive; retired workers continue to participate in progress tracking.
+//!
+//! Processes cannot join or leave a running computation: the communication layer connects all
+//! processes before any dataflow runs, and progress tracking expects every worker to take part.
+//! Elasticity is therefore limited to the workers of the processes started with the computation.
+//!
+//! Changes are agreed through a `Sequencer`: a coordinating worker, usually worker zero, proposes
+//! each `Change` with `Membership::propose`, and all workers apply the agreed changes in sequence
+//! order with `follow`. The workers active at a time result from the changes at times not after it,
+//! taken in order of their times whatever order they were applied in: a `Set` replaces the active
+//! workers, and admissions and retirements modify them. A change must be applied by every worker before it
+//! routes any data at or beyond the change's time, which is most easily ensured by choosing times
+//! beyond the frontiers of the affected inputs; a worker panics if it applies a change too late.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::time::Instant;
+//!
+//! use timely::dataflow::InputHandle;
+//! use timely::dataflow::channels::pact::ElasticExchange;
+//! use timely::dataflow::operators::{Input, Operator, Inspect, Probe};
+//! use timely::synchronization::{Sequencer, Membership};
+//! use timely::synchronization::membership::Change;
+//!
+//! timely::execute(timely::Configuration::Process(4), |worker, _| {
+//!
+//!     // initially only workers zero and one receive data.
+//!     let membership = Membership::<u64>::new(worker.peers(), vec![0, 1]);
+//!     let mut sequencer = Sequencer::new(worker, Instant::now());
+//!
+//!     let mut input = InputHandle::new();
+//!     let route = membership.clone();
+//!     let probe = worker.dataflow(|scope| {
+//!         scope.input_from(&mut input)
+//!              .unary(ElasticExchange::new(|x: &u64| *x, route), "Route", |_,_| |input, output| {
+//!                  input.for_each(|time, data| output.session(&time).give_vec(&mut data.replace(Vec::new())));
+//!              })
+//!              .inspect(|x| println!("seen: {:?}", x))
+//!              .probe()
+//!     });
+//!
+//!     // the first worker admits workers two and three from round 5 on.
+//!     if worker.index() == 0 {
+//!         membership.propose(&mut sequencer, 5, Change::Admit(vec![2, 3]));
+//!     }
+//!
+//!     for round in 0 .. 10 {
+//!         // wait until the change has been agreed before routing data for round 5.
+//!         while round == 5 && membership.peers_at(&round) < 4 {
+//!             worker.step();
+//!             membership.follow(&mut sequencer);
+//!         }
+//!         input.send(round);
+//!         input.advance_to(round + 1);
+//!         while probe.less_than(input.time()) {
+//!             worker.step();
+//!         }
+//!     }
+//! }).unwrap();
+//! ```
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use crate::progress::Timestamp;
+use crate::synchronization::Sequencer;
+use crate::ExchangeData;
+
+/// A change to the active workers, from an agreed time on.
+#[derive(Debug, Clone, PartialEq, Eq, Abomonation, Serialize, Deserialize)]
+pub enum Change {
+    /// Makes exactly the listed workers active.
+    Set(Vec<usize>),
+    /// Makes the listed workers active, in addition to those already active.
+    Admit(Vec<usize>),
+    /// Makes the listed workers inactive.
+    Retire(Vec<usize>),
+}
+
+/// The sets of active workers, as they change over time.
+///
+/// Clones share the same changes.
+pub struct Membership<T> {
+    inner: Rc<RefCell<MembershipInner<T>>>,
+}
+
+struct MembershipInner<T> {
+    // The number of workers in the computation.
+    peers: usize,
+    // Changes in the order applied, with the times from which they apply.
+    changes: Vec<(T, Change)>,
+    // The maximal times at which data have been routed.
+    routed: Vec<T>,
+    // Incremented with each change, to invalidate cached routing decisions.
+    version: usize,
+}
+
+impl<T> Clone for Membership<T> {
+    fn clone(&self) -> Self {
+        Membership { inner: self.inner.clone() }
+    }
+}
+
+impl<T: Timestamp> Membership<T> {
+    /// Creates a membership of `peers` workers, in which the workers `active` are active from the minimum time.
+    pub fn new(peers: usize, active: Vec<usize>) -> Self {
+        let membership = Membership {
+            inner: Rc::new(RefCell::new(MembershipInner {
+                peers,
+                changes: Vec::new(),
+                routed: Vec::new(),
+                version: 0,
+            }))
+        };
+        membership.rescale(T::minimum(), active);
+        membership
+    }
+
+    /// The number of workers in the computation.
+    pub fn peers(&self) -> usize {
+        self.inner.borrow().peers
+    }
+
+    /// Makes exactly the workers `active` active from `time` on.
+    ///
+    /// This panics under the same conditions as `apply`.
+    pub fn rescale(&self, time: T, active: Vec<usize>) {
+        self.apply(time, Change::Set(active));
+    }
+
+    /// Applies `change` from `time` on.
+    ///
+    /// This panics if `change` lists a worker beyond `peers()`, if it would leave no workers active at
+    /// some time, or if data have already been routed at or beyond `time`.
+    pub fn apply(&self, time: T, change: Change) {
+        {
+            let mut inner = self.inner.borrow_mut();
+            let workers = match &change {
+                Change::Set(workers) | Change::Admit(workers) | Change::Retire(workers) => workers,
+            };
+            assert!(workers.iter().all(|&index| index < inner.peers), "workers {:?} exceed the {} peers", workers, inner.peers);
+            if let Some(routed) = inner.routed.iter().find(|routed| time.less_equal(routed)) {
+                panic!("membership change at {:?} applied after data were routed at {:?}", time, routed);
+            }
+            inner.changes.push((time.clone(), change));
+            inner.version += 1;
+        }
+        // The change may affect the workers active at its time and at the times of later changes.
+        let times: Vec<T> = self.inner.borrow().changes.iter().map(|(start, _)| start.clone()).filter(|start| time.less_equal(start)).collect();
+        for start in times.iter() {
+            if self.active_at(start).is_empty() {
+                let mut inner = self.inner.borrow_mut();
+                inner.changes.pop();
+                inner.version += 1;
+                panic!("membership change at {:?} leaves no active workers at {:?}", time, start);
+            }
+        }
+    }
+
+    /// Proposes that all workers apply `change` from `time` on.
+    ///
+    /// The change takes effect once it is agreed and applied with `follow`.
+    pub fn propose(&self, sequencer: &mut Sequencer<(T, Change)>, time: T, change: Change) where T: ExchangeData {
+        sequencer.push((time, change));
+    }
+
+    /// Applies the changes a sequencer has agreed upon, returning the number applied.
+    pub fn follow(&self, sequencer: &mut Sequencer<(T, Change)>) -> usize {
+        let mut applied = 0;
+        for (time, change) in sequencer {
+            self.apply(time, change);
+            applied += 1;
+        }
+        applied
+    }
+
+    /// The workers active at `time`, in increasing order.
+    ///
+    /// These result from the changes with times less or equal to `time`, applied in order of their
+    /// times, and in the order they were applied for equal times. This panics if the times of two
+    /// such changes are incomparable.
+    pub fn active_at(&self, time: &T) -> Vec<usize> {
+        let inner = self.inner.borrow();
+        let mut changes: Vec<&(T, Change)> = inner.changes.iter().filter(|(start, _)| start.less_equal(time)).collect();
+        changes.sort_by(|(a, _), (b, _)| {
+            if a.eq(b) { ::std::cmp::Ordering::Equal }
+            else if a.less_than(b) { ::std::cmp::Ordering::Less }
+            else if b.less_than(a) { ::std::cmp::Ordering::Greater }
+            else { panic!("incomparable membership changes at {:?} and {:?}", a, b) }
+        });
+        let mut active = Vec::new();
+        for (_, change) in changes {
+            match change {
+                Change::Set(workers) => { active = workers.clone(); },
+                Change::Admit(workers) => { active.extend(workers.iter().cloned()); },
+                Change::Retire(workers) => { active.retain(|index| !workers.contains(index)); },
+            }
+            active.sort();
+            active.dedup();
+        }
+        active
+    }
+
+    /// The number of workers active at `time`.
+    pub fn peers_at(&self, time: &T) -> usize {
+        self.active_at(time).len()
+    }
+
+    /// Records that data at `time` have been routed, so that later changes at or before `time` are refused.
+    pub(crate) fn routed(&self, time: &T) {
+        let mut inner = self.inner.borrow_mut();
+        if !inner.routed.iter().any(|routed| time.less_equal(routed)) {
+            inner.routed.retain(|routed| !routed.less_equal(time));
+            inner.routed.push(time.clone());
+        }
+    }
+
+    /// A counter incremented by each change.
+    pub fn version(&self) -> usize {
+        self.inner.borrow().version
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Change, Membership};
+
+    #[test]
+    fn ordered_by_time() {
+        let membership = Membership::<u64>::new(4, vec![0]);
+        membership.rescale(10, vec![0, 1, 2]);
+        membership.rescale(5, vec![1, 0]);
+        assert_eq!(membership.active_at(&0), vec![0]);
+        assert_eq!(membership.active_at(&7), vec![0, 1]);
+        assert_eq!(membership.active_at(&12), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn admit_and_retire() {
+        let membership = Membership::<u64>::new(4, vec![0, 1]);
+        membership.apply(5, Change::Admit(vec![2, 3]));
+        membership.apply(8, Change::Retire(vec![0]));
+        assert_eq!(membership.active_at(&6), vec![0, 1, 2, 3]);
+        assert_eq!(membership.active_at(&9), vec![1, 2, 3]);
+        assert_eq!(membership.peers_at(&9), 3);
+    }
+
+    #[test]
+    fn applied_out_of_order() {
+        let membership = Membership::<u64>::new(4, vec![0, 1]);
+        membership.apply(8, Change::Retire(vec![0]));
+        membership.apply(5, Change::Admit(vec![2, 3]));
+        assert_eq!(membership.active_at(&6), vec![0, 1, 2, 3]);
+        assert_eq!(membership.active_at(&9), vec![1, 2, 3]);
+
+        // a set at an intermediate time overrides earlier changes, but not later ones.
+        membership.rescale(7, vec![0, 3]);
+        assert_eq!(membership.active_at(&7), vec![0, 3]);
+        assert_eq!(membership.active_at(&9), vec![3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "no active workers")]
+    fn none_active() {
+        let membership = Membership::<u64>::new(4, vec![0, 1]);
+        membership.apply(8, Change::Retire(vec![0]));
+        membership.apply(5, Change::Retire(vec![1]));
+    }
+
+    #[test]
+    #[should_panic(expected = "exceed")]
+    fn beyond_peers() {
+        Membership::<u64>::new(2, vec![0, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "after data were routed")]
+    fn too_late() {
+        let membership = Membership::<u64>::new(2, vec![0]);
+        membership.routed(&3);
+        membership.rescale(4, vec![0, 1]);
+        membership.rescale(3, vec![1]);
+    }
+}
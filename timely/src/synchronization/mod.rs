@@ -2,6 +2,8 @@
 
 pub mod barrier;
 pub mod sequence;
+pub mod membership;
 
 pub use self::barrier::Barrier;
-pub use self::sequence::Sequencer;
\ No newline at end of file
+pub use self::sequence::Sequencer;
+pub use self::membership::Membership;
\ No newline at end of file
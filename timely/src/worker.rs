@@ -4,11 +4,17 @@ use std::rc::Rc;
 use std::cell::{RefCell, RefMut};
 use std::any::Any;
 use std::time::{Instant, Duration};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::collections::hash_map::Entry;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
 use crate::communication::{Allocate, Data, Push, Pull};
+use crate::communication::codec::{Codec, DefaultCodec};
 use crate::communication::allocator::thread::{ThreadPusher, ThreadPuller};
+use crate::communication::allocator::zero_copy::compression::Compression;
+use crate::communication::buzzer::WakerSlot;
 use crate::scheduling::{Schedule, Scheduler, Activations};
 use crate::progress::timestamp::{Refines};
 use crate::progress::SubgraphBuilder;
@@ -34,7 +40,18 @@ pub trait AsWorker: Scheduler {
     /// scheduled in response to the receipt of records on the channel.
     /// Most commonly, this would be the address of the *target* of the
     /// channel.
-    fn allocate<T: Data>(&mut self, identifier: usize, address: &[usize]) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>);
+    fn allocate<T: Data>(&mut self, identifier: usize, address: &[usize]) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>) {
+        self.allocate_with_codec(identifier, address, DefaultCodec::default())
+    }
+    /// Allocates a new channel whose data are serialized with `codec`.
+    ///
+    /// All workers must supply the same codec for the same channel.
+    fn allocate_with_codec<T: Send+Sync+Any+'static, C: Codec<T>>(&mut self, identifier: usize, address: &[usize], codec: C) -> (Vec<Box<dyn Push<Message<T>>>>, Box<dyn Pull<Message<T>>>);
+    /// Sets the compression of data sent to other processes on the channel `identifier`.
+    ///
+    /// This must be called before the channel is allocated, and is ignored by allocators that do
+    /// not send data between processes. The default implementation ignores the setting.
+    fn set_channel_compression(&mut self, _identifier: usize, _compression: Compression) { }
     /// Constructs a pipeline channel from the worker to itself.
     ///
     /// By default this method uses the native channel allocation mechanism, but the expectation is
@@ -47,6 +64,8 @@ pub trait AsWorker: Scheduler {
     fn log_register(&self) -> ::std::cell::RefMut<crate::logging_core::Registry<crate::logging::WorkerIdentifier>>;
     /// Provides access to the timely logging stream.
     fn logging(&self) -> Option<crate::logging::TimelyLogger> { self.log_register().get("timely") }
+    /// Provides access to the runtime statistics of operators.
+    fn stats_registry(&self) -> ::std::cell::RefMut<crate::progress::stats::StatsRegistry>;
 }
 
 /// A `Worker` is the entry point to a timely dataflow computation. It wraps a `Allocate`,
@@ -67,17 +86,24 @@ pub struct Worker<A: Allocate> {
     // Temporary storage for channel identifiers during dataflow construction.
     // These are then associated with a dataflow once constructed.
     temp_channel_ids: Rc<RefCell<Vec<usize>>>,
+
+    stats: Rc<RefCell<crate::progress::stats::StatsRegistry>>,
+
+    watchdog: Rc<RefCell<Option<Watchdog>>>,
 }
 
 impl<A: Allocate> AsWorker for Worker<A> {
     fn index(&self) -> usize { self.allocator.borrow().index() }
     fn peers(&self) -> usize { self.allocator.borrow().peers() }
-    fn allocate<D: Data>(&mut self, identifier: usize, address: &[usize]) -> (Vec<Box<dyn Push<Message<D>>>>, Box<dyn Pull<Message<D>>>) {
+    fn allocate_with_codec<D: Send+Sync+Any+'static, C: Codec<D>>(&mut self, identifier: usize, address: &[usize], codec: C) -> (Vec<Box<dyn Push<Message<D>>>>, Box<dyn Pull<Message<D>>>) {
         if address.len() == 0 { panic!("Unacceptable address: Length zero"); }
         let mut paths = self.paths.borrow_mut();
         paths.insert(identifier, address.to_vec());
         self.temp_channel_ids.borrow_mut().push(identifier);
-        self.allocator.borrow_mut().allocate(identifier)
+        self.allocator.borrow_mut().allocate_with_codec(identifier, codec)
+    }
+    fn set_channel_compression(&mut self, identifier: usize, compression: Compression) {
+        self.allocator.borrow_mut().set_channel_compression(identifier, compression)
     }
     fn pipeline<T: 'static>(&mut self, identifier: usize, address: &[usize]) -> (ThreadPusher<Message<T>>, ThreadPuller<Message<T>>) {
         if address.len() == 0 { panic!("Unacceptable address: Length zero"); }
@@ -91,6 +117,9 @@ impl<A: Allocate> AsWorker for Worker<A> {
     fn log_register(&self) -> RefMut<crate::logging_core::Registry<crate::logging::WorkerIdentifier>> {
         self.log_register()
     }
+    fn stats_registry(&self) -> RefMut<crate::progress::stats::StatsRegistry> {
+        self.stats.borrow_mut()
+    }
 }
 
 impl<A: Allocate> Scheduler for Worker<A> {
@@ -115,6 +144,8 @@ impl<A: Allocate> Worker<A> {
             activations: Rc::new(RefCell::new(Activations::new(now.clone()))),
             active_dataflows: Default::default(),
             temp_channel_ids: Default::default(),
+            stats: Default::default(),
+            watchdog: Default::default(),
         }
     }
 
@@ -152,6 +183,8 @@ impl<A: Allocate> Worker<A> {
     /// there is work to perform or until this timeout expires. A value of
     /// `None` allows the worker to park indefinitely, whereas a value of
     /// `Some(Duration::new(0, 0))` will return without parking the thread.
+    /// If file descriptors are registered with the worker's activations, the
+    /// worker blocks in `poll` on them rather than parking the thread.
     ///
     /// # Examples
     ///
@@ -171,6 +204,17 @@ impl<A: Allocate> Worker<A> {
     /// });
     /// ```
     pub fn step_or_park(&mut self, duration: Option<Duration>) -> bool {
+        // Events that would unpark the thread must interrupt `poll` if we block in it.
+        #[cfg(unix)]
+        let polling = self.activations.borrow().has_fds() && duration != Some(Duration::new(0, 0));
+        #[cfg(unix)]
+        {
+            if polling {
+                let waker = self.activations.borrow_mut().fd_waker().expect("failed to create self-pipe");
+                WakerSlot::current().register_poll(&waker);
+            }
+        }
+
         {   // Process channel events. Activate responders.
             println!("START STEP OR PARK");
             let mut allocator = self.allocator.borrow_mut();
@@ -193,6 +237,17 @@ impl<A: Allocate> Worker<A> {
             }
         }
 
+        // Activate operators whose file descriptors are readable.
+        #[cfg(unix)]
+        {
+            if self.activations.borrow().has_fds() {
+                self.activations
+                    .borrow_mut()
+                    .poll_ready(Some(Duration::new(0, 0)))
+                    .expect("failed to poll file descriptors");
+            }
+        }
+
         // Organize activations.
         //println!("HERE1");
         self.activations
@@ -208,6 +263,11 @@ impl<A: Allocate> Worker<A> {
             (Some(x), Some(y)) => Some(std::cmp::min(x, y)),
             (x, y) => x.or(y),
         };
+        // Wake up in time for the watchdog, if there is one.
+        let delay = match (delay, self.watchdog.borrow().as_ref().map(|w| w.due())) {
+            (Some(x), Some(y)) => Some(std::cmp::min(x, y)),
+            (x, y) => x.or(y),
+        };
         println!("dataflow is empty {}", self.dataflows.borrow().is_empty());
 
         if !self.dataflows.borrow().is_empty() && delay != Some(Duration::new(0, 0)) {
@@ -217,6 +277,23 @@ impl<A: Allocate> Worker<A> {
             self.logging().as_mut().map(|l| l.log(crate::logging::ParkEvent::park(delay)));
             self.logging.borrow_mut().flush();
 
+            #[cfg(unix)]
+            {
+                if polling {
+                    if self.allocator.borrow().events().borrow().is_empty() {
+                        self.activations
+                            .borrow_mut()
+                            .poll_ready(delay)
+                            .expect("failed to poll file descriptors");
+                    }
+                }
+                else {
+                    self.allocator
+                        .borrow()
+                        .await_events(delay);
+                }
+            }
+            #[cfg(not(unix))]
             self.allocator
                 .borrow()
                 .await_events(delay);
@@ -252,6 +329,15 @@ impl<A: Allocate> Worker<A> {
             println!("HERE6");
         }
 
+        self.watch();
+
+        #[cfg(unix)]
+        {
+            if polling {
+                WakerSlot::current().clear_poll();
+            }
+        }
+
         // Clean up, indicate if dataflows remain.
         self.logging.borrow_mut().flush();
         self.allocator.borrow_mut().release();
@@ -283,6 +369,60 @@ impl<A: Allocate> Worker<A> {
         while func() { self.step(); }
     }
 
+    /// Returns a future that steps the worker as long as `func` evaluates to true.
+    ///
+    /// Rather than parking the thread, the future yields to other tasks of its executor
+    /// whenever the worker has no immediate work, and is woken by the same events that
+    /// would unpark the worker thread: data from other workers, `SyncActivator`s, and
+    /// delayed activations. The future must be polled on the worker's thread, which holds
+    /// for any executor that runs tasks on the current thread.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::future::Future;
+    /// use std::sync::Arc;
+    /// use std::task::{Context, Poll, Wake};
+    ///
+    /// // A minimal executor, which parks the thread until its task is woken.
+    /// struct Unpark(std::thread::Thread);
+    /// impl Wake for Unpark {
+    ///     fn wake(self: Arc<Self>) { self.0.unpark(); }
+    /// }
+    /// fn block_on<F: Future>(future: F) -> F::Output {
+    ///     let mut future = Box::pin(future);
+    ///     let waker = Arc::new(Unpark(std::thread::current())).into();
+    ///     let mut context = Context::from_waker(&waker);
+    ///     loop {
+    ///         match future.as_mut().poll(&mut context) {
+    ///             Poll::Ready(output) => return output,
+    ///             Poll::Pending => std::thread::park(),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// timely::execute_from_args(::std::env::args(), |worker, _| {
+    ///
+    ///     use timely::dataflow::operators::{ToStream, Inspect, Probe};
+    ///
+    ///     let probe =
+    ///     worker.dataflow::<usize,_,_>(|scope| {
+    ///         (0 .. 10)
+    ///             .to_stream(scope)
+    ///             .inspect(|x| println!("{:?}", x))
+    ///             .probe()
+    ///     });
+    ///
+    ///     block_on(worker.step_while_async(|| probe.less_than(&0)));
+    /// });
+    /// ```
+    pub fn step_while_async<F: FnMut() -> bool + Unpin>(&mut self, func: F) -> StepWhile<'_, A, F> {
+        StepWhile {
+            worker: self,
+            func,
+        }
+    }
+
     /// The index of the worker out of its peers.
     ///
     /// # Examples
@@ -330,6 +470,54 @@ impl<A: Allocate> Worker<A> {
     /// ```
     pub fn timer(&self) -> Instant { self.timer }
 
+    /// Communication metrics of this worker's process, if its allocator maintains them.
+    ///
+    /// Metrics are maintained by allocators that send data between processes, and are
+    /// shared by all workers of the process.
+    ///
+    /// # Examples
+    /// ```
+    /// timely::execute_from_args(::std::env::args(), |worker, _| {
+    ///
+    ///     if let Some(metrics) = worker.metrics() {
+    ///         for (key, traffic) in metrics.traffic_snapshot() {
+    ///             println!("{:?}\t{:?}", key, traffic);
+    ///         }
+    ///         if worker.index() == 0 {
+    ///             metrics.export_to_file("timely-metrics.prom").expect("failed to export metrics");
+    ///         }
+    ///     }
+    /// });
+    /// ```
+    pub fn metrics(&self) -> Option<crate::communication::metrics::Metrics> {
+        self.allocator.borrow().metrics().cloned()
+    }
+
+    /// Runtime statistics of this worker's operators, by operator address.
+    ///
+    /// Each scope counts the invocations of its operators, the time they were scheduled for, and
+    /// the records they consumed and produced. Operators of dropped dataflows are not reported.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::operators::{ToStream, Inspect};
+    ///
+    /// timely::execute_from_args(::std::env::args(), |worker, _| {
+    ///     worker.dataflow::<u64,_,_>(|scope| {
+    ///         (0 .. 10).to_stream(scope).inspect(|x| println!("seen: {:?}", x));
+    ///     });
+    ///     while worker.step() { }
+    ///     for (addr, stats) in worker.operator_stats() {
+    ///         println!("{:?}\t{}\t{} invocations, {:?} total, {:?} max, {} in, {} out",
+    ///             addr, stats.name, stats.invocations, stats.total_time, stats.max_time,
+    ///             stats.records_consumed, stats.records_produced);
+    ///     }
+    /// }).unwrap();
+    /// ```
+    pub fn operator_stats(&self) -> ::std::collections::BTreeMap<Vec<usize>, crate::progress::OperatorStats> {
+        self.stats.borrow_mut().snapshot()
+    }
+
     /// Allocate a new worker-unique identifier.
     ///
     /// This method is public, though it is not expected to be widely used outside
@@ -443,9 +631,12 @@ impl<A: Allocate> Worker<A> {
         let mut temp_channel_ids = self.temp_channel_ids.borrow_mut();
         let channel_ids = temp_channel_ids.drain(..).collect::<Vec<_>>();
 
+        let stats = self.stats.borrow_mut().register(identifier, operator.path().to_vec(), operator.name().to_string());
+
         let wrapper = Wrapper {
             logging,
             identifier,
+            stats,
             operate: Some(Box::new(operator)),
             resources: Some(Box::new(resources)),
             channel_ids,
@@ -484,6 +675,113 @@ impl<A: Allocate> Worker<A> {
         self.dataflows.borrow().keys().cloned().collect()
     }
 
+    /// The progress tracking state of the operators of the dataflow `index`, if it is installed.
+    ///
+    /// The report lists each operator's input frontiers, the messages still to reach it, and the
+    /// capabilities it holds. To learn why a frontier is not advancing, `holders` walks back from
+    /// an operator input to the pointstamps upstream of it.
+    ///
+    /// # Examples
+    /// ```
+    /// use timely::dataflow::InputHandle;
+    /// use timely::dataflow::operators::{Input, Probe};
+    ///
+    /// timely::execute_from_args(::std::env::args(), |worker, _| {
+    ///     let mut input = InputHandle::<u64, u64>::new();
+    ///     let probe = worker.dataflow(|scope| scope.input_from(&mut input).probe());
+    ///     input.send(0);
+    ///     input.advance_to(1);
+    ///     worker.step_while(|| probe.less_than(input.time()));
+    ///
+    ///     for index in worker.installed_dataflows() {
+    ///         let report = worker.dataflow_frontiers(index).unwrap();
+    ///         for operator in report.operators().values().filter(|op| op.name == "Probe") {
+    ///             println!("{:?} is at {:?}", operator.addr, operator.input_frontiers[0]);
+    ///             for holder in report.holders(&operator.addr, 0) {
+    ///                 println!("  held by {} {:?} at {:?}", holder.name, holder.port, holder.pointstamps);
+    ///             }
+    ///         }
+    ///     }
+    /// }).unwrap();
+    /// ```
+    pub fn dataflow_frontiers(&self, index: usize) -> Option<crate::progress::DataflowFrontiers> {
+        let dataflows = self.dataflows.borrow();
+        let operate = dataflows.get(&index)?.operate.as_ref()?;
+        let mut report = crate::progress::DataflowFrontiers::new();
+        operate.frontiers(&mut report);
+        Some(report)
+    }
+
+    /// Reports dataflows whose frontiers have not advanced for `period`, or stops doing so for `None`.
+    ///
+    /// While enabled, `step_or_park` compares the input frontiers of the installed dataflows once
+    /// per `period`, waking up to do so if it would park for longer. If none has advanced, it logs
+    /// a `StallEvent` listing the minimal outstanding pointstamps and the operators at which they
+    /// are outstanding, or prints it to standard error if there is no "timely" logger.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use timely::logging::TimelyEvent;
+    ///
+    /// timely::execute_from_args(::std::env::args(), |worker, _| {
+    ///     worker.log_register().insert::<TimelyEvent,_>("timely", |_time, data| {
+    ///         for (_, _, event) in data.iter() {
+    ///             if let TimelyEvent::Stall(stall) = event {
+    ///                 println!("stalled for {:?}: {:?}", stall.elapsed, stall.pointstamps);
+    ///             }
+    ///         }
+    ///     });
+    ///     worker.set_stall_timeout(Some(Duration::from_secs(10)));
+    /// }).unwrap();
+    /// ```
+    pub fn set_stall_timeout(&mut self, period: Option<Duration>) {
+        *self.watchdog.borrow_mut() = period.map(Watchdog::new);
+    }
+
+    /// Compares frontiers for the watchdog, if it is due, and reports a stall.
+    fn watch(&mut self) {
+        let mut watchdog = self.watchdog.borrow_mut();
+        let watchdog = match watchdog.as_mut() {
+            Some(watchdog) if watchdog.due() == Duration::new(0, 0) => watchdog,
+            _ => return,
+        };
+        watchdog.checked = Instant::now();
+
+        let reports = self.installed_dataflows()
+            .into_iter()
+            .filter_map(|index| self.dataflow_frontiers(index))
+            .collect::<Vec<_>>();
+        let frontiers = reports
+            .iter()
+            .flat_map(|report| report.operators().values())
+            .map(|operator| (operator.addr.clone(), operator.input_frontiers.clone()))
+            .collect::<BTreeMap<_,_>>();
+
+        if reports.is_empty() || frontiers != watchdog.frontiers {
+            watchdog.frontiers = frontiers;
+            watchdog.advanced = watchdog.checked;
+            return;
+        }
+
+        let mut pointstamps = Vec::new();
+        for operator in reports.iter().flat_map(|report| report.operators().values()) {
+            let ports =
+            operator.messages.iter().enumerate().map(|(port, p)| (crate::progress::Port::Target(port), p))
+                .chain(operator.capabilities.iter().enumerate().map(|(port, p)| (crate::progress::Port::Source(port), p)));
+            for (port, outstanding) in ports {
+                for pointstamp in outstanding.iter().filter(|pointstamp| pointstamp.minimal) {
+                    pointstamps.push((operator.addr.clone(), operator.name.clone(), port, pointstamp.time.clone(), pointstamp.count));
+                }
+            }
+        }
+        let stall = crate::logging::StallEvent { elapsed: watchdog.advanced.elapsed(), pointstamps };
+        match self.logging() {
+            Some(logger) => logger.log(stall),
+            None => eprintln!("timely: worker {} frontiers stalled: {:?}", self.index(), stall),
+        }
+    }
+
     // Acquire a new distinct dataflow identifier.
     fn allocate_dataflow_index(&mut self) -> usize {
         *self.dataflow_counter.borrow_mut() += 1;
@@ -506,13 +804,76 @@ impl<A: Allocate> Clone for Worker<A> {
             activations: self.activations.clone(),
             active_dataflows: Vec::new(),
             temp_channel_ids: self.temp_channel_ids.clone(),
+            stats: self.stats.clone(),
+            watchdog: self.watchdog.clone(),
+        }
+    }
+}
+
+/// A future that steps a worker while a condition holds.
+///
+/// Returned by `Worker::step_while_async`. Each poll performs at most one step.
+pub struct StepWhile<'a, A: Allocate, F: FnMut() -> bool + Unpin> {
+    worker: &'a mut Worker<A>,
+    func: F,
+}
+
+impl<'a, A: Allocate, F: FnMut() -> bool + Unpin> Future for StepWhile<'a, A, F> {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, context: &mut Context) -> Poll<()> {
+
+        let this = self.get_mut();
+
+        // Register before stepping, so that events during the step wake the task.
+        let slot = WakerSlot::current();
+        slot.register(context.waker());
+
+        if (this.func)() { this.worker.step_or_park(Some(Duration::new(0, 0))); }
+        if !(this.func)() {
+            slot.clear();
+            return Poll::Ready(());
+        }
+
+        // Yield, and arrange to be polled again when there is work to perform: at once, or from the
+        // readiness helper thread once a registered descriptor is readable or a delayed activation is due.
+        let mut activations = this.worker.activations.borrow_mut();
+        match activations.empty_for() {
+            Some(delay) if delay == Duration::new(0, 0) => context.waker().wake_by_ref(),
+            delay if delay.is_some() || activations.has_fds() => {
+                activations.watch_fds(delay, context.waker()).expect("failed to watch file descriptors");
+            },
+            _ => { },
         }
+        Poll::Pending
+    }
+}
+
+/// Watches the frontiers of a worker's dataflows for a lack of progress.
+struct Watchdog {
+    period: Duration,
+    /// When frontiers were last compared.
+    checked: Instant,
+    /// When frontiers were last seen to change.
+    advanced: Instant,
+    /// The input frontiers of operators, by address.
+    frontiers: BTreeMap<Vec<usize>, Vec<Vec<String>>>,
+}
+
+impl Watchdog {
+    fn new(period: Duration) -> Self {
+        let now = Instant::now();
+        Watchdog { period, checked: now, advanced: now, frontiers: BTreeMap::new() }
+    }
+    /// The time until frontiers are next to be compared.
+    fn due(&self) -> Duration {
+        self.period.checked_sub(self.checked.elapsed()).unwrap_or(Duration::new(0, 0))
     }
 }
 
 struct Wrapper {
     logging: Option<TimelyLogger>,
     identifier: usize,
+    stats: Rc<RefCell<crate::progress::OperatorStats>>,
     operate: Option<Box<dyn Schedule>>,
     resources: Option<Box<dyn Any>>,
     channel_ids: Vec<usize>,
@@ -531,7 +892,9 @@ impl Wrapper {
             l.log(crate::logging::ScheduleEvent::start(self.identifier));
         }
 
+        let start = Instant::now();
         let incomplete = self.operate.as_mut().map(|op| op.schedule()).unwrap_or(false);
+        self.stats.borrow_mut().record(start.elapsed(), 0, 0);
         if !incomplete {
             self.operate = None;
             self.resources = None;